@@ -1,21 +1,25 @@
+use crate::money::Money;
 use chrono::{Local, NaiveDate};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::{error::Error, fmt};
 
+#[derive(Clone)]
 pub struct Account {
     name: String,
-    balance: f32,
-    transactions: HashMap<String, Transaction>,
+    balance: Money,
+    transactions: HashMap<u32, Transaction>,
+    next_id: u32,
 }
 
 impl Account {
     fn new(name: &str) -> Account {
         Account {
             name: String::from(name),
-            balance: 0.0,
+            balance: Money::zero(),
             transactions: HashMap::new(),
+            next_id: 1,
         }
     }
 
@@ -23,6 +27,9 @@ impl Account {
         if name.trim_end_matches('\n').is_empty() {
             return Err(Box::from("Account must have a name"));
         }
+        if name.starts_with('!') {
+            return Err(Box::from("Account names cannot start with '!'"));
+        }
         Ok(Self::new(name))
     }
 
@@ -30,58 +37,78 @@ impl Account {
         &self.name
     }
 
-    pub fn _balance(&self) -> &f32 {
+    pub fn balance(&self) -> &Money {
         &self.balance
     }
 
-    pub fn transactions(&self) -> &HashMap<String, Transaction> {
+    pub fn transactions(&self) -> &HashMap<u32, Transaction> {
         &self.transactions
     }
 
-    pub fn add_new_transaction(&mut self, label: &str, amount: f32) -> Result<(), Box<dyn Error>> {
-        let today = NaiveDate::from(Local::now().naive_local());
-        self.check_transaction_not_exists(&format!("{}-{}-{}", today, label, amount))?;
+    /// The ID the next transaction added will get
+    pub fn next_id(&self) -> u32 {
+        self.next_id
+    }
 
-        self.balance += amount;
-        self.transactions.insert(
-            format!("{}-{}-{}", today, label, amount),
-            Transaction::new(label, amount, today)?,
-        );
-        Ok(())
+    /// Sets the ID the next transaction added will get, as read back from a file, so that
+    /// the IDs of removed transactions are not given out again. It never goes below one
+    /// past the highest ID present.
+    pub fn set_next_id(&mut self, next_id: u32) {
+        self.next_id = self.next_id.max(next_id);
     }
 
+    /// Adds a transaction dated today, returning its ID
+    pub fn add_new_transaction(
+        &mut self,
+        label: &str,
+        amount: Money,
+    ) -> Result<u32, Box<dyn Error>> {
+        let today = NaiveDate::from(Local::now().naive_local());
+        self.add_transaction(label, amount, today)
+    }
+
+    /// Adds a transaction under the next free ID, returning that ID
     pub fn add_transaction(
         &mut self,
         label: &str,
-        amount: f32,
+        amount: Money,
+        date: NaiveDate,
+    ) -> Result<u32, Box<dyn Error>> {
+        let id = self.next_id;
+        self.insert_transaction(id, label, amount, date)?;
+        Ok(id)
+    }
+
+    /// Adds a transaction under an ID it already has, e.g. one read back from a file
+    pub fn insert_transaction(
+        &mut self,
+        id: u32,
+        label: &str,
+        amount: Money,
         date: NaiveDate,
     ) -> Result<(), Box<dyn Error>> {
-        self.check_transaction_not_exists(&format!("{}-{}-{}", date, label, amount))?;
+        self.check_transaction_not_exists(id)?;
 
-        self.balance += amount;
-        self.transactions.insert(
-            format!("{}-{}-{}", date, label, amount),
-            Transaction::new(label, amount, date)?,
-        );
+        let transaction = Transaction::new(id, label, amount, date)?;
+        let next_id = self.id_after(id)?;
+        self.balance = self.balance.checked_add(amount)?;
+        self.transactions.insert(id, transaction);
+        self.next_id = next_id;
         Ok(())
     }
 
     pub fn edit_transaction_label(
         &mut self,
-        key: &str,
+        id: u32,
         new_label: String,
     ) -> Result<(), Box<dyn Error>> {
-        self.check_transaction_exists(key)?;
-
-        let transaction = self.transactions.get(key).unwrap();
-
-        self.check_transaction_not_exists(&format!(
-            "{}-{}-{}",
-            transaction.date, new_label, transaction.date
-        ))?;
+        self.check_transaction_exists(id)?;
+        if new_label.is_empty() {
+            return Err("No name provided for transaction".into());
+        }
 
         self.transactions
-            .get_mut(key)
+            .get_mut(&id)
             .unwrap()
             .edit_label(new_label);
         Ok(())
@@ -89,23 +116,18 @@ impl Account {
 
     pub fn edit_transaction_amount(
         &mut self,
-        key: &str,
-        new_amount: f32,
+        id: u32,
+        new_amount: Money,
     ) -> Result<(), Box<dyn Error>> {
-        self.check_transaction_exists(key)?;
-
-        let transaction = self.transactions.get(key).unwrap();
-
-        self.check_transaction_not_exists(&format!(
-            "{}-{}-{}",
-            transaction.date, transaction.label, new_amount
-        ))?;
+        self.check_transaction_exists(id)?;
 
-        self.balance -= self.transactions.get(key).unwrap().amount;
-        self.balance += new_amount;
+        self.balance = self
+            .balance
+            .checked_sub(self.transactions.get(&id).unwrap().amount)?
+            .checked_add(new_amount)?;
 
         self.transactions
-            .get_mut(key)
+            .get_mut(&id)
             .unwrap()
             .edit_amount(new_amount);
         Ok(())
@@ -113,43 +135,107 @@ impl Account {
 
     pub fn edit_transaction_date(
         &mut self,
-        key: &str,
+        id: u32,
         new_date: NaiveDate,
     ) -> Result<(), Box<dyn Error>> {
-        self.check_transaction_exists(key)?;
+        self.check_transaction_exists(id)?;
 
-        let transaction = self.transactions.get(key).unwrap();
+        self.transactions.get_mut(&id).unwrap().edit_date(new_date);
+        Ok(())
+    }
 
-        self.check_transaction_not_exists(&format!(
-            "{}-{}-{}",
-            new_date, transaction.label, transaction.amount
-        ))?;
+    pub fn edit_transaction_category(
+        &mut self,
+        id: u32,
+        new_category: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_transaction_exists(id)?;
 
-        self.transactions.get_mut(key).unwrap().edit_date(new_date);
+        self.transactions
+            .get_mut(&id)
+            .unwrap()
+            .edit_category(new_category);
         Ok(())
     }
 
-    pub fn remove_transaction(&mut self, key: &str) -> Result<(), Box<dyn Error>> {
-        self.check_transaction_exists(key)?;
-        self.balance -= self.transactions.remove(key).unwrap().amount;
+    /// Sets or clears the transfer the given transaction is one side of
+    pub fn link_transaction(
+        &mut self,
+        id: u32,
+        transfer: Option<TransferLink>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_transaction_exists(id)?;
+
+        self.transactions.get_mut(&id).unwrap().transfer = transfer;
         Ok(())
     }
 
-    fn check_transaction_exists(&mut self, key: &str) -> Result<(), Box<dyn Error>> {
-        if !self.transactions.contains_key(key) {
+    /// Sets or clears the ID the bank gave the given transaction when it was imported
+    pub fn set_transaction_import_id(
+        &mut self,
+        id: u32,
+        import_id: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_transaction_exists(id)?;
+
+        self.transactions.get_mut(&id).unwrap().import_id = import_id;
+        Ok(())
+    }
+
+    /// Puts a copy of a transaction in under the given ID, replacing any already there
+    pub fn put_transaction(
+        &mut self,
+        id: u32,
+        transaction: &Transaction,
+    ) -> Result<(), Box<dyn Error>> {
+        let next_id = self.id_after(id)?;
+        if self.transactions.contains_key(&id) {
+            self.remove_transaction(id)?;
+        }
+
+        let mut copy = transaction.clone();
+        copy.id = id;
+        self.balance = self.balance.checked_add(copy.amount)?;
+        self.transactions.insert(id, copy);
+        self.next_id = next_id;
+        Ok(())
+    }
+
+    pub fn remove_transaction(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        self.check_transaction_exists(id)?;
+        self.balance = self
+            .balance
+            .checked_sub(self.transactions.get(&id).unwrap().amount)?;
+        self.transactions.remove(&id);
+        Ok(())
+    }
+
+    /// The next ID once a transaction with the given ID is present
+    fn id_after(&self, id: u32) -> Result<u32, Box<dyn Error>> {
+        match id.checked_add(1) {
+            Some(after) => Ok(self.next_id.max(after)),
+            None => Err(Box::from(format!(
+                "Transaction #{id} in {} is past the highest ID allowed",
+                self.name
+            ))),
+        }
+    }
+
+    fn check_transaction_exists(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        if !self.transactions.contains_key(&id) {
             return Err(Box::from(format!(
-                "Transaction {} is not present in {}",
-                key, self.name
+                "Transaction #{} is not present in {}",
+                id, self.name
             )));
         }
         Ok(())
     }
 
-    fn check_transaction_not_exists(&mut self, key: &str) -> Result<(), Box<dyn Error>> {
-        if self.transactions.contains_key(key) {
+    fn check_transaction_not_exists(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        if self.transactions.contains_key(&id) {
             return Err(Box::from(format!(
-                "Transaction {} is already present in {}",
-                key, self.name
+                "Transaction #{} is already present in {}",
+                id, self.name
             )));
         }
         Ok(())
@@ -160,74 +246,140 @@ impl fmt::Display for Account {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Name: {} | Balance: ${:.2}\nTransactions:\n{}",
+            "Name: {} | Balance: ${}\nTransactions:\n{}",
             self.name,
             self.balance,
             self.transactions
                 .values()
-                .sorted_by(|a, b| Ord::cmp(&a.date, &b.date))
+                .sorted_by(|a, b| Ord::cmp(&(a.date, a.id), &(b.date, b.id)))
                 .join("\n"),
         )
     }
 }
 
-#[derive(PartialEq)]
+/// Points at the other side of a transfer: the account it was made with and
+/// the ID of the matching transaction there
+#[derive(Clone, PartialEq)]
+pub struct TransferLink {
+    account: String,
+    id: u32,
+}
+
+impl TransferLink {
+    pub fn new(account: &str, id: u32) -> TransferLink {
+        TransferLink {
+            account: String::from(account),
+            id,
+        }
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Transaction {
+    id: u32,
     label: String,
-    amount: f32,
+    amount: Money,
     date: NaiveDate,
+    category: Option<String>,
+    transfer: Option<TransferLink>,
+    import_id: Option<String>,
 }
 
 impl Transaction {
-    fn new(label: &str, amount: f32, now: NaiveDate) -> Result<Transaction, Box<dyn Error>> {
+    fn new(
+        id: u32,
+        label: &str,
+        amount: Money,
+        now: NaiveDate,
+    ) -> Result<Transaction, Box<dyn Error>> {
         if label.is_empty() {
             return Err("No name provided for transaction".into());
         }
         Ok(Transaction {
+            id,
             label: String::from(label),
             amount,
             date: now,
+            category: None,
+            transfer: None,
+            import_id: None,
         })
     }
 
+    pub fn id(&self) -> u32 {
+        self.id
+    }
     pub fn label(&self) -> &str {
         &self.label
     }
-    pub fn amount(&self) -> &f32 {
+    pub fn amount(&self) -> &Money {
         &self.amount
     }
     pub fn date(&self) -> &NaiveDate {
         &self.date
     }
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+    pub fn transfer(&self) -> Option<&TransferLink> {
+        self.transfer.as_ref()
+    }
+    pub fn import_id(&self) -> Option<&str> {
+        self.import_id.as_deref()
+    }
 
     fn edit_label(&mut self, new: String) {
         self.label = new;
     }
 
-    fn edit_amount(&mut self, new: f32) {
+    fn edit_amount(&mut self, new: Money) {
         self.amount = new;
     }
 
     fn edit_date(&mut self, new: NaiveDate) {
         self.date = new;
     }
+
+    fn edit_category(&mut self, new: Option<String>) {
+        self.category = new;
+    }
 }
 
 impl fmt::Display for Transaction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Date: {} | Label: {} | Amount: ${:.2}",
+            "ID: {} | Date: {} | Label: {} | Amount: ${}",
+            self.id,
             self.date.format("%d %b %Y"),
             self.label,
             self.amount
-        )
+        )?;
+        if let Some(category) = &self.category {
+            write!(f, " | Category: {}", category)?;
+        }
+        match &self.transfer {
+            // money leaving this account is the "from" side of the transfer
+            Some(link) if self.amount.is_negative() => {
+                write!(f, " | Transfer to {} #{}", link.account, link.id)
+            }
+            Some(link) => write!(f, " | Transfer from {} #{}", link.account, link.id),
+            None => Ok(()),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::account::Account;
+    use crate::money::Money;
     use chrono::{Local, NaiveDate};
 
     #[test]
@@ -239,10 +391,11 @@ mod tests {
     #[test]
     fn balance_increasing() {
         let mut a = Account::build("account").unwrap();
-        let mut sum: f32 = 0.0;
+        let mut sum = Money::zero();
         for i in 0..100 {
-            a.add_new_transaction(&format!("{i}"), i as f32).unwrap();
-            sum += i as f32;
+            let amount = Money::new(i * 101, 2);
+            a.add_new_transaction(&format!("{i}"), amount).unwrap();
+            sum = sum.checked_add(amount).unwrap();
         }
         assert_eq!(a.balance, sum);
     }
@@ -250,29 +403,86 @@ mod tests {
     #[test]
     fn balance_decreasing() {
         let mut a = Account::build("account").unwrap();
-        a.add_new_transaction("x", 100.0).unwrap();
-        assert_eq!(a.balance, 100.0);
-        a.add_new_transaction("y", -50.0).unwrap();
-        assert_eq!(a.balance, 50.0);
+        a.add_new_transaction("x", Money::new(100, 0)).unwrap();
+        assert_eq!(a.balance, Money::new(100, 0));
+        a.add_new_transaction("y", Money::new(-50, 0)).unwrap();
+        assert_eq!(a.balance, Money::new(50, 0));
     }
 
     #[test]
     #[should_panic]
     fn transaction_add_error() {
         let mut a = Account::build("account").unwrap();
-        a.add_new_transaction("", 15.0).unwrap();
+        a.add_new_transaction("", Money::new(15, 0)).unwrap();
+    }
+
+    #[test]
+    fn balance_overflow() {
+        let mut a = Account::build("account").unwrap();
+        a.add_new_transaction("x", Money::new(i64::MAX, 2)).unwrap();
+        assert!(a.add_new_transaction("y", Money::new(1, 2)).is_err());
+        assert_eq!(a.transactions().len(), 1);
+        assert_eq!(a.balance, Money::new(i64::MAX, 2));
+    }
+
+    #[test]
+    fn highest_id() {
+        let mut a = Account::build("account").unwrap();
+        let day = "2024-05-25".parse().unwrap();
+        assert!(a
+            .insert_transaction(u32::MAX, "x", Money::new(1, 0), day)
+            .is_err());
+        assert!(a.transactions().is_empty());
+        assert_eq!(a.balance, Money::zero());
+
+        a.insert_transaction(u32::MAX - 1, "x", Money::new(1, 0), day)
+            .unwrap();
+        assert_eq!(a.next_id(), u32::MAX);
+    }
+
+    #[test]
+    fn duplicate_transactions_get_unique_ids() {
+        let mut a = Account::build("account").unwrap();
+        let first = a
+            .add_new_transaction("Coffee", Money::new(-450, 2))
+            .unwrap();
+        let second = a
+            .add_new_transaction("Coffee", Money::new(-450, 2))
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(a.transactions().len(), 2);
+
+        a.remove_transaction(first).unwrap();
+        let third = a
+            .add_new_transaction("Coffee", Money::new(-450, 2))
+            .unwrap();
+        assert!(third > second);
+        assert_eq!(a.balance, Money::new(-900, 2));
+    }
+
+    #[test]
+    fn insert_existing_id() {
+        let mut a = Account::build("account").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 25).unwrap();
+        a.insert_transaction(7, "x", Money::new(100, 2), date)
+            .unwrap();
+        assert!(a
+            .insert_transaction(7, "y", Money::new(100, 2), date)
+            .is_err());
+        assert_eq!(a.add_transaction("z", Money::new(100, 2), date).unwrap(), 8);
     }
 
     #[test]
     fn account_printing() {
         let mut a = Account::build("Savings").unwrap();
-        a.add_new_transaction("Transaction", 10.0).unwrap();
+        a.add_new_transaction("Transaction", Money::new(1000, 2))
+            .unwrap();
         assert_eq!(
             &format!("{}", a),
             &format!(
                 "Name: Savings | Balance: $10.00\n\
                    Transactions:\n\
-                   Date: {} | Label: Transaction | Amount: $10.00",
+                   ID: 1 | Date: {} | Label: Transaction | Amount: $10.00",
                 NaiveDate::from(Local::now().naive_local()).format("%d %b %Y")
             )
         );
@@ -0,0 +1,271 @@
+use crate::account::{Account, TransferLink};
+use crate::budget::Budgets;
+use crate::ledger::Ledger;
+use crate::money::Money;
+use crate::recurring::Recurring;
+use chrono::NaiveDate;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Name of the section holding the budgets. Account names cannot start with '!',
+/// so this can never clash with an account.
+const BUDGETS_SECTION: &str = "!budgets";
+const RECURRING_SECTION: &str = "!recurring";
+
+/// Reads a file in the brace format used before version 2, where each account is its name
+/// and an opening brace followed by its transactions as bare comma separated fields
+pub fn read(contents: &str) -> Result<Ledger, Box<dyn Error>> {
+    let split: Vec<&str> = contents.split(',').collect();
+
+    if !split.first().unwrap().ends_with('{') || split.len() < 2 {
+        return Err(Box::from("Malformed File - Comma Separators"));
+    }
+
+    let mut ledger = Ledger::default();
+    let mut account_holder: Account;
+    let mut iter = 0;
+    while iter < split.len() {
+        if split[iter].trim_end_matches('{') == BUDGETS_SECTION {
+            iter = read_budgets(&split, iter, &mut ledger.budgets)?;
+            continue;
+        }
+        if split[iter].trim_end_matches('{') == RECURRING_SECTION {
+            iter = read_recurring(&split, iter, &mut ledger.recurring)?;
+            continue;
+        }
+
+        (account_holder, iter) = read_account(&split, iter)?;
+
+        ledger
+            .accounts
+            .insert(account_holder.name().to_lowercase(), account_holder);
+    }
+
+    Ok(ledger)
+}
+
+fn read_budgets(
+    split: &[&str],
+    mut iter: usize,
+    budgets: &mut Budgets,
+) -> Result<usize, Box<dyn Error>> {
+    iter += 1; // budget category OR "}"
+
+    while iter + 1 < split.len() && !&split[iter].eq("}") {
+        let category = split[iter];
+
+        iter += 1; // budget limit
+
+        let limit: Money = match split[iter].parse() {
+            Ok(val) => val,
+            _ => return Err(Box::from(format!("Budget limit {} not valid", split[iter]))),
+        };
+
+        budgets.set_limit(category, limit)?;
+
+        iter += 1; // next token (should be "}")
+    }
+    if iter >= split.len() || !&split[iter].eq("}") {
+        return Err(Box::from("Malformed file - Budget Ending Braces"));
+    }
+
+    iter += 1;
+    Ok(iter)
+}
+
+fn read_recurring(
+    split: &[&str],
+    mut iter: usize,
+    recurring: &mut Vec<Recurring>,
+) -> Result<usize, Box<dyn Error>> {
+    iter += 1; // recurring account OR "}"
+
+    // each recurring transaction is account, label, amount, frequency, next due date, category
+    while iter + 6 < split.len() && !&split[iter].eq("}") {
+        let amount: Money = match split[iter + 2].parse() {
+            Ok(val) => val,
+            _ => return Err(Box::from(format!("Value {} not valid", split[iter + 2]))),
+        };
+
+        let frequency = split[iter + 3].parse()?;
+
+        let next_due: NaiveDate = match NaiveDate::from_str(split[iter + 4]) {
+            Ok(val) => val,
+            _ => return Err(Box::from(format!("Date {} not valid", split[iter + 4]))),
+        };
+
+        let category = match split[iter + 5] {
+            "" => None,
+            category => Some(String::from(category)),
+        };
+
+        recurring.push(Recurring::build(
+            split[iter],
+            split[iter + 1],
+            amount,
+            category,
+            frequency,
+            next_due,
+        )?);
+
+        iter += 6; // next token (should be "}")
+    }
+    if iter >= split.len() || !&split[iter].eq("}") {
+        return Err(Box::from("Malformed file - Recurring Ending Braces"));
+    }
+
+    iter += 1;
+    Ok(iter)
+}
+
+fn read_account(split: &[&str], mut iter: usize) -> Result<(Account, usize), Box<dyn Error>> {
+    // unwrap safe as we know that iter is at most at very last spot at start of loop
+
+    let mut account = Account::build(split.get(iter).unwrap().trim_end_matches('{').trim())?;
+
+    iter += 1; // transaction amount OR "}"
+
+    while iter < split.len() && !&split[iter].eq("}") {
+        // files written before transactions had IDs go straight to the amount
+        let transaction_id: Option<u32> = match split[iter].strip_prefix('#') {
+            Some(slice) => match slice.parse() {
+                Ok(val) => {
+                    iter += 1; // transaction amount
+                    Some(val)
+                }
+                _ => return Err(Box::from(format!("ID {} not valid", slice))),
+            },
+            None => None,
+        };
+
+        let transaction_amount: Money = match split.get(iter) {
+            Some(slice) => match slice.parse() {
+                Ok(val) => val,
+                _ => return Err(Box::from(format!("Value {} not valid", slice))),
+            },
+            _ => break,
+        };
+
+        iter += 1; // transaction name
+
+        let transaction_name = match split.get(iter) {
+            Some(slice) => slice,
+            _ => break,
+        };
+
+        iter += 1; // transaction date
+
+        let transaction_date: NaiveDate = match split.get(iter) {
+            Some(slice) => match NaiveDate::from_str(slice) {
+                Ok(val) => val,
+                _ => return Err(Box::from(format!("Date {} not valid", slice))),
+            },
+            _ => break,
+        };
+
+        iter += 1; // optional tagged fields OR next token (should be "}")
+
+        let mut category: Option<String> = None;
+        let mut transfer: Option<TransferLink> = None;
+        let mut import_id: Option<String> = None;
+        while let Some(slice) = split.get(iter) {
+            if let Some(slice) = slice.strip_prefix('~') {
+                if slice.is_empty() {
+                    return Err(Box::from("Category must not be empty"));
+                }
+                category = Some(String::from(slice));
+            } else if let Some(slice) = slice.strip_prefix('>') {
+                transfer = match slice.rsplit_once('#').map(|(a, id)| (a, id.parse())) {
+                    Some((other_account, Ok(other_id))) if !other_account.is_empty() => {
+                        Some(TransferLink::new(other_account, other_id))
+                    }
+                    _ => return Err(Box::from(format!("Transfer {} not valid", slice))),
+                };
+            } else if let Some(slice) = slice.strip_prefix('^') {
+                if slice.is_empty() {
+                    return Err(Box::from("Import ID must not be empty"));
+                }
+                import_id = Some(String::from(slice));
+            } else {
+                break;
+            }
+            iter += 1;
+        }
+
+        let id = match transaction_id {
+            Some(id) => {
+                account.insert_transaction(
+                    id,
+                    transaction_name,
+                    transaction_amount,
+                    transaction_date,
+                )?;
+                id
+            }
+            None => {
+                account.add_transaction(transaction_name, transaction_amount, transaction_date)?
+            }
+        };
+        if category.is_some() {
+            account.edit_transaction_category(id, category)?;
+        }
+        if transfer.is_some() {
+            account.link_transaction(id, transfer)?;
+        }
+        if import_id.is_some() {
+            account.set_transaction_import_id(id, import_id)?;
+        }
+    }
+    if iter >= split.len() {
+        return Err(Box::from("Malformed file - Wrong number of entries"));
+    }
+    if !&split[iter].eq("}") {
+        return Err(Box::from("Malformed file - Ending Braces"));
+    }
+
+    iter += 1;
+    Ok((account, iter))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::brace_format::read;
+
+    #[test]
+    fn tagged_fields_and_sections() {
+        let ledger = read(
+            "Savings{,#1,10.00,Pay,2024-05-25,~Income,^A1,#3,-4.00,Transfer,2024-05-26,>Checking#1,},\
+            Checking{,#1,4.00,Transfer,2024-05-26,>Savings#3,},\
+            !budgets{,Fun,50.00,},\
+            !recurring{,Savings,Rent,-1000.00,monthly:31,2024-05-31,Housing,}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            format!("{}", ledger.accounts.get("savings").unwrap()),
+            "Name: Savings | Balance: $6.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Pay | Amount: $10.00 | Category: Income\n\
+            ID: 3 | Date: 26 May 2024 | Label: Transfer | Amount: $-4.00 | Transfer to Checking #1"
+        );
+        assert_eq!(
+            ledger.accounts.get("savings").unwrap().transactions()[&1].import_id(),
+            Some("A1")
+        );
+        assert_eq!(ledger.budgets.limits().len(), 1);
+        assert_eq!(ledger.recurring.len(), 1);
+    }
+
+    #[test]
+    fn legacy_without_ids() {
+        let ledger = read("Savings{,1,a,2024-06-10,2,b,2024-06-11,}").unwrap();
+        let savings = ledger.accounts.get("savings").unwrap();
+        assert_eq!(savings.transactions()[&2].label(), "b");
+    }
+
+    #[test]
+    #[should_panic]
+    fn bad_budget() {
+        read("!budgets{,Fun,lots,}").unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use crate::account::Account;
+use crate::money::Money;
+use crate::report;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Percentage of a budget that can be spent before it is flagged as nearly used up
+const NEAR_LIMIT_PERCENT: i64 = 90;
+
+/// Monthly spending limits, keyed by category
+#[derive(Clone, Default, PartialEq)]
+pub struct Budgets {
+    limits: BTreeMap<String, Money>,
+}
+
+impl Budgets {
+    pub fn limits(&self) -> &BTreeMap<String, Money> {
+        &self.limits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Sets the monthly limit for a category, replacing any existing one
+    pub fn set_limit(&mut self, category: &str, limit: Money) -> Result<(), Box<dyn Error>> {
+        if category.is_empty() {
+            return Err(Box::from("Budget must have a category"));
+        }
+        if limit.is_negative() || limit.is_zero() {
+            return Err(Box::from(format!(
+                "Budget limit must be positive, not {limit}"
+            )));
+        }
+        self.limits.insert(String::from(category), limit);
+        Ok(())
+    }
+
+    pub fn remove_limit(&mut self, category: &str) -> Result<(), Box<dyn Error>> {
+        if self.limits.remove(category).is_none() {
+            return Err(Box::from(format!("No budget set for {category}")));
+        }
+        Ok(())
+    }
+
+    /// How much of each budget has been spent in the month `date` falls in
+    pub fn status(
+        &self,
+        accounts: &HashMap<String, Account>,
+        date: NaiveDate,
+    ) -> Result<Vec<BudgetStatus>, Box<dyn Error>> {
+        let (start, end) = report::month_bounds(date);
+        let totals = report::category_totals(accounts, start, end)?;
+
+        let mut statuses = Vec::new();
+        for (category, limit) in &self.limits {
+            // spending is negative, so flip the sign to count it up towards the limit
+            let spent = match totals.get(category) {
+                Some(total) => total.checked_neg()?,
+                None => Money::zero(),
+            };
+            statuses.push(BudgetStatus {
+                category: category.clone(),
+                limit: *limit,
+                spent,
+                remaining: limit.checked_sub(spent)?,
+            });
+        }
+        Ok(statuses)
+    }
+}
+
+pub struct BudgetStatus {
+    category: String,
+    limit: Money,
+    spent: Money,
+    remaining: Money,
+}
+
+impl BudgetStatus {
+    pub fn is_over(&self) -> bool {
+        self.remaining.is_negative()
+    }
+
+    pub fn is_near(&self) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        match (
+            self.spent.checked_mul(100),
+            self.limit.checked_mul(NEAR_LIMIT_PERCENT),
+        ) {
+            (Ok(spent), Ok(threshold)) => spent >= threshold,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for BudgetStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: ${} of ${} spent | ${} remaining",
+            self.category, self.spent, self.limit, self.remaining
+        )?;
+        if self.is_over() {
+            write!(f, " | OVER BUDGET")?;
+        } else if self.is_near() {
+            write!(f, " | NEAR LIMIT")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::budget::Budgets;
+    use crate::money::Money;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn form_accounts() -> HashMap<String, Account> {
+        let mut account = Account::build("Savings").unwrap();
+        for (amount, day, category) in [
+            (-9500, "2024-05-02", "Groceries"),
+            (-6000, "2024-05-03", "Fun"),
+            (-1000, "2024-05-04", "Transport"),
+            (-7000, "2024-04-30", "Transport"),
+        ] {
+            let id = account
+                .add_transaction("x", Money::new(amount, 2), date(day))
+                .unwrap();
+            account
+                .edit_transaction_category(id, Some(String::from(category)))
+                .unwrap();
+        }
+        HashMap::from([(String::from("savings"), account)])
+    }
+
+    #[test]
+    fn status_test() {
+        let mut budgets = Budgets::default();
+        budgets
+            .set_limit("Groceries", Money::new(10000, 2))
+            .unwrap();
+        budgets.set_limit("Fun", Money::new(5000, 2)).unwrap();
+        budgets.set_limit("Transport", Money::new(5000, 2)).unwrap();
+        budgets.set_limit("Rent", Money::new(100000, 2)).unwrap();
+
+        let lines: Vec<String> = budgets
+            .status(&form_accounts(), date("2024-05-20"))
+            .unwrap()
+            .iter()
+            .map(|status| format!("{status}"))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "Fun: $60.00 of $50.00 spent | $-10.00 remaining | OVER BUDGET",
+                "Groceries: $95.00 of $100.00 spent | $5.00 remaining | NEAR LIMIT",
+                "Rent: $0.00 of $1000.00 spent | $1000.00 remaining",
+                "Transport: $10.00 of $50.00 spent | $40.00 remaining",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_limits() {
+        let mut budgets = Budgets::default();
+        assert!(budgets.set_limit("", Money::new(100, 2)).is_err());
+        assert!(budgets.set_limit("Fun", Money::zero()).is_err());
+        assert!(budgets.set_limit("Fun", Money::new(-100, 2)).is_err());
+        assert!(budgets.remove_limit("Fun").is_err());
+        assert!(budgets.is_empty());
+    }
+}
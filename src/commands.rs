@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+/// How many of a command's arguments a parameter takes
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Required,
+    /// Left out, or given as an empty word when a later argument is given
+    Optional,
+    /// Every argument left at the end, which cannot be given by name
+    Rest,
+}
+
+/// An argument a command takes, given in order or by name as `--name value` or `name=value`
+pub struct Param {
+    name: &'static str,
+    kind: Kind,
+    /// What to give, and what is taken if it is left out
+    about: &'static str,
+}
+
+const fn required(name: &'static str, about: &'static str) -> Param {
+    Param {
+        name,
+        kind: Kind::Required,
+        about,
+    }
+}
+
+const fn optional(name: &'static str, about: &'static str) -> Param {
+    Param {
+        name,
+        kind: Kind::Optional,
+        about,
+    }
+}
+
+const fn rest(name: &'static str, about: &'static str) -> Param {
+    Param {
+        name,
+        kind: Kind::Rest,
+        about,
+    }
+}
+
+/// A command as entered in the menu, and as named on the command line
+pub struct Command {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Other words it can be entered as
+    aliases: &'static [&'static str],
+    params: &'static [Param],
+    summary: &'static str,
+    /// Whether it only changes the ledger in memory or shows it, and so can be run in a batch
+    pub batch: bool,
+}
+
+const ACCOUNT: Param = required("account", "the account's name, in any case");
+const ID: Param = required(
+    "id",
+    "the transaction's ID, as shown in the list, e.g. 3 or #3",
+);
+const LABEL: Param = required("label", "what the transaction was for");
+const AMOUNT: Param = required(
+    "amount",
+    "e.g. -4.50 for money spent or 1200 for money received",
+);
+const DATE: Param = required("date", "YYYY-MM-DD");
+const CATEGORY: Param = optional(
+    "category",
+    "what it is counted under in reports, none if not given",
+);
+
+/// Every command, in the order the menu lists them
+pub const COMMANDS: [Command; 34] = [
+    Command {
+        code: "aa",
+        name: "add-account",
+        aliases: &[],
+        params: &[required("account", "the new account's name")],
+        summary: "add new account",
+        batch: true,
+    },
+    Command {
+        code: "ra",
+        name: "remove-account",
+        aliases: &[],
+        params: &[ACCOUNT],
+        summary: "remove an account",
+        batch: true,
+    },
+    Command {
+        code: "at",
+        name: "add-transaction",
+        aliases: &[],
+        params: &[
+            ACCOUNT,
+            LABEL,
+            AMOUNT,
+            CATEGORY,
+            optional("date", "YYYY-MM-DD, today if not given"),
+        ],
+        summary: "add transaction to account, from today unless a date is given",
+        batch: true,
+    },
+    Command {
+        code: "atd",
+        name: "add-dated-transaction",
+        aliases: &[],
+        params: &[ACCOUNT, LABEL, AMOUNT, DATE, CATEGORY],
+        summary: "add transaction from another day to account",
+        batch: true,
+    },
+    Command {
+        code: "tr",
+        name: "transfer",
+        aliases: &[],
+        params: &[
+            required("from", "the account the money leaves"),
+            required("to", "the account the money goes to"),
+            required("amount", "how much is moved, more than zero"),
+            optional("date", "YYYY-MM-DD, today if not given"),
+        ],
+        summary: "transfer between accounts",
+        batch: true,
+    },
+    Command {
+        code: "eta",
+        name: "edit-amount",
+        aliases: &[],
+        params: &[ACCOUNT, ID, required("amount", "the new amount")],
+        summary: "edit amount of transaction",
+        batch: true,
+    },
+    Command {
+        code: "etd",
+        name: "edit-date",
+        aliases: &[],
+        params: &[ACCOUNT, ID, required("date", "the new date, YYYY-MM-DD")],
+        summary: "edit date of transaction",
+        batch: true,
+    },
+    Command {
+        code: "etl",
+        name: "edit-label",
+        aliases: &[],
+        params: &[ACCOUNT, ID, required("label", "the new label")],
+        summary: "edit label of transaction",
+        batch: true,
+    },
+    Command {
+        code: "etc",
+        name: "edit-category",
+        aliases: &[],
+        params: &[
+            ACCOUNT,
+            ID,
+            optional("category", "the new category, clearing it if none is given"),
+        ],
+        summary: "edit category of transaction",
+        batch: true,
+    },
+    Command {
+        code: "rt",
+        name: "remove-transaction",
+        aliases: &[],
+        params: &[ACCOUNT, ID],
+        summary: "remove a transaction",
+        batch: true,
+    },
+    Command {
+        code: "cr",
+        name: "category-report",
+        aliases: &[],
+        params: &[
+            optional("start", "YYYY-MM-DD, the start of this month if not given"),
+            optional("end", "YYYY-MM-DD, the end of this month if not given"),
+        ],
+        summary: "show totals per category",
+        batch: true,
+    },
+    Command {
+        code: "ab",
+        name: "add-budget",
+        aliases: &[],
+        params: &[
+            required("category", "the category to limit"),
+            required("limit", "how much can be spent on it each month"),
+        ],
+        summary: "set the monthly budget for a category",
+        batch: true,
+    },
+    Command {
+        code: "rb",
+        name: "remove-budget",
+        aliases: &[],
+        params: &[required("category", "the category to stop limiting")],
+        summary: "remove the budget for a category",
+        batch: true,
+    },
+    Command {
+        code: "ar",
+        name: "add-recurring",
+        aliases: &[],
+        params: &[
+            ACCOUNT,
+            LABEL,
+            AMOUNT,
+            required(
+                "frequency",
+                "daily, weekly, monthly or yearly, from the first date",
+            ),
+            required("first", "the date it is first due, YYYY-MM-DD"),
+            CATEGORY,
+        ],
+        summary: "add recurring transaction",
+        batch: true,
+    },
+    Command {
+        code: "rr",
+        name: "remove-recurring",
+        aliases: &[],
+        params: &[required("number", "its number in the list")],
+        summary: "remove a recurring transaction",
+        batch: true,
+    },
+    Command {
+        code: "import",
+        name: "import",
+        aliases: &[],
+        params: &[
+            ACCOUNT,
+            required("file", "an OFX, QFX or CSV bank statement"),
+            rest(
+                "column=value",
+                "for CSV statements, which columns hold the date, desc and amount, or debit \
+                and credit, the date format, and header=no if there is no header row",
+            ),
+        ],
+        summary: "import a bank statement into an account",
+        batch: true,
+    },
+    Command {
+        code: "qi",
+        name: "import-qif",
+        aliases: &[],
+        params: &[
+            required("file", "the QIF file to read"),
+            optional("account", "where transactions with no account go"),
+        ],
+        summary: "add the accounts in a QIF file",
+        batch: true,
+    },
+    Command {
+        code: "qe",
+        name: "export-qif",
+        aliases: &[],
+        params: &[required("file", "the QIF file to write")],
+        summary: "export all accounts to a QIF file",
+        batch: false,
+    },
+    Command {
+        code: "list",
+        name: "list",
+        aliases: &[],
+        params: &[optional("account", "the account to show, all if not given")],
+        summary: "show accounts and their transactions",
+        batch: true,
+    },
+    Command {
+        code: "find",
+        name: "find",
+        aliases: &[],
+        params: &[
+            optional("account", "the account to search, all if not given or empty"),
+            optional(
+                "label",
+                "part of the label, in any case, or a regular expression between slashes, e.g. /^iced/",
+            ),
+            optional("min", "the least amount, e.g. -20 to leave out spending over 20"),
+            optional("max", "the greatest amount, e.g. 0 for only money spent"),
+            optional("from", "the first date, YYYY-MM-DD"),
+            optional("to", "the last date, YYYY-MM-DD"),
+            optional("category", "the category, or Uncategorised for those with none, leaving out transfers"),
+        ],
+        summary: "list the transactions matching what is given, with the account and ID to edit them by",
+        batch: true,
+    },
+    Command {
+        code: "balance",
+        name: "balance",
+        aliases: &[],
+        params: &[optional("account", "the account to show, all if not given")],
+        summary: "show balances, one tab-separated line per account",
+        batch: true,
+    },
+    Command {
+        code: "b",
+        name: "backup",
+        aliases: &[],
+        params: &[],
+        summary: "back up the data file as last saved",
+        batch: false,
+    },
+    Command {
+        code: "restore",
+        name: "restore",
+        aliases: &[],
+        params: &[optional(
+            "number",
+            "the backup to put back, listing them if not given",
+        )],
+        summary: "list backups of the data file, or replace it with one of them",
+        batch: false,
+    },
+    Command {
+        code: "s",
+        name: "save",
+        aliases: &[],
+        params: &[],
+        summary: "save changes",
+        batch: false,
+    },
+    Command {
+        code: "undo",
+        name: "undo",
+        aliases: &["u"],
+        params: &[optional("count", "how many changes, 1 if not given")],
+        summary: "undo the last change, or that many changes",
+        batch: false,
+    },
+    Command {
+        code: "redo",
+        name: "redo",
+        aliases: &[],
+        params: &[optional("count", "how many changes, 1 if not given")],
+        summary: "redo the last change undone, or that many changes",
+        batch: false,
+    },
+    Command {
+        code: "history",
+        name: "history",
+        aliases: &[],
+        params: &[],
+        summary: "list the changes that can be undone or redone",
+        batch: false,
+    },
+    Command {
+        code: "reload",
+        name: "reload",
+        aliases: &[],
+        params: &[],
+        summary: "load the data file as last saved, which can itself be undone",
+        batch: false,
+    },
+    Command {
+        code: "log",
+        name: "log",
+        aliases: &[],
+        params: &[
+            ACCOUNT,
+            optional("id", "one of its transactions, all of them if not given"),
+        ],
+        summary: "list the changes saved to an account, and who made them",
+        batch: false,
+    },
+    Command {
+        code: "replay",
+        name: "replay",
+        aliases: &[],
+        params: &[],
+        summary: "rebuild all accounts from the journal of changes kept beside the data file",
+        batch: false,
+    },
+    Command {
+        code: "merge",
+        name: "merge",
+        aliases: &[],
+        params: &[],
+        summary: "add the changes made here to the data file as changed elsewhere",
+        batch: false,
+    },
+    Command {
+        code: "pw",
+        name: "passphrase",
+        aliases: &[],
+        params: &[],
+        summary: "encrypt the data file with a new passphrase",
+        batch: false,
+    },
+    Command {
+        code: "batch",
+        name: "batch",
+        aliases: &[],
+        params: &[optional(
+            "file",
+            "commands one per line, read from standard input if not given or -",
+        )],
+        summary: "run the commands in a file, saving only if all succeed (command line only)",
+        batch: false,
+    },
+    Command {
+        code: "help",
+        name: "help",
+        aliases: &[],
+        params: &[optional("command", "the command to describe")],
+        summary: "describe a command and what it takes",
+        batch: true,
+    },
+];
+
+/// The command entered as the given code, name or alias
+pub fn find(word: &str) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.words().any(|entered| entered == word))
+}
+
+impl Command {
+    /// Every word the command can be entered as
+    pub fn words(&self) -> impl Iterator<Item = &'static str> {
+        [self.code, self.name]
+            .into_iter()
+            .chain(self.aliases.iter().copied())
+    }
+
+    /// How the command is written, e.g. `etc [account] [id] [category]?`
+    pub fn usage(&self) -> String {
+        let mut usage = String::from(self.code);
+        for param in self.params {
+            usage += &match param.kind {
+                Kind::Required => format!(" [{}]", param.name),
+                Kind::Optional => format!(" [{}]?", param.name),
+                Kind::Rest => format!(" [{}]...", param.name),
+            };
+        }
+        usage
+    }
+
+    /// The command's usage, what it does and what each argument is for
+    pub fn help(&self) -> String {
+        let mut lines = vec![format!("{} - {}", self.usage(), self.summary)];
+        for param in self.params {
+            lines.push(format!("\t{:<12} {}", param.name, param.about));
+        }
+        let names = self
+            .words()
+            .filter(|word| *word != self.code)
+            .collect::<Vec<&str>>();
+        if !names.is_empty() {
+            lines.push(format!("Can also be entered as {}", names.join(" or ")));
+        }
+        if let Some(param) = self.params.iter().find(|p| p.kind != Kind::Rest) {
+            lines.push(format!(
+                "Arguments can be given in order, or by name as --{0} value or {0}=value",
+                param.name
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Puts the words entered in the order the command's parameters are listed, taking
+    /// those given by name out of order, so each can be read by its position. Optional
+    /// arguments left out before one that is given are empty words.
+    pub fn arrange(&self, words: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+        let fail = |problem: String| -> Box<dyn Error> {
+            Box::from(format!("{problem}\nUsage: {}", self.usage()))
+        };
+
+        let mut named: HashMap<&str, String> = HashMap::new();
+        let mut positional = Vec::new();
+        let mut rest = words.iter().skip(1);
+        while let Some(word) = rest.next() {
+            let (name, value) = match word.strip_prefix("--") {
+                Some(name) => match rest.next() {
+                    Some(value) => (name, value.as_str()),
+                    None => return Err(fail(format!("--{name} needs a value after it"))),
+                },
+                None => match word.split_once('=') {
+                    Some((name, value)) if self.named(name).is_some() => (name, value),
+                    _ => {
+                        positional.push(word.clone());
+                        continue;
+                    }
+                },
+            };
+            let param = match self.named(name) {
+                Some(param) => param,
+                None => return Err(fail(format!("{} has no {name} to give", self.code))),
+            };
+            if named.insert(param.name, String::from(value)).is_some() {
+                return Err(fail(format!("{name} was given more than once")));
+            }
+        }
+
+        let mut arranged = vec![String::from(self.code)];
+        let mut positional = positional.into_iter();
+        let mut left_out = 0;
+        for param in self.params {
+            if param.kind == Kind::Rest {
+                arranged.extend(positional.by_ref());
+                break;
+            }
+            match named.remove(param.name).or_else(|| positional.next()) {
+                Some(value) => {
+                    arranged.extend(std::iter::repeat_n(String::new(), left_out));
+                    left_out = 0;
+                    arranged.push(value);
+                }
+                None if param.kind == Kind::Required => {
+                    return Err(fail(format!("{} needs the {}", self.code, param.name)))
+                }
+                None => left_out += 1,
+            }
+        }
+        if let Some(extra) = positional.next() {
+            return Err(fail(format!("{extra} is more than {} takes", self.code)));
+        }
+        Ok(arranged)
+    }
+
+    /// The parameter that can be given by this name
+    fn named(&self, name: &str) -> Option<&Param> {
+        self.params
+            .iter()
+            .find(|param| param.name == name && param.kind != Kind::Rest)
+    }
+}
+
+/// One line per command with its usage and summary, as the menu lists them
+pub fn menu() -> String {
+    let mut lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            let usage = command.usage();
+            let short = match usage.split_once(' ') {
+                Some((code, params)) => format!("{code:<3} {params}"),
+                None => format!("{usage:<3}"),
+            };
+            format!("\t{short} - {}", command.summary)
+        })
+        .collect();
+    lines.push(String::from("\tq   - save and exit program"));
+    lines.push(String::from("\tq!  - exit program without saving"));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{find, menu};
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| String::from(*word)).collect()
+    }
+
+    #[test]
+    fn arrange_test() {
+        let at = find("add-transaction").unwrap();
+        assert_eq!(
+            at.arrange(&words(&["at", "savings", "coffee", "-4.5"]))
+                .unwrap(),
+            ["at", "savings", "coffee", "-4.5"]
+        );
+        assert_eq!(
+            at.arrange(&words(&[
+                "at",
+                "savings",
+                "coffee",
+                "-4.5",
+                "--date",
+                "2024-05-01",
+                "--category",
+                "food"
+            ]))
+            .unwrap(),
+            ["at", "savings", "coffee", "-4.5", "food", "2024-05-01"]
+        );
+        // left out optional arguments before one given are empty
+        assert_eq!(
+            at.arrange(&words(&[
+                "at",
+                "date=2024-05-01",
+                "amount=-4.5",
+                "savings",
+                "coffee"
+            ]))
+            .unwrap(),
+            ["at", "savings", "coffee", "-4.5", "", "2024-05-01"]
+        );
+
+        // column=value options are not names of import's parameters
+        let import = find("import").unwrap();
+        assert_eq!(
+            import
+                .arrange(&words(&[
+                    "import",
+                    "file=bank.csv",
+                    "savings",
+                    "date=1",
+                    "amount=3"
+                ]))
+                .unwrap(),
+            ["import", "savings", "bank.csv", "date=1", "amount=3"]
+        );
+
+        assert_eq!(
+            find("u").unwrap().arrange(&words(&["u"])).unwrap(),
+            ["undo"]
+        );
+    }
+
+    #[test]
+    fn arrange_invalid() {
+        let at = find("at").unwrap();
+        for given in [
+            &["at", "savings", "coffee"][..],
+            &[
+                "at",
+                "savings",
+                "coffee",
+                "-4.5",
+                "food",
+                "2024-05-01",
+                "extra",
+            ],
+            &["at", "savings", "coffee", "-4.5", "--when", "2024-05-01"],
+            &["at", "savings", "coffee", "-4.5", "--date"],
+            &["at", "savings", "coffee", "-4.5", "--date", "x", "date=y"],
+        ] {
+            assert!(at.arrange(&words(given)).is_err());
+        }
+        assert_eq!(
+            at.arrange(&words(&["at", "savings"]))
+                .err()
+                .unwrap()
+                .to_string(),
+            "at needs the label\nUsage: at [account] [label] [amount] [category]? [date]?"
+        );
+    }
+
+    #[test]
+    fn help_test() {
+        assert_eq!(
+            find("etc").unwrap().help(),
+            "etc [account] [id] [category]? - edit category of transaction\n\
+            \taccount      the account's name, in any case\n\
+            \tid           the transaction's ID, as shown in the list, e.g. 3 or #3\n\
+            \tcategory     the new category, clearing it if none is given\n\
+            Can also be entered as edit-category\n\
+            Arguments can be given in order, or by name as --account value or account=value"
+        );
+        assert!(menu().starts_with("\taa  [account] - add new account\n"));
+        assert!(menu().contains("\ts   - save changes\n"));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::commands::COMMANDS;
+use crate::ledger::Ledger;
+use crate::tokenizer;
+use itertools::Itertools;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Completes commands typed in the menu: their names first, then account names and the
+/// labels of transactions already entered
+#[derive(Default)]
+pub struct MenuCompleter {
+    accounts: Vec<String>,
+    labels: Vec<String>,
+}
+
+impl MenuCompleter {
+    /// Takes the account names and labels to offer from the ledger as it is now
+    pub fn update(&mut self, ledger: &Ledger) {
+        self.accounts = ledger
+            .accounts
+            .values()
+            .map(|account| String::from(account.name()))
+            .sorted()
+            .collect();
+        self.labels = ledger
+            .accounts
+            .values()
+            .flat_map(|account| account.transactions().values())
+            .map(|t| String::from(t.label()))
+            .sorted()
+            .dedup()
+            .collect();
+    }
+
+    /// Where the word being typed at `pos` starts, and the words it could be completed to,
+    /// quoted if they need to be
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = word_start(&line[..pos]);
+        let typed = line[start..pos]
+            .replace('\\', "")
+            .trim_start_matches(['"', '\''])
+            .to_lowercase();
+        let matches = |word: &&str| word.to_lowercase().starts_with(&typed);
+
+        let before = tokenizer::tokenize(&line[..start]).map_or(0, |words| words.len());
+        let words: Vec<&str> = match before {
+            0 => COMMANDS
+                .iter()
+                .flat_map(|command| command.words())
+                .chain(["q", "q!"])
+                .unique()
+                .collect(),
+            // the first argument of every command taking an account is the account
+            1 => self.accounts.iter().map(String::as_str).collect(),
+            _ => self
+                .accounts
+                .iter()
+                .chain(&self.labels)
+                .map(String::as_str)
+                .collect(),
+        };
+        let candidates = words
+            .into_iter()
+            .filter(matches)
+            .map(tokenizer::quote)
+            .collect();
+        (start, candidates)
+    }
+}
+
+/// Where the last word in `line` begins, taking quotes and escapes into account
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (None | Some('"'), '\\') => escaped = true,
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c.is_whitespace() => start = index + c.len_utf8(),
+            (None, _) => {}
+        }
+    }
+    start
+}
+
+impl Completer for MenuCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for MenuCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for MenuCompleter {}
+
+impl Validator for MenuCompleter {}
+
+impl Helper for MenuCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::completion::MenuCompleter;
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use std::collections::HashMap;
+
+    #[test]
+    fn complete_words() {
+        let mut savings = Account::build("Savings").unwrap();
+        savings
+            .add_transaction(
+                "Salary",
+                Money::new(100000, 2),
+                "2024-05-01".parse().unwrap(),
+            )
+            .unwrap();
+        savings
+            .add_transaction(
+                "Iced Coffee",
+                Money::new(-450, 2),
+                "2024-05-02".parse().unwrap(),
+            )
+            .unwrap();
+        let ledger = Ledger::from(HashMap::from([
+            (String::from("savings"), savings),
+            (String::from("cash"), Account::build("Cash").unwrap()),
+        ]));
+        let mut completer = MenuCompleter::default();
+        completer.update(&ledger);
+
+        assert_eq!(
+            completer.candidates("re", 2),
+            (
+                0,
+                vec![
+                    String::from("remove-account"),
+                    String::from("remove-transaction"),
+                    String::from("remove-budget"),
+                    String::from("remove-recurring"),
+                    String::from("restore"),
+                    String::from("redo"),
+                    String::from("reload"),
+                    String::from("replay"),
+                ]
+            )
+        );
+        assert_eq!(
+            completer.candidates("at s", 4),
+            (3, vec![String::from("Savings")])
+        );
+        // labels come after the account, quoted if they have spaces
+        assert_eq!(
+            completer.candidates("at savings sa", 13),
+            (11, vec![String::from("Savings"), String::from("Salary")])
+        );
+        assert_eq!(
+            completer.candidates("at savings ic", 13),
+            (11, vec![String::from("\"Iced Coffee\"")])
+        );
+        assert_eq!(
+            completer.candidates("at savings \"iced c", 18),
+            (11, vec![String::from("\"Iced Coffee\"")])
+        );
+        assert_eq!(
+            completer.candidates("tr cash s 5", 9),
+            (8, vec![String::from("Savings"), String::from("Salary")])
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::error::Error;
+
+/// Splits RFC 4180 CSV into records of fields. Fields may be quoted, in which case they
+/// can hold commas, line breaks and doubled quotes (""), and records may end in LF or CRLF.
+pub fn parse(contents: &str) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false; // currently inside quotes
+    let mut was_quoted = false; // current field started with a quote
+    let mut line = 1;
+
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() && !was_quoted => {
+                quoted = true;
+                was_quoted = true;
+            }
+            '"' => return Err(Box::from(format!("Unexpected quote on line {line}"))),
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                was_quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                was_quoted = false;
+                line += 1;
+            }
+            _ if was_quoted => {
+                return Err(Box::from(format!(
+                    "Unexpected text after closing quote on line {line}"
+                )))
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(Box::from(format!("Unclosed quote on line {line}")));
+    }
+    // a final record without a trailing line break
+    if !field.is_empty() || was_quoted || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Joins fields into one CSV record ending in CRLF, quoting any field that holds a comma,
+/// quote or line break, or that starts or ends with a space
+pub fn write_record(fields: &[impl AsRef<str>]) -> String {
+    let mut record = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            let needs_quotes = field.contains([',', '"', '\n', '\r'])
+                || field.starts_with(' ')
+                || field.ends_with(' ');
+            match needs_quotes {
+                true => format!("\"{}\"", field.replace('"', "\"\"")),
+                false => String::from(field),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    record.push_str("\r\n");
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csv::{parse, write_record};
+
+    #[test]
+    fn plain() {
+        assert_eq!(
+            parse("a,b,c\n1,2,3\n").unwrap(),
+            vec![vec!["a", "b", "c"], vec!["1", "2", "3"]]
+        );
+        assert_eq!(
+            parse("a,,\r\nb").unwrap(),
+            vec![vec!["a", "", ""], vec!["b"]]
+        );
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(
+            parse("\"Dinner, drinks\",\"say \"\"hi\"\"\",\"two\nlines\"\n\"\"").unwrap(),
+            vec![vec!["Dinner, drinks", "say \"hi\"", "two\nlines"], vec![""]]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("a,\"b\nc").is_err());
+        assert!(parse("a,b\"c\"").is_err());
+        assert!(parse("\"a\"b,c").is_err());
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let fields = [
+            "plain",
+            "Dinner, drinks",
+            "say \"hi\"",
+            "two\nlines",
+            " padded",
+            "",
+        ];
+        let record = write_record(&fields);
+
+        assert_eq!(
+            record,
+            "plain,\"Dinner, drinks\",\"say \"\"hi\"\"\",\"two\nlines\",\" padded\",\r\n"
+        );
+        assert_eq!(parse(&record).unwrap(), vec![fields]);
+    }
+}
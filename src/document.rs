@@ -0,0 +1,405 @@
+use crate::account::{Account, Transaction, TransferLink};
+use crate::budget::Budgets;
+use crate::file_processing::{FORMAT_NAME, FORMAT_VERSION};
+use crate::ledger::Ledger;
+use crate::recurring::Recurring;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::error::Error;
+
+/// The whole data file as written to JSON or TOML. Amounts are decimal strings such as
+/// "-12.50" so they never pass through floating point, and dates are "YYYY-MM-DD".
+#[derive(Serialize, Deserialize)]
+struct Document {
+    /// Always "budgeting-app"
+    format: String,
+    version: u32,
+    #[serde(default)]
+    accounts: Vec<AccountRecord>,
+    #[serde(default)]
+    budgets: Vec<BudgetRecord>,
+    #[serde(default)]
+    recurring: Vec<RecurringRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountRecord {
+    name: String,
+    /// The ID the account's next transaction will get, left out by files from before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next_id: Option<u32>,
+    #[serde(default)]
+    transactions: Vec<TransactionRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransactionRecord {
+    id: u32,
+    date: String,
+    label: String,
+    amount: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    /// The other side of a transfer, by account name and transaction ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transfer: Option<TransferRecord>,
+    /// The bank's ID for a transaction brought in from a statement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    import_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransferRecord {
+    account: String,
+    id: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BudgetRecord {
+    category: String,
+    /// Monthly limit
+    limit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecurringRecord {
+    account: String,
+    label: String,
+    amount: String,
+    /// "daily", "weekly", "monthly:DAY" or "yearly:MONTH-DAY"
+    frequency: String,
+    next_due: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+}
+
+pub fn write_json(ledger: &Ledger) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(&to_document(ledger))? + "\n")
+}
+
+pub fn read_json(contents: &str) -> Result<Ledger, Box<dyn Error>> {
+    match serde_json::from_str(contents) {
+        Ok(document) => from_document(document),
+        Err(e) => Err(Box::from(format!("Malformed JSON file - {e}"))),
+    }
+}
+
+pub fn write_toml(ledger: &Ledger) -> Result<String, Box<dyn Error>> {
+    Ok(toml::to_string(&to_document(ledger))?)
+}
+
+pub fn read_toml(contents: &str) -> Result<Ledger, Box<dyn Error>> {
+    match toml::from_str(contents) {
+        Ok(document) => from_document(document),
+        Err(e) => Err(Box::from(format!("Malformed TOML file - {e}"))),
+    }
+}
+
+fn to_document(ledger: &Ledger) -> Document {
+    Document {
+        format: String::from(FORMAT_NAME),
+        version: FORMAT_VERSION,
+        accounts: ledger
+            .accounts
+            .values()
+            .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+            .map(account_record)
+            .collect(),
+        budgets: budget_records(&ledger.budgets),
+        recurring: recurring_records(&ledger.recurring),
+    }
+}
+
+fn from_document(document: Document) -> Result<Ledger, Box<dyn Error>> {
+    if document.format != FORMAT_NAME {
+        return Err(Box::from(format!(
+            "File format {} is not {}",
+            document.format, FORMAT_NAME
+        )));
+    }
+    if document.version > FORMAT_VERSION {
+        return Err(Box::from(format!(
+            "File is format version {}, which is newer than this program supports",
+            document.version
+        )));
+    }
+
+    let mut ledger = Ledger::default();
+
+    for record in document.accounts {
+        let name = record.name.clone();
+        let account = read_account(record)?;
+        match ledger.accounts.entry(name.to_lowercase()) {
+            Entry::Occupied(_) => {
+                return Err(Box::from(format!("Account {name} is in the file twice")))
+            }
+            Entry::Vacant(entry) => entry.insert(account),
+        };
+    }
+
+    ledger.budgets = read_budgets(document.budgets)?;
+    ledger.recurring = read_recurring(document.recurring)?;
+
+    Ok(ledger)
+}
+
+pub fn account_record(account: &Account) -> AccountRecord {
+    AccountRecord {
+        name: String::from(account.name()),
+        next_id: Some(account.next_id()),
+        transactions: account
+            .transactions()
+            .values()
+            .sorted_by(|a, b| Ord::cmp(&(a.date(), a.id()), &(b.date(), b.id())))
+            .map(transaction_record)
+            .collect(),
+    }
+}
+
+pub fn transaction_record(t: &Transaction) -> TransactionRecord {
+    TransactionRecord {
+        id: t.id(),
+        date: t.date().to_string(),
+        label: String::from(t.label()),
+        amount: t.amount().to_string(),
+        category: t.category().map(String::from),
+        transfer: t.transfer().map(|link| TransferRecord {
+            account: String::from(link.account()),
+            id: link.id(),
+        }),
+        import_id: t.import_id().map(String::from),
+    }
+}
+
+pub fn budget_records(budgets: &Budgets) -> Vec<BudgetRecord> {
+    budgets
+        .limits()
+        .iter()
+        .map(|(category, limit)| BudgetRecord {
+            category: category.clone(),
+            limit: limit.to_string(),
+        })
+        .collect()
+}
+
+pub fn recurring_records(recurring: &[Recurring]) -> Vec<RecurringRecord> {
+    recurring
+        .iter()
+        .map(|template| RecurringRecord {
+            account: String::from(template.account()),
+            label: String::from(template.label()),
+            amount: template.amount().to_string(),
+            frequency: template.frequency().to_string(),
+            next_due: template.next_due().to_string(),
+            category: template.category().map(String::from),
+        })
+        .collect()
+}
+
+pub fn read_account(record: AccountRecord) -> Result<Account, Box<dyn Error>> {
+    let mut account = Account::build(&record.name)?;
+    for t in record.transactions {
+        insert_transaction(&mut account, t)?;
+    }
+    if let Some(next_id) = record.next_id {
+        account.set_next_id(next_id);
+    }
+    Ok(account)
+}
+
+/// Reads a transaction held by the named account
+pub fn read_transaction(
+    account_name: &str,
+    record: TransactionRecord,
+) -> Result<Transaction, Box<dyn Error>> {
+    let id = record.id;
+    let mut account = Account::build(account_name)?;
+    insert_transaction(&mut account, record)?;
+    // unwrap safe as it was just inserted
+    Ok(account.transactions().get(&id).unwrap().clone())
+}
+
+fn insert_transaction(account: &mut Account, t: TransactionRecord) -> Result<(), Box<dyn Error>> {
+    let name = String::from(account.name());
+    let context = |e: Box<dyn Error>| format!("{name} #{}: {e}", t.id);
+    let amount = t.amount.parse().map_err(context)?;
+    let date = match t.date.parse() {
+        Ok(date) => date,
+        Err(e) => return Err(Box::from(context(Box::new(e)))),
+    };
+    account
+        .insert_transaction(t.id, &t.label, amount, date)
+        .map_err(context)?;
+    account.edit_transaction_category(t.id, t.category)?;
+    account.set_transaction_import_id(t.id, t.import_id)?;
+    account.link_transaction(
+        t.id,
+        t.transfer
+            .map(|link| TransferLink::new(&link.account, link.id)),
+    )?;
+    Ok(())
+}
+
+pub fn read_budgets(records: Vec<BudgetRecord>) -> Result<Budgets, Box<dyn Error>> {
+    let mut budgets = Budgets::default();
+    for budget in records {
+        budgets.set_limit(&budget.category, budget.limit.parse()?)?;
+    }
+    Ok(budgets)
+}
+
+pub fn read_recurring(records: Vec<RecurringRecord>) -> Result<Vec<Recurring>, Box<dyn Error>> {
+    let mut recurring = Vec::new();
+    for template in records {
+        let next_due = match template.next_due.parse() {
+            Ok(date) => date,
+            Err(e) => {
+                return Err(Box::from(format!(
+                    "Date {} not valid: {e}",
+                    template.next_due
+                )))
+            }
+        };
+        recurring.push(Recurring::build(
+            &template.account,
+            &template.label,
+            template.amount.parse()?,
+            template.category,
+            template.frequency.parse()?,
+            next_due,
+        )?);
+    }
+    Ok(recurring)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::document::*;
+    use crate::money::Money;
+    use crate::recurring::Frequency;
+    use crate::transfer::transfer;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn form_ledger() -> Ledger {
+        let mut savings = Account::build("Savings").unwrap();
+        let id = savings
+            .add_transaction("Dinner, drinks", Money::new(-4200, 2), date("2024-05-25"))
+            .unwrap();
+        savings
+            .edit_transaction_category(id, Some(String::from("Eating Out")))
+            .unwrap();
+        savings
+            .set_transaction_import_id(id, Some(String::from("A1")))
+            .unwrap();
+
+        let mut accounts = HashMap::from([
+            (String::from("savings"), savings),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ]);
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(150, 2),
+            date("2024-05-27"),
+        )
+        .unwrap();
+
+        let mut ledger = Ledger::from(accounts);
+        ledger
+            .budgets
+            .set_limit("Eating Out", Money::new(10000, 2))
+            .unwrap();
+        ledger.recurring.push(
+            Recurring::build(
+                "Savings",
+                "Rent",
+                Money::new(-100000, 2),
+                None,
+                Frequency::Monthly(31),
+                date("2024-05-31"),
+            )
+            .unwrap(),
+        );
+        ledger
+    }
+
+    fn assert_same(a: &Ledger, b: &Ledger) {
+        assert_eq!(a.accounts.len(), b.accounts.len());
+        for (key, account) in &a.accounts {
+            assert_eq!(
+                format!("{account}"),
+                format!("{}", b.accounts.get(key).unwrap())
+            );
+        }
+        assert_eq!(a.budgets.limits(), b.budgets.limits());
+        assert_eq!(a.recurring.len(), b.recurring.len());
+        for (x, y) in a.recurring.iter().zip(b.recurring.iter()) {
+            assert_eq!(format!("{x}"), format!("{y}"));
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let ledger = form_ledger();
+        let json = write_json(&ledger).unwrap();
+
+        assert!(json.starts_with("{\n  \"format\": \"budgeting-app\",\n  \"version\": 2,"));
+        assert!(json.contains(
+            "\"label\": \"Transfer\",\n          \"amount\": \"-1.50\",\n          \
+            \"transfer\": {\n            \"account\": \"Checking\",\n            \"id\": 1\n"
+        ));
+        assert_same(&ledger, &read_json(&json).unwrap());
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let ledger = form_ledger();
+        let toml = write_toml(&ledger).unwrap();
+
+        assert!(toml.starts_with("format = \"budgeting-app\"\nversion = 2\n"));
+        assert_same(&ledger, &read_toml(&toml).unwrap());
+    }
+
+    #[test]
+    fn minimal_document() {
+        let ledger = read_json(
+            r#"{"format": "budgeting-app", "version": 2, "accounts": [
+                {"name": "Savings", "transactions": [
+                    {"id": 4, "date": "2024-05-25", "label": "Pay", "amount": "100"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            ledger.accounts.get("savings").unwrap().balance(),
+            &Money::new(10000, 2)
+        );
+    }
+
+    #[test]
+    fn invalid_documents() {
+        for bad in [
+            r#"{"format": "something-else", "version": 2}"#,
+            r#"{"format": "budgeting-app", "version": 3}"#,
+            r#"{"format": "budgeting-app"}"#,
+            r#"{"format": "budgeting-app", "version": 2, "accounts": [{"name": "a"}, {"name": "A"}]}"#,
+            r#"{"format": "budgeting-app", "version": 2, "accounts": [{"name": "a", "transactions": [
+                {"id": 1, "date": "2024-05-25", "label": "x", "amount": "1.0.0"}]}]}"#,
+            r#"{"format": "budgeting-app", "version": 2, "budgets": [{"category": "Fun", "limit": "-5"}]}"#,
+        ] {
+            assert!(read_json(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::error::Error;
+
+/// Start of every encrypted data file. It is followed by the salt the key was derived
+/// with, the nonce and then the encrypted contents, which authenticate all that comes before.
+const MAGIC: &[u8] = b"budgeting-app-encrypted,1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// Encrypts and decrypts data files with a key derived from a passphrase
+pub struct Cipher {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+impl Cipher {
+    /// A cipher for a passphrase not used before, with a fresh salt
+    pub fn new(passphrase: &str) -> Result<Cipher, Box<dyn Error>> {
+        if passphrase.is_empty() {
+            return Err(Box::from("Passphrase cannot be empty"));
+        }
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::build(passphrase, salt)
+    }
+
+    /// The cipher an encrypted file was written with, checking the passphrase opens it
+    pub fn open(passphrase: &str, file: &[u8]) -> Result<Cipher, Box<dyn Error>> {
+        let cipher = Self::build(passphrase, salt_of(file)?)?;
+        cipher.decrypt(file)?;
+        Ok(cipher)
+    }
+
+    fn build(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Cipher, Box<dyn Error>> {
+        Ok(Cipher {
+            passphrase: String::from(passphrase),
+            salt,
+            key: derive_key(passphrase, &salt)?,
+        })
+    }
+
+    pub fn encrypt(&self, contents: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut file = Vec::with_capacity(HEADER_LEN + contents.len() + 16);
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&self.salt);
+        file.extend_from_slice(&nonce);
+
+        let payload = Payload {
+            msg: contents.as_bytes(),
+            aad: &file,
+        };
+        match XChaCha20Poly1305::new(&self.key).encrypt(&nonce, payload) {
+            Ok(encrypted) => file.extend(encrypted),
+            Err(_) => return Err(Box::from("Failed encrypting the data file")),
+        }
+        Ok(file)
+    }
+
+    /// Decrypts a file written with this passphrase, even if under a different salt
+    pub fn decrypt(&self, file: &[u8]) -> Result<String, Box<dyn Error>> {
+        let salt = salt_of(file)?;
+        let key = match salt == self.salt {
+            true => self.key,
+            false => derive_key(&self.passphrase, &salt)?,
+        };
+
+        let (header, encrypted) = file.split_at(HEADER_LEN);
+        let payload = Payload {
+            msg: encrypted,
+            aad: header,
+        };
+        let nonce = XNonce::from_slice(&header[MAGIC.len() + SALT_LEN..]);
+        match XChaCha20Poly1305::new(&key).decrypt(nonce, payload) {
+            Ok(contents) => Ok(String::from_utf8(contents)?),
+            Err(_) => Err(Box::from(
+                "Could not decrypt - wrong passphrase or damaged file",
+            )),
+        }
+    }
+}
+
+pub fn is_encrypted(file: &[u8]) -> bool {
+    file.starts_with(MAGIC)
+}
+
+fn salt_of(file: &[u8]) -> Result<[u8; SALT_LEN], Box<dyn Error>> {
+    if !is_encrypted(file) || file.len() < HEADER_LEN {
+        return Err(Box::from("Not an encrypted data file"));
+    }
+    let mut salt = [0; SALT_LEN];
+    salt.copy_from_slice(&file[MAGIC.len()..MAGIC.len() + SALT_LEN]);
+    Ok(salt)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, Box<dyn Error>> {
+    let mut key = Key::default();
+    if let Err(e) = Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key) {
+        return Err(Box::from(format!(
+            "Failed deriving key from passphrase: {e}"
+        )));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encryption::*;
+
+    #[test]
+    fn round_trip() {
+        let cipher = Cipher::new("correct horse").unwrap();
+        let file = cipher.encrypt("budgeting-app,2\r\n").unwrap();
+
+        assert!(is_encrypted(&file));
+        assert_eq!(cipher.decrypt(&file).unwrap(), "budgeting-app,2\r\n");
+        // each write uses a new nonce
+        assert_ne!(file, cipher.encrypt("budgeting-app,2\r\n").unwrap());
+
+        let opened = Cipher::open("correct horse", &file).unwrap();
+        assert_eq!(opened.decrypt(&file).unwrap(), "budgeting-app,2\r\n");
+
+        // a new salt for the same passphrase still decrypts
+        let other = Cipher::new("correct horse").unwrap();
+        assert_eq!(other.decrypt(&file).unwrap(), "budgeting-app,2\r\n");
+    }
+
+    #[test]
+    fn wrong_passphrase_or_tampered() {
+        let cipher = Cipher::new("correct horse").unwrap();
+        let mut file = cipher.encrypt("secret").unwrap();
+
+        assert!(Cipher::open("wrong horse", &file).is_err());
+        assert!(Cipher::new("").is_err());
+        assert!(cipher.decrypt(b"budgeting-app,2\r\n").is_err());
+
+        let last = file.len() - 1;
+        file[last] ^= 1;
+        assert!(cipher.decrypt(&file).is_err());
+    }
+}
@@ -1,44 +1,143 @@
-use crate::account::Account;
+use crate::account::{Account, TransferLink};
+use crate::brace_format;
+use crate::csv;
+use crate::document;
+use crate::encryption::{self, Cipher};
+use crate::ledger::Ledger;
+use crate::money::Money;
+use crate::recurring::Recurring;
 use chrono::NaiveDate;
 use itertools::Itertools;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
-use std::io::Read;
-use std::str::FromStr;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// First record of every data file, followed by the format version
+pub(super) const FORMAT_NAME: &str = "budgeting-app";
+pub(super) const FORMAT_VERSION: u32 = 2;
+
+/// How a data file is stored, decided by its extension or the --format flag
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum FileFormat {
+    Csv,
+    Json,
+    Toml,
+    Sqlite,
+}
+
+impl FileFormat {
+    pub(super) const EXTENSIONS: [&'static str; 4] = [".csv", ".json", ".toml", ".db"];
+    /// Names given to --format, in the same order as the extensions
+    pub(super) const NAMES: [&'static str; 4] = ["csv", "json", "toml", "sqlite"];
+    const ALL: [FileFormat; 4] = [
+        FileFormat::Csv,
+        FileFormat::Json,
+        FileFormat::Toml,
+        FileFormat::Sqlite,
+    ];
+
+    pub(super) fn from_path(file_path: &str) -> Result<FileFormat, Box<dyn Error>> {
+        let lowercase = file_path.to_lowercase();
+        match Self::EXTENSIONS
+            .iter()
+            .position(|extension| lowercase.ends_with(extension))
+        {
+            Some(index) => Ok(Self::ALL[index]),
+            None => Err(Box::from(format!(
+                "File {file_path} should end in one of {}",
+                Self::EXTENSIONS.join(", ")
+            ))),
+        }
+    }
+
+    pub(super) fn from_name(name: &str) -> Result<FileFormat, Box<dyn Error>> {
+        match Self::NAMES
+            .iter()
+            .position(|format| name.eq_ignore_ascii_case(format))
+        {
+            Some(index) => Ok(Self::ALL[index]),
+            None => Err(Box::from(format!(
+                "Format {name} should be one of {}",
+                Self::NAMES.join(", ")
+            ))),
+        }
+    }
+}
 
-pub(super) fn get_file_contents(file_path: &str) -> Result<String, Box<dyn Error>> {
+/// The second record of every data file. Each record after it is an account, a transaction,
+/// a budget or a recurring transaction, using only the columns that apply to it. An account's
+/// id is the one its next transaction will get.
+const COLUMNS: [&str; 11] = [
+    "record",
+    "account",
+    "id",
+    "date",
+    "label",
+    "amount",
+    "category",
+    "transfer account",
+    "transfer id",
+    "import id",
+    "frequency",
+];
+
+/// Reads a data file, decrypting it with `cipher` if it is encrypted
+pub(super) fn get_file_contents(
+    file_path: &str,
+    cipher: Option<&Cipher>,
+) -> Result<String, Box<dyn Error>> {
     let mut file = match fs::File::open(file_path) {
         Ok(file) => file,
         Err(_) => fs::File::create_new(file_path)?, // if file cannot be opened it does not exist
     };
 
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    Ok(contents)
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    decode(file_path, contents, cipher)
 }
 
-pub(super) fn write_to_file(
+/// The text held in the bytes of a data file, decrypting them if they are encrypted
+pub(super) fn decode(
     file_path: &str,
-    accounts: &HashMap<String, Account>,
-) -> Result<(), Box<dyn Error>> {
-    let mut buf: Vec<String> = Vec::new();
-    for account in accounts.values() {
-        buf.push(format!("{}{{", account.name()));
+    contents: Vec<u8>,
+    cipher: Option<&Cipher>,
+) -> Result<String, Box<dyn Error>> {
+    if !encryption::is_encrypted(&contents) {
+        return Ok(String::from_utf8(contents)?);
+    }
+    match cipher {
+        Some(cipher) => cipher.decrypt(&contents),
+        None => Err(Box::from(format!(
+            "{file_path} is encrypted, and needs its passphrase to be read"
+        ))),
+    }
+}
 
-        for transaction in account
-            .transactions()
-            .values()
-            .sorted_by(|a, b| Ord::cmp(a.date(), b.date()))
-        {
-            buf.push(format!("{}", transaction.amount()));
-            buf.push(transaction.label().to_string());
-            buf.push(format!("{}", transaction.date()));
-        }
-        buf.push(String::from("}"));
+/// The bytes to write for the text of a data file, encrypted if there is a `cipher`
+pub(super) fn encode(contents: &str, cipher: Option<&Cipher>) -> Result<Vec<u8>, Box<dyn Error>> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(contents),
+        None => Ok(contents.as_bytes().to_vec()),
     }
+}
+
+pub(super) fn write_to_file(
+    file_path: &str,
+    format: FileFormat,
+    ledger: &Ledger,
+    cipher: Option<&Cipher>,
+) -> Result<(), Box<dyn Error>> {
+    let contents = match format {
+        FileFormat::Csv => write_csv(ledger),
+        FileFormat::Json => document::write_json(ledger)?,
+        FileFormat::Toml => document::write_toml(ledger)?,
+        FileFormat::Sqlite => return Err(Box::from(format!("{file_path} is not a text file"))),
+    };
 
-    if let Err(e) = fs::write(file_path, buf.join(",")) {
+    if let Err(e) = write_atomically(file_path, encode(&contents, cipher)?) {
         return Err(Box::from(format!(
             "Failed writing to {}:\n{}",
             file_path, e
@@ -48,138 +147,367 @@ pub(super) fn write_to_file(
     Ok(())
 }
 
-pub(super) fn read_from_string(
-    contents: String,
-) -> Result<HashMap<String, Account>, Box<dyn Error>> {
-    if contents.is_empty() {
-        return Ok(HashMap::new());
+/// Writes to a temporary file beside the data file, syncs it to disk and renames it over
+/// the original, so a crash part way through leaves either the old file or the new one
+pub(super) fn write_atomically(file_path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp_path = format!("{file_path}.tmp");
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, file_path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
     }
+    result?;
 
-    let split: Vec<&str> = contents.split(',').collect();
+    // the rename itself is only durable once the directory holding it is synced,
+    // which not every platform allows
+    let directory = match Path::new(file_path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(directory) = fs::File::open(directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+pub(super) fn write_csv(ledger: &Ledger) -> String {
+    let mut rows: Vec<[String; COLUMNS.len()]> = Vec::new();
+
+    for account in ledger
+        .accounts
+        .values()
+        .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+    {
+        rows.push(row(
+            "account",
+            &[
+                ("account", account.name()),
+                ("id", &account.next_id().to_string()),
+            ],
+        ));
 
-    if !split.first().unwrap().ends_with('{') || split.len() < 2 {
-        return Err(Box::from("Malformed File - Comma Separators"));
+        for transaction in account
+            .transactions()
+            .values()
+            .sorted_by(|a, b| Ord::cmp(&(a.date(), a.id()), &(b.date(), b.id())))
+        {
+            let transfer_id = transaction.transfer().map(|link| link.id().to_string());
+            rows.push(row(
+                "transaction",
+                &[
+                    ("account", account.name()),
+                    ("id", &transaction.id().to_string()),
+                    ("date", &transaction.date().to_string()),
+                    ("label", transaction.label()),
+                    ("amount", &transaction.amount().to_string()),
+                    ("category", transaction.category().unwrap_or_default()),
+                    (
+                        "transfer account",
+                        transaction
+                            .transfer()
+                            .map(|l| l.account())
+                            .unwrap_or_default(),
+                    ),
+                    ("transfer id", transfer_id.as_deref().unwrap_or_default()),
+                    ("import id", transaction.import_id().unwrap_or_default()),
+                ],
+            ));
+        }
     }
 
-    let mut accounts: HashMap<String, Account> = HashMap::new();
-    let mut account_holder: Account;
-    let mut iter = 0;
-    while iter < split.len() {
-        (account_holder, iter) = read_account(&split, iter)?;
+    for (category, limit) in ledger.budgets.limits() {
+        rows.push(row(
+            "budget",
+            &[("category", category), ("amount", &limit.to_string())],
+        ));
+    }
 
-        accounts.insert(account_holder.name().to_lowercase(), account_holder);
+    for template in &ledger.recurring {
+        rows.push(row(
+            "recurring",
+            &[
+                ("account", template.account()),
+                ("date", &template.next_due().to_string()),
+                ("label", template.label()),
+                ("amount", &template.amount().to_string()),
+                ("category", template.category().unwrap_or_default()),
+                ("frequency", &template.frequency().to_string()),
+            ],
+        ));
     }
 
-    Ok(accounts)
+    let mut contents = csv::write_record(&[FORMAT_NAME, &FORMAT_VERSION.to_string()]);
+    contents += &csv::write_record(&COLUMNS);
+    for fields in rows {
+        contents += &csv::write_record(&fields);
+    }
+    contents
 }
 
-fn read_account(split: &[&str], mut iter: usize) -> Result<(Account, usize), Box<dyn Error>> {
-    // unwrap safe as we know that iter is at most at very last spot at start of loop
-
-    let mut account = match Account::build(split.get(iter).unwrap().trim_end_matches('{').trim()) {
-        Ok(a) => a,
-        Err(e) => return Err(e),
-    };
+/// Lays out one record, leaving every column not given empty
+fn row(record: &str, fields: &[(&str, &str)]) -> [String; COLUMNS.len()] {
+    let mut row: [String; COLUMNS.len()] = Default::default();
+    row[0] = String::from(record);
+    for (name, value) in fields {
+        // unwrap safe as only names from COLUMNS are passed in
+        let index = COLUMNS.iter().position(|column| column == name).unwrap();
+        row[index] = String::from(*value);
+    }
+    row
+}
 
-    iter += 1; // transaction amount OR "}"
+/// Whether the contents of a CSV file are in the brace format used before version 2,
+/// and so should be written back out in the current format once read
+pub(super) fn needs_migration(format: FileFormat, contents: &str) -> bool {
+    format == FileFormat::Csv && is_brace_format(contents)
+}
 
-    while iter < split.len() - 2 && !&split[iter].eq("}") {
-        let transaction_amount: f32 = match split.get(iter) {
-            Some(slice) => match slice.parse() {
-                Ok(val) => val,
-                _ => return Err(Box::from(format!("Value {} not valid", slice))),
-            },
-            _ => break,
-        };
+fn is_brace_format(contents: &str) -> bool {
+    !contents.is_empty() && !contents.starts_with(&format!("{FORMAT_NAME},"))
+}
 
-        iter += 1; // transaction nameq
+/// Rewrites a file read in an older format in the current one, keeping a copy of the
+/// original beside it. Returns a message saying where the copy is.
+pub(super) fn migrate(
+    file_path: &str,
+    ledger: &Ledger,
+    cipher: Option<&Cipher>,
+) -> Result<String, Box<dyn Error>> {
+    let copy_path = format!("{file_path}.v1");
+    if let Err(e) = fs::copy(file_path, &copy_path) {
+        return Err(Box::from(format!(
+            "Failed copying {file_path} before converting it: {e}"
+        )));
+    }
+    write_to_file(file_path, FileFormat::Csv, ledger, cipher)?;
+    Ok(format!(
+        "Converted {file_path} to format version {FORMAT_VERSION}, keeping the old file as {copy_path}"
+    ))
+}
 
-        let transaction_name = match split.get(iter) {
-            Some(slice) => slice,
-            _ => break,
-        };
+/// Reads the contents of a data file in the given format
+pub(super) fn read(format: FileFormat, contents: String) -> Result<Ledger, Box<dyn Error>> {
+    if contents.is_empty() {
+        return Ok(Ledger::default());
+    }
+    match format {
+        FileFormat::Csv => read_from_string(contents),
+        FileFormat::Json => document::read_json(&contents),
+        FileFormat::Toml => document::read_toml(&contents),
+        FileFormat::Sqlite => Err(Box::from("SQLite databases are not text files")),
+    }
+}
 
-        iter += 1; // transaction date
+pub(super) fn read_from_string(contents: String) -> Result<Ledger, Box<dyn Error>> {
+    if contents.is_empty() {
+        return Ok(Ledger::default());
+    }
+    if is_brace_format(&contents) {
+        return brace_format::read(&contents);
+    }
 
-        let transaction_date: NaiveDate = match split.get(iter) {
-            Some(slice) => match NaiveDate::from_str(slice) {
-                Ok(val) => val,
-                _ => return Err(Box::from(format!("Date {} not valid", slice))),
-            },
-            _ => break,
-        };
+    let mut records = csv::parse(&contents)?.into_iter();
 
-        iter += 1; // next token (should be "}")
+    // unwrap safe as the contents start with the format name
+    let version = records.next().unwrap();
+    match version.get(1).map(|v| v.parse::<u32>()) {
+        Some(Ok(version)) if version <= FORMAT_VERSION => {}
+        Some(Ok(version)) => {
+            return Err(Box::from(format!(
+                "File is format version {version}, which is newer than this program supports"
+            )))
+        }
+        _ => return Err(Box::from("Malformed file - Format version")),
+    }
 
-        account.add_transaction(transaction_name, transaction_amount, transaction_date)?
+    let columns: Vec<String> = match records.next() {
+        Some(columns) => columns,
+        None => return Err(Box::from("Malformed file - No column names")),
+    };
+    let mut indexes: HashMap<&str, usize> = HashMap::new();
+    for name in COLUMNS {
+        match columns.iter().position(|column| column == name) {
+            Some(index) => indexes.insert(name, index),
+            None => return Err(Box::from(format!("Malformed file - No {name} column"))),
+        };
     }
-    if iter >= split.len() {
-        return Err(Box::from("Malformed file - Wrong number of entries"));
+
+    let mut ledger = Ledger::default();
+    // the version and column names take the first two rows
+    for (record, number) in records.zip(3..) {
+        if record.iter().all(String::is_empty) {
+            continue;
+        }
+        let field = |name: &str| record.get(indexes[name]).map_or("", String::as_str);
+        read_record(field, &mut ledger).map_err(|e| format!("Row {number}: {e}"))?;
     }
-    if !&split[iter].eq("}") {
-        return Err(Box::from("Malformed file - Ending Braces"));
+
+    Ok(ledger)
+}
+
+fn read_record<'a>(
+    field: impl Fn(&str) -> &'a str,
+    ledger: &mut Ledger,
+) -> Result<(), Box<dyn Error>> {
+    let optional = |name: &str| match field(name) {
+        "" => None,
+        value => Some(String::from(value)),
+    };
+    let amount = || -> Result<Money, Box<dyn Error>> {
+        match field("amount").parse() {
+            Ok(amount) => Ok(amount),
+            Err(e) => Err(Box::from(format!(
+                "Amount {} not valid: {e}",
+                field("amount")
+            ))),
+        }
+    };
+    let date = || -> Result<NaiveDate, Box<dyn Error>> {
+        match field("date").parse() {
+            Ok(date) => Ok(date),
+            Err(e) => Err(Box::from(format!("Date {} not valid: {e}", field("date")))),
+        }
+    };
+
+    let id = || -> Result<u32, Box<dyn Error>> {
+        match field("id").parse() {
+            Ok(id) => Ok(id),
+            Err(e) => Err(Box::from(format!("ID {} not valid: {e}", field("id")))),
+        }
+    };
+
+    match field("record") {
+        "account" => {
+            let account = get_or_add_account(&mut ledger.accounts, field("account"))?;
+            // left empty by files written before the next ID was kept
+            if !field("id").is_empty() {
+                account.set_next_id(id()?);
+            }
+        }
+        "transaction" => {
+            let account = get_or_add_account(&mut ledger.accounts, field("account"))?;
+            let id = match field("id") {
+                "" => account.add_transaction(field("label"), amount()?, date()?)?,
+                _ => {
+                    let id = id()?;
+                    account.insert_transaction(id, field("label"), amount()?, date()?)?;
+                    id
+                }
+            };
+            account.edit_transaction_category(id, optional("category"))?;
+            account.set_transaction_import_id(id, optional("import id"))?;
+
+            let transfer = match (field("transfer account"), field("transfer id")) {
+                ("", "") => None,
+                (other_account, other_id) => match other_id.parse() {
+                    Ok(other_id) if !other_account.is_empty() => {
+                        Some(TransferLink::new(other_account, other_id))
+                    }
+                    _ => {
+                        return Err(Box::from(format!(
+                            "Transfer {other_account} #{other_id} not valid"
+                        )))
+                    }
+                },
+            };
+            account.link_transaction(id, transfer)?;
+        }
+        "budget" => ledger.budgets.set_limit(field("category"), amount()?)?,
+        "recurring" => ledger.recurring.push(Recurring::build(
+            field("account"),
+            field("label"),
+            amount()?,
+            optional("category"),
+            field("frequency").parse()?,
+            date()?,
+        )?),
+        other => return Err(Box::from(format!("Unknown record type {other}"))),
     }
+    Ok(())
+}
 
-    iter += 1;
-    Ok((account, iter))
+fn get_or_add_account<'a>(
+    accounts: &'a mut HashMap<String, Account>,
+    name: &str,
+) -> Result<&'a mut Account, Box<dyn Error>> {
+    match accounts.entry(name.to_lowercase()) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => Ok(entry.insert(Account::build(name)?)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::recurring::Frequency;
+    use std::collections::HashMap;
 
     #[test]
     fn empty_file() {
-        let empty = get_file_contents("src/test-files/empty.csv").unwrap();
-        assert!(read_from_string(empty).unwrap().is_empty());
+        let empty = get_file_contents("src/test-files/empty.csv", None).unwrap();
+        assert!(read_from_string(empty).unwrap().accounts.is_empty());
     }
 
     #[test]
     #[should_panic]
     fn bad_start() {
-        let _ =
-            read_from_string(get_file_contents("src/test-files/bad-start.csv").unwrap()).unwrap();
+        let _ = read_from_string(get_file_contents("src/test-files/bad-start.csv", None).unwrap())
+            .unwrap();
     }
 
     #[test]
     #[should_panic]
     fn bad_end() {
-        let _ = read_from_string(get_file_contents("src/test-files/bad-end.csv").unwrap()).unwrap();
+        let _ = read_from_string(get_file_contents("src/test-files/bad-end.csv", None).unwrap())
+            .unwrap();
     }
 
     #[test]
     #[should_panic]
     fn bad_trans_label() {
-        let _ = read_from_string(get_file_contents("src/test-files/bad-trans-label.csv").unwrap())
-            .unwrap();
+        let _ = read_from_string(
+            get_file_contents("src/test-files/bad-trans-label.csv", None).unwrap(),
+        )
+        .unwrap();
     }
 
     #[test]
     fn one_account() {
-        let a = get_file_contents("src/test-files/1-account.csv").unwrap();
+        let a = get_file_contents("src/test-files/1-account.csv", None).unwrap();
         assert_eq!(
-            &format!("{}", read_from_string(a).unwrap().get("savings").unwrap()),
+            &format!(
+                "{}",
+                read_from_string(a)
+                    .unwrap()
+                    .accounts
+                    .get("savings")
+                    .unwrap()
+            ),
             "Name: Savings | Balance: $1.00\n\
                    Transactions:\n\
-                   Date: 10 Jun 2024 | Label: a | Amount: $1.00"
+                   ID: 1 | Date: 10 Jun 2024 | Label: a | Amount: $1.00"
         );
     }
 
     #[test]
     fn two_accounts() {
-        let b =
-            read_from_string(get_file_contents("src/test-files/2-account.csv").unwrap()).unwrap();
+        let b = read_from_string(get_file_contents("src/test-files/2-account.csv", None).unwrap())
+            .unwrap();
         assert_eq!(
-            &format!("{}", b.get("savings").unwrap()),
+            &format!("{}", b.accounts.get("savings").unwrap()),
             "Name: Savings | Balance: $1.00\n\
                    Transactions:\n\
-                   Date: 10 Jun 2024 | Label: a | Amount: $1.00"
+                   ID: 1 | Date: 10 Jun 2024 | Label: a | Amount: $1.00"
         );
         assert_eq!(
-            &format!("{}", b.get("expenses").unwrap()),
+            &format!("{}", b.accounts.get("expenses").unwrap()),
             "Name: Expenses | Balance: $3.00\n\
                    Transactions:\n\
-                   Date: 10 Jun 2024 | Label: c | Amount: $3.00"
+                   ID: 1 | Date: 10 Jun 2024 | Label: c | Amount: $3.00"
         );
     }
 
@@ -187,11 +515,65 @@ mod tests {
         let mut a = Account::build("Savings").unwrap();
         let day1: NaiveDate = "2024-05-25".parse().unwrap();
         let day2: NaiveDate = "2024-05-26".parse().unwrap();
-        a.add_transaction("a", 1.0, day1).unwrap();
-        a.add_transaction("b", 2.0, day2).unwrap();
+        a.add_transaction("a", Money::new(100, 2), day1).unwrap();
+        a.add_transaction("b", Money::new(200, 2), day2).unwrap();
         a
     }
 
+    #[test]
+    fn category_write_read_test() {
+        let file_path = "src/test-files/category-write-read-test.csv";
+
+        let mut account = form_account();
+        account
+            .edit_transaction_category(2, Some(String::from("Groceries")))
+            .unwrap();
+        let map = HashMap::from([(account.name().to_lowercase(), account)]);
+
+        write_to_file(file_path, FileFormat::Csv, &Ledger::from(map), None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(
+            file_contents.ends_with("transaction,Savings,2,2024-05-26,b,2.00,Groceries,,,,\r\n")
+        );
+
+        let map2 = read_from_string(file_contents).unwrap();
+        let account2 = map2.accounts.get("savings").unwrap();
+        assert_eq!(account2.transactions().get(&1).unwrap().category(), None);
+        assert_eq!(
+            account2.transactions().get(&2).unwrap().category(),
+            Some("Groceries")
+        );
+    }
+
+    #[test]
+    fn import_id_write_read_test() {
+        let file_path = "src/test-files/import-id-write-read-test.csv";
+
+        let mut account = form_account();
+        account
+            .edit_transaction_category(1, Some(String::from("Fun")))
+            .unwrap();
+        account
+            .set_transaction_import_id(1, Some(String::from("20240525-0001")))
+            .unwrap();
+        let map = HashMap::from([(account.name().to_lowercase(), account)]);
+
+        write_to_file(file_path, FileFormat::Csv, &Ledger::from(map), None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(file_contents
+            .contains("transaction,Savings,1,2024-05-25,a,1.00,Fun,,,20240525-0001,\r\n"));
+
+        let map2 = read_from_string(file_contents).unwrap();
+        let account2 = map2.accounts.get("savings").unwrap();
+        assert_eq!(
+            account2.transactions().get(&1).unwrap().import_id(),
+            Some("20240525-0001")
+        );
+        assert_eq!(account2.transactions().get(&2).unwrap().import_id(), None);
+    }
+
     #[test]
     fn write_test() {
         let account = form_account();
@@ -199,16 +581,17 @@ mod tests {
 
         let file_path = "src/test-files/write-test.csv";
 
-        write_to_file(file_path, &map).unwrap();
+        write_to_file(file_path, FileFormat::Csv, &Ledger::from(map), None).unwrap();
 
-        let file_contents = get_file_contents(file_path).unwrap();
-
-        let day1: NaiveDate = "2024-05-25".parse().unwrap();
-        let day2: NaiveDate = "2024-05-26".parse().unwrap();
+        let file_contents = get_file_contents(file_path, None).unwrap();
 
         assert_eq!(
             &file_contents,
-            &format!("Savings{{,1,a,{day1},2,b,{day2},}}")
+            "budgeting-app,2\r\n\
+            record,account,id,date,label,amount,category,transfer account,transfer id,import id,frequency\r\n\
+            account,Savings,3,,,,,,,,\r\n\
+            transaction,Savings,1,2024-05-25,a,1.00,,,,,\r\n\
+            transaction,Savings,2,2024-05-26,b,2.00,,,,,\r\n"
         );
     }
 
@@ -219,16 +602,288 @@ mod tests {
         let account = form_account();
         let map = HashMap::from([(account.name().to_lowercase(), account)]);
 
-        write_to_file(file_path, &map).unwrap();
+        write_to_file(file_path, FileFormat::Csv, &Ledger::from(map), None).unwrap();
 
-        let file_contents = get_file_contents(file_path).unwrap();
+        let file_contents = get_file_contents(file_path, None).unwrap();
 
         let binding = read_from_string(file_contents).unwrap();
-        let account2 = binding.get("savings").unwrap();
+        let account2 = binding.accounts.get("savings").unwrap();
 
         assert_eq!(&format!("{}", form_account()), &format!("{account2}"));
     }
 
+    #[test]
+    fn ids_preserved() {
+        let file_path = "src/test-files/ids-write-read-test.csv";
+
+        let mut account = form_account();
+        account.remove_transaction(1).unwrap();
+        let day: NaiveDate = "2024-05-25".parse().unwrap();
+        account
+            .add_transaction("a", Money::new(100, 2), day)
+            .unwrap();
+
+        let map = HashMap::from([(account.name().to_lowercase(), account)]);
+        write_to_file(file_path, FileFormat::Csv, &Ledger::from(map), None).unwrap();
+
+        let map2 = read_from_string(get_file_contents(file_path, None).unwrap()).unwrap();
+        let account2 = map2.accounts.get("savings").unwrap();
+
+        assert!(!account2.transactions().contains_key(&1));
+        assert_eq!(account2.transactions().get(&2).unwrap().label(), "b");
+        assert_eq!(account2.transactions().get(&3).unwrap().label(), "a");
+    }
+
+    #[test]
+    fn next_id_preserved() {
+        let mut account = form_account();
+        account.remove_transaction(2).unwrap();
+        let ledger = Ledger::from(HashMap::from([(String::from("savings"), account)]));
+
+        for file_path in [
+            "src/test-files/next-id-write-read-test.csv",
+            "src/test-files/next-id-write-read-test.json",
+            "src/test-files/next-id-write-read-test.toml",
+        ] {
+            let format = FileFormat::from_path(file_path).unwrap();
+            write_to_file(file_path, format, &ledger, None).unwrap();
+
+            let ledger2 = read(format, get_file_contents(file_path, None).unwrap()).unwrap();
+            assert_eq!(ledger2.accounts["savings"].next_id(), 3, "{file_path}");
+        }
+
+        // files from before the next ID was kept take the one after the highest present
+        let older = read_from_string(format!(
+            "{FORMAT_NAME},2\n{}\naccount,Savings,,,,,,,,,\ntransaction,Savings,4,2024-05-25,a,1.00,,,,,\n",
+            COLUMNS.join(",")
+        ))
+        .unwrap();
+        assert_eq!(older.accounts["savings"].next_id(), 5);
+    }
+
+    #[test]
+    fn transfer_write_read_test() {
+        let file_path = "src/test-files/transfer-write-read-test.csv";
+
+        let mut map = HashMap::from([
+            (String::from("savings"), form_account()),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ]);
+        let day: NaiveDate = "2024-05-27".parse().unwrap();
+        crate::transfer::transfer(&mut map, "savings", "checking", Money::new(150, 2), day)
+            .unwrap();
+
+        let ledger = Ledger::from(map);
+        write_to_file(file_path, FileFormat::Csv, &ledger, None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(
+            file_contents.contains("transaction,Savings,3,2024-05-27,Transfer,-1.50,,Checking,1,,")
+        );
+
+        let map2 = read_from_string(file_contents).unwrap();
+        for key in ["savings", "checking"] {
+            assert_eq!(
+                format!("{}", ledger.accounts.get(key).unwrap()),
+                format!("{}", map2.accounts.get(key).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn budget_write_read_test() {
+        let file_path = "src/test-files/budget-write-read-test.csv";
+
+        let mut ledger = Ledger::from(HashMap::from([(String::from("savings"), form_account())]));
+        ledger
+            .budgets
+            .set_limit("Groceries", Money::new(40000, 2))
+            .unwrap();
+        ledger
+            .budgets
+            .set_limit("Fun", Money::new(5000, 2))
+            .unwrap();
+
+        write_to_file(file_path, FileFormat::Csv, &ledger, None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(file_contents
+            .ends_with("budget,,,,,50.00,Fun,,,,\r\nbudget,,,,,400.00,Groceries,,,,\r\n"));
+
+        let ledger2 = read_from_string(file_contents).unwrap();
+        assert_eq!(ledger2.budgets.limits(), ledger.budgets.limits());
+        assert_eq!(ledger2.accounts.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn bad_budget() {
+        read_from_string(String::from(
+            "budgeting-app,2\n\
+            record,account,id,date,label,amount,category,transfer account,transfer id,import id,frequency\n\
+            budget,,,,,lots,Fun,,,,\n",
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn special_characters_write_read_test() {
+        let file_path = "src/test-files/special-characters-write-read-test.csv";
+
+        let mut account = Account::build("Joint {Shared}").unwrap();
+        let day: NaiveDate = "2024-05-25".parse().unwrap();
+        let id = account
+            .add_transaction("Dinner, drinks \"on me\"", Money::new(-4200, 2), day)
+            .unwrap();
+        account
+            .edit_transaction_category(id, Some(String::from("Eating, Out")))
+            .unwrap();
+        let ledger = Ledger::from(HashMap::from([(account.name().to_lowercase(), account)]));
+
+        write_to_file(file_path, FileFormat::Csv, &ledger, None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(file_contents.contains(
+            "transaction,Joint {Shared},1,2024-05-25,\"Dinner, drinks \"\"on me\"\"\",-42.00,\"Eating, Out\""
+        ));
+
+        let ledger2 = read_from_string(file_contents).unwrap();
+        assert_eq!(
+            format!("{}", ledger.accounts.get("joint {shared}").unwrap()),
+            format!("{}", ledger2.accounts.get("joint {shared}").unwrap())
+        );
+    }
+
+    #[test]
+    fn migrate_test() {
+        let file_path = "src/test-files/migrate-test.csv";
+        fs::copy("src/test-files/2-account.csv", file_path).unwrap();
+
+        let contents = get_file_contents(file_path, None).unwrap();
+        assert!(needs_migration(FileFormat::Csv, &contents));
+        let ledger = read_from_string(contents).unwrap();
+
+        assert_eq!(
+            migrate(file_path, &ledger, None).unwrap(),
+            "Converted src/test-files/migrate-test.csv to format version 2, \
+            keeping the old file as src/test-files/migrate-test.csv.v1"
+        );
+
+        let contents = get_file_contents(file_path, None).unwrap();
+        assert!(!needs_migration(FileFormat::Csv, &contents));
+        let migrated = read_from_string(contents).unwrap();
+        for key in ["savings", "expenses"] {
+            assert_eq!(
+                format!("{}", ledger.accounts.get(key).unwrap()),
+                format!("{}", migrated.accounts.get(key).unwrap())
+            );
+        }
+        assert_eq!(
+            get_file_contents("src/test-files/migrate-test.csv.v1", None).unwrap(),
+            get_file_contents("src/test-files/2-account.csv", None).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_and_toml_write_read_test() {
+        let ledger = Ledger::from(HashMap::from([(String::from("savings"), form_account())]));
+
+        for file_path in [
+            "src/test-files/write-read-test.json",
+            "src/test-files/write-read-test.toml",
+        ] {
+            let format = FileFormat::from_path(file_path).unwrap();
+            write_to_file(file_path, format, &ledger, None).unwrap();
+
+            let contents = get_file_contents(file_path, None).unwrap();
+            assert!(!needs_migration(format, &contents));
+
+            let ledger2 = read(format, contents).unwrap();
+            assert_eq!(
+                format!("{}", ledger.accounts.get("savings").unwrap()),
+                format!("{}", ledger2.accounts.get("savings").unwrap())
+            );
+        }
+
+        assert!(FileFormat::from_path("src/test-files/write-read-test.txt").is_err());
+    }
+
+    #[test]
+    fn write_atomically_test() {
+        let file_path = "src/test-files/atomic-write-test.csv";
+        write_atomically(file_path, "first").unwrap();
+        write_atomically(file_path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(file_path).unwrap(), "second");
+        assert!(!Path::new("src/test-files/atomic-write-test.csv.tmp").exists());
+        assert!(write_atomically("src/test-files/missing/atomic-write-test.csv", "a").is_err());
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(FileFormat::from_path("a.JSON").unwrap(), FileFormat::Json);
+        assert_eq!(FileFormat::from_name("SQLite").unwrap(), FileFormat::Sqlite);
+        assert!(FileFormat::from_name("db").is_err());
+    }
+
+    #[test]
+    fn newer_version() {
+        let error = read_from_string(String::from("budgeting-app,3\r\nrecord\r\n"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "File is format version 3, which is newer than this program supports"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_column() {
+        read_from_string(String::from("budgeting-app,2\r\nrecord,account\r\n")).unwrap();
+    }
+
+    #[test]
+    fn recurring_write_read_test() {
+        let file_path = "src/test-files/recurring-write-read-test.csv";
+
+        let mut ledger = Ledger::default();
+        let day: NaiveDate = "2024-05-31".parse().unwrap();
+        for (label, category, frequency) in [
+            ("Rent", Some(String::from("Housing")), "monthly"),
+            ("Pay", None, "weekly"),
+        ] {
+            ledger.recurring.push(
+                Recurring::build(
+                    "Savings",
+                    label,
+                    Money::new(-100000, 2),
+                    category,
+                    Frequency::build(frequency, day).unwrap(),
+                    day,
+                )
+                .unwrap(),
+            );
+        }
+
+        write_to_file(file_path, FileFormat::Csv, &ledger, None).unwrap();
+
+        let file_contents = get_file_contents(file_path, None).unwrap();
+        assert!(file_contents.ends_with(
+            "recurring,Savings,,2024-05-31,Rent,-1000.00,Housing,,,,monthly:31\r\n\
+            recurring,Savings,,2024-05-31,Pay,-1000.00,,,,,weekly\r\n"
+        ));
+
+        let ledger2 = read_from_string(file_contents).unwrap();
+        assert_eq!(ledger2.recurring.len(), 2);
+        for (a, b) in ledger.recurring.iter().zip(ledger2.recurring.iter()) {
+            assert_eq!(format!("{a}"), format!("{b}"));
+        }
+    }
+
     #[test]
     fn large_write_read_test() {
         let file_path = "src/test-files/large-write-read-test.csv";
@@ -240,7 +895,11 @@ mod tests {
         let mut num = 0;
         for date in days {
             account
-                .add_transaction(&format!("Transaction{}", num), num as f32, date)
+                .add_transaction(
+                    &format!("Transaction{}", num),
+                    Money::new(num * 100, 2),
+                    date,
+                )
                 .unwrap();
             num += 1;
             if num == 1000 {
@@ -248,15 +907,15 @@ mod tests {
             }
         }
 
-        let map1 = HashMap::from([(account.name().to_lowercase(), account)]);
-        let account1 = map1.get("savings").unwrap();
+        let map1 = Ledger::from(HashMap::from([(account.name().to_lowercase(), account)]));
+        let account1 = map1.accounts.get("savings").unwrap();
 
-        write_to_file(file_path, &map1).unwrap();
+        write_to_file(file_path, FileFormat::Csv, &map1, None).unwrap();
 
-        let file_contents = get_file_contents(file_path).unwrap();
+        let file_contents = get_file_contents(file_path, None).unwrap();
 
         let map2 = read_from_string(file_contents).unwrap();
-        let account2 = map2.get("savings").unwrap();
+        let account2 = map2.accounts.get("savings").unwrap();
 
         assert_eq!(&format!("{account1}"), &format!("{account2}"));
     }
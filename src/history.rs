@@ -0,0 +1,341 @@
+use crate::account::{Account, Transaction};
+use crate::budget::Budgets;
+use crate::ledger::Ledger;
+use crate::recurring::Recurring;
+use std::collections::BTreeSet;
+use std::error::Error;
+
+/// How many operations are kept to be undone
+pub const HISTORY_KEPT: usize = 100;
+
+/// One part of an operation, holding what was there before and after it
+pub enum Change {
+    /// An account added or removed whole, keyed by lowercase name
+    Account {
+        key: String,
+        before: Option<Account>,
+        after: Option<Account>,
+    },
+    /// A transaction added, changed or removed in an account that was kept
+    Transaction {
+        key: String,
+        id: u32,
+        before: Option<Transaction>,
+        after: Option<Transaction>,
+    },
+    Budgets {
+        before: Budgets,
+        after: Budgets,
+    },
+    Recurring {
+        before: Vec<Recurring>,
+        after: Vec<Recurring>,
+    },
+}
+
+impl Change {
+    /// Puts back the state from before the change if `undo`, otherwise from after it
+    pub fn apply(&self, ledger: &mut Ledger, undo: bool) -> Result<(), Box<dyn Error>> {
+        match self {
+            Change::Account { key, before, after } => match if undo { before } else { after } {
+                Some(account) => {
+                    ledger.accounts.insert(key.clone(), account.clone());
+                }
+                None => {
+                    ledger.accounts.remove(key);
+                }
+            },
+            Change::Transaction {
+                key,
+                id,
+                before,
+                after,
+            } => {
+                let account = match ledger.accounts.get_mut(key) {
+                    Some(account) => account,
+                    None => return Err(Box::from(format!("Account {key} no longer exists"))),
+                };
+                match if undo { before } else { after } {
+                    Some(transaction) => account.put_transaction(*id, transaction)?,
+                    None => {
+                        if account.transactions().contains_key(id) {
+                            account.remove_transaction(*id)?;
+                        }
+                    }
+                }
+            }
+            Change::Budgets { before, after } => {
+                ledger.budgets = if undo { before } else { after }.clone();
+            }
+            Change::Recurring { before, after } => {
+                ledger.recurring = if undo { before } else { after }.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A command that changed the ledger, as the changes it made
+struct Operation {
+    command: String,
+    changes: Vec<Change>,
+}
+
+/// Operations that can be undone, and those undone that can be redone
+#[derive(Default)]
+pub struct History {
+    /// Oldest first
+    done: Vec<Operation>,
+    /// Most recently undone last
+    undone: Vec<Operation>,
+}
+
+impl History {
+    /// Records what a command changed, as found by `changes_between`. Commands that changed
+    /// nothing are not recorded. Anything undone can no longer be redone once more is changed.
+    pub fn record(&mut self, command: &str, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        self.undone.clear();
+        self.done.push(Operation {
+            command: String::from(command),
+            changes,
+        });
+        if self.done.len() > HISTORY_KEPT {
+            self.done.remove(0);
+        }
+    }
+
+    /// Undoes the last `count` operations, returning the commands undone, most recent first
+    pub fn undo(
+        &mut self,
+        ledger: &mut Ledger,
+        count: usize,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        check_count(count, self.done.len(), "undone")?;
+        let mut commands = Vec::new();
+        for _ in 0..count {
+            // unwrap safe as the count was checked
+            let operation = self.done.pop().unwrap();
+            for change in operation.changes.iter().rev() {
+                change.apply(ledger, true)?;
+            }
+            commands.push(operation.command.clone());
+            self.undone.push(operation);
+        }
+        Ok(commands)
+    }
+
+    /// Redoes the last `count` operations undone, returning the commands redone in order
+    pub fn redo(
+        &mut self,
+        ledger: &mut Ledger,
+        count: usize,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        check_count(count, self.undone.len(), "redone")?;
+        let mut commands = Vec::new();
+        for _ in 0..count {
+            // unwrap safe as the count was checked
+            let operation = self.undone.pop().unwrap();
+            for change in &operation.changes {
+                change.apply(ledger, false)?;
+            }
+            commands.push(operation.command.clone());
+            self.done.push(operation);
+        }
+        Ok(commands)
+    }
+
+    /// Commands that can be undone, most recent first
+    pub fn done(&self) -> Vec<&str> {
+        self.done.iter().rev().map(|o| o.command.as_str()).collect()
+    }
+
+    /// Commands that can be redone, next to be redone first
+    pub fn undone(&self) -> Vec<&str> {
+        self.undone
+            .iter()
+            .rev()
+            .map(|o| o.command.as_str())
+            .collect()
+    }
+}
+
+fn check_count(count: usize, available: usize, action: &str) -> Result<(), Box<dyn Error>> {
+    match available {
+        0 => Err(Box::from(format!("There is nothing to be {action}"))),
+        _ if count == 0 || count > available => Err(Box::from(format!(
+            "Between 1 and {available} operations can be {action}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// What was changed going from `before` to `after`
+pub fn changes_between(before: &Ledger, after: &Ledger) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let keys: BTreeSet<&String> = before
+        .accounts
+        .keys()
+        .chain(after.accounts.keys())
+        .collect();
+    for key in keys {
+        match (before.accounts.get(key), after.accounts.get(key)) {
+            (Some(old), Some(new)) => {
+                let ids: BTreeSet<&u32> = old
+                    .transactions()
+                    .keys()
+                    .chain(new.transactions().keys())
+                    .collect();
+                for id in ids {
+                    let (was, is) = (old.transactions().get(id), new.transactions().get(id));
+                    if was != is {
+                        changes.push(Change::Transaction {
+                            key: key.clone(),
+                            id: *id,
+                            before: was.cloned(),
+                            after: is.cloned(),
+                        });
+                    }
+                }
+            }
+            (old, new) => changes.push(Change::Account {
+                key: key.clone(),
+                before: old.cloned(),
+                after: new.cloned(),
+            }),
+        }
+    }
+
+    if before.budgets != after.budgets {
+        changes.push(Change::Budgets {
+            before: before.budgets.clone(),
+            after: after.budgets.clone(),
+        });
+    }
+    if before.recurring != after.recurring {
+        changes.push(Change::Recurring {
+            before: before.recurring.clone(),
+            after: after.recurring.clone(),
+        });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::history::{changes_between, History};
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::transfer::transfer;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn balances(ledger: &Ledger) -> Vec<(String, Money, usize)> {
+        let mut balances: Vec<(String, Money, usize)> = ledger
+            .accounts
+            .iter()
+            .map(|(key, a)| (key.clone(), *a.balance(), a.transactions().len()))
+            .collect();
+        balances.sort_by(|a, b| a.0.cmp(&b.0));
+        balances
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        let mut ledger = Ledger::from(HashMap::from([(
+            String::from("savings"),
+            Account::build("Savings").unwrap(),
+        )]));
+        let start = balances(&ledger);
+
+        let before = ledger.clone();
+        ledger
+            .accounts
+            .insert(String::from("cash"), Account::build("Cash").unwrap());
+        history.record("aa cash", changes_between(&before, &ledger));
+
+        let before = ledger.clone();
+        transfer(
+            &mut ledger.accounts,
+            "savings",
+            "cash",
+            Money::new(500, 2),
+            date("2024-05-25"),
+        )
+        .unwrap();
+        history.record("tr savings cash 5", changes_between(&before, &ledger));
+
+        let before = ledger.clone();
+        ledger
+            .budgets
+            .set_limit("Fun", Money::new(5000, 2))
+            .unwrap();
+        history.record("ab fun 50", changes_between(&before, &ledger));
+
+        // changing nothing is not recorded
+        history.record("cr", changes_between(&ledger, &ledger));
+        assert_eq!(
+            history.done(),
+            vec!["ab fun 50", "tr savings cash 5", "aa cash"]
+        );
+        let end = balances(&ledger);
+
+        assert_eq!(
+            history.undo(&mut ledger, 2).unwrap(),
+            vec!["ab fun 50", "tr savings cash 5"]
+        );
+        assert!(ledger.budgets.is_empty());
+        assert_eq!(ledger.accounts["savings"].balance(), &Money::zero());
+        assert_eq!(ledger.accounts["cash"].transactions().len(), 0);
+        assert_eq!(history.undone(), vec!["tr savings cash 5", "ab fun 50"]);
+
+        history.undo(&mut ledger, 1).unwrap();
+        assert_eq!(balances(&ledger), start);
+        assert!(history.undo(&mut ledger, 1).is_err());
+
+        assert_eq!(
+            history.redo(&mut ledger, 3).unwrap(),
+            vec!["aa cash", "tr savings cash 5", "ab fun 50"]
+        );
+        assert_eq!(balances(&ledger), end);
+        assert!(!ledger.budgets.is_empty());
+        let link = ledger.accounts["cash"].transactions()[&1]
+            .transfer()
+            .unwrap()
+            .clone();
+        assert_eq!((link.account(), link.id()), ("Savings", 1));
+    }
+
+    #[test]
+    fn new_change_clears_redo() {
+        let mut history = History::default();
+        let mut ledger = Ledger::default();
+
+        let before = ledger.clone();
+        ledger
+            .accounts
+            .insert(String::from("cash"), Account::build("Cash").unwrap());
+        history.record("aa cash", changes_between(&before, &ledger));
+        history.undo(&mut ledger, 1).unwrap();
+        assert!(ledger.accounts.is_empty());
+
+        let before = ledger.clone();
+        ledger
+            .accounts
+            .insert(String::from("card"), Account::build("Card").unwrap());
+        history.record("aa card", changes_between(&before, &ledger));
+
+        assert!(history.undone().is_empty());
+        assert!(history.redo(&mut ledger, 1).is_err());
+        assert!(history.undo(&mut ledger, 2).is_err());
+    }
+}
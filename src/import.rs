@@ -0,0 +1,468 @@
+use crate::account::Account;
+use crate::csv;
+use crate::money::Money;
+use crate::ofx;
+use chrono::NaiveDate;
+use convert_case::{Case, Casing};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Where a field is found in each row: a column number counting from 1, or a header name
+enum Column {
+    Number(usize),
+    Name(String),
+}
+
+impl Column {
+    fn parse(s: &str) -> Result<Column, Box<dyn Error>> {
+        match s.parse::<usize>() {
+            Ok(0) => Err(Box::from("Column numbers start at 1")),
+            Ok(number) => Ok(Column::Number(number)),
+            Err(_) if s.is_empty() => Err(Box::from("Column name cannot be empty")),
+            Err(_) => Ok(Column::Name(s.to_lowercase())),
+        }
+    }
+
+    /// The index of this column in each row
+    fn resolve(&self, header: Option<&[String]>) -> Result<usize, Box<dyn Error>> {
+        match (self, header) {
+            (Column::Number(number), _) => Ok(number - 1),
+            (Column::Name(name), Some(header)) => header
+                .iter()
+                .position(|h| h.trim().to_lowercase() == *name)
+                .ok_or_else(|| Box::from(format!("No column named {name} in header"))),
+            (Column::Name(name), None) => Err(Box::from(format!(
+                "Column {name} can only be found by name when the file has a header"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum AmountColumns<C> {
+    /// One column, negative for money going out
+    Signed(C),
+    /// Money out and money in in separate columns, either of which may be blank
+    DebitCredit(C, C),
+}
+
+/// Which columns of a bank statement hold each part of a transaction
+pub struct CsvMapping {
+    date: Column,
+    date_format: String,
+    amount: AmountColumns<Column>,
+    description: Column,
+    header: bool,
+}
+
+impl Default for CsvMapping {
+    fn default() -> CsvMapping {
+        CsvMapping {
+            date: Column::Number(1),
+            date_format: String::from("%Y-%m-%d"),
+            amount: AmountColumns::Signed(Column::Number(3)),
+            description: Column::Number(2),
+            header: true,
+        }
+    }
+}
+
+impl CsvMapping {
+    /// Builds a mapping from options such as "date=2", "format=%d/%m/%Y", "amount=Amount",
+    /// "debit=4", "credit=5", "desc=3" and "header=no", using the defaults for any left out
+    pub fn build(options: &[String]) -> Result<CsvMapping, Box<dyn Error>> {
+        let mut mapping = CsvMapping::default();
+        let mut amount = None;
+        let mut debit = None;
+        let mut credit = None;
+
+        for option in options {
+            let (key, value) = match option.split_once('=') {
+                Some(pair) => pair,
+                None => return Err(Box::from(format!("Option {option} should be key=value"))),
+            };
+            match key.to_lowercase().as_str() {
+                "date" => mapping.date = Column::parse(value)?,
+                "format" => mapping.date_format = String::from(value),
+                "amount" => amount = Some(Column::parse(value)?),
+                "debit" => debit = Some(Column::parse(value)?),
+                "credit" => credit = Some(Column::parse(value)?),
+                "desc" => mapping.description = Column::parse(value)?,
+                "header" => {
+                    mapping.header = match value.to_lowercase().as_str() {
+                        "yes" | "true" => true,
+                        "no" | "false" => false,
+                        _ => return Err(Box::from(format!("Header option {value} invalid"))),
+                    }
+                }
+                _ => return Err(Box::from(format!("Unknown import option {key}"))),
+            }
+        }
+
+        mapping.amount = match (amount, debit, credit) {
+            (Some(amount), None, None) => AmountColumns::Signed(amount),
+            (None, Some(debit), Some(credit)) => AmountColumns::DebitCredit(debit, credit),
+            (None, None, None) => mapping.amount,
+            (Some(_), _, _) => {
+                return Err(Box::from(
+                    "Use either an amount column or debit and credit columns",
+                ))
+            }
+            _ => return Err(Box::from("Debit and credit columns must be given together")),
+        };
+
+        Ok(mapping)
+    }
+}
+
+/// How many rows of a statement were added to an account and how many were already there
+pub struct ImportSummary {
+    account: String,
+    added: usize,
+    skipped: usize,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Imported {} transactions into {}, skipped {} already present",
+            self.added, self.account, self.skipped
+        )
+    }
+}
+
+/// Adds every row of a CSV bank statement to `account`. A row is skipped if the account
+/// already holds a transaction with the same date, label and amount, each existing
+/// transaction matching at most one row so repeated purchases in a day are kept.
+/// Nothing is added if any row cannot be read or added.
+pub fn import_csv(
+    account: &mut Account,
+    contents: &str,
+    mapping: &CsvMapping,
+) -> Result<ImportSummary, Box<dyn Error>> {
+    let mut records = csv::parse(contents)?;
+    let header = match mapping.header && !records.is_empty() {
+        true => Some(records.remove(0)),
+        false => None,
+    };
+    let first_row = if header.is_some() { 2 } else { 1 };
+
+    let date = mapping.date.resolve(header.as_deref())?;
+    let description = mapping.description.resolve(header.as_deref())?;
+    let amount = match &mapping.amount {
+        AmountColumns::Signed(column) => AmountColumns::Signed(column.resolve(header.as_deref())?),
+        AmountColumns::DebitCredit(debit, credit) => AmountColumns::DebitCredit(
+            debit.resolve(header.as_deref())?,
+            credit.resolve(header.as_deref())?,
+        ),
+    };
+
+    let mut rows: Vec<(NaiveDate, String, Money)> = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        // blank lines are common at the end of exports
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let row = index + first_row;
+        let field = |column: usize| match record.get(column) {
+            Some(field) => Ok(field.trim()),
+            None => Err(Box::<dyn Error>::from(format!(
+                "Row {row} has no column {}",
+                column + 1
+            ))),
+        };
+
+        let row_date = match NaiveDate::parse_from_str(field(date)?, &mapping.date_format) {
+            Ok(d) => d,
+            Err(e) => return Err(Box::from(format!("Row {row} date invalid: {e}"))),
+        };
+
+        let label = field(description)?.to_case(Case::Title);
+        if label.is_empty() {
+            return Err(Box::from(format!("Row {row} has no description")));
+        }
+
+        let row_amount = match amount {
+            AmountColumns::Signed(column) => parse_amount(field(column)?),
+            AmountColumns::DebitCredit(debit, credit) => {
+                debit_credit(field(debit)?, field(credit)?)
+            }
+        };
+        let row_amount = match row_amount {
+            Ok(a) => a,
+            Err(e) => return Err(Box::from(format!("Row {row} amount invalid: {e}"))),
+        };
+
+        rows.push((row_date, label, row_amount));
+    }
+
+    let mut existing: Vec<(NaiveDate, String, Money)> = account
+        .transactions()
+        .values()
+        .map(|t| (*t.date(), String::from(t.label()), *t.amount()))
+        .collect();
+
+    let mut summary = ImportSummary {
+        account: String::from(account.name()),
+        added: 0,
+        skipped: 0,
+    };
+    // rows are added to a copy so the account is left as it was if one fails
+    let mut imported = account.clone();
+    for row in rows {
+        if let Some(position) = existing.iter().position(|e| *e == row) {
+            existing.swap_remove(position);
+            summary.skipped += 1;
+            continue;
+        }
+        let (row_date, label, row_amount) = row;
+        imported.add_transaction(&label, row_amount, row_date)?;
+        summary.added += 1;
+    }
+
+    *account = imported;
+    Ok(summary)
+}
+
+/// Adds every transaction of an OFX or QFX statement to `account`, skipping any whose
+/// FITID has already been imported. Nothing is added if the statement cannot be read or
+/// any transaction in it cannot be added.
+pub fn import_ofx(account: &mut Account, contents: &str) -> Result<ImportSummary, Box<dyn Error>> {
+    let entries = ofx::parse(contents)?;
+
+    let mut seen: HashSet<String> = account
+        .transactions()
+        .values()
+        .filter_map(|t| t.import_id().map(String::from))
+        .collect();
+
+    let mut summary = ImportSummary {
+        account: String::from(account.name()),
+        added: 0,
+        skipped: 0,
+    };
+    let mut imported = account.clone();
+    for entry in entries {
+        let import_id = String::from(entry.fitid());
+        if !seen.insert(import_id.clone()) {
+            summary.skipped += 1;
+            continue;
+        }
+        let label = entry.label().to_case(Case::Title);
+        let id = imported.add_transaction(&label, *entry.amount(), *entry.date())?;
+        imported.set_transaction_import_id(id, Some(import_id))?;
+        summary.added += 1;
+    }
+
+    *account = imported;
+    Ok(summary)
+}
+
+/// Reads an amount as banks write them, allowing a currency sign, thousands separators
+/// and brackets for negative amounts, e.g. "$1,234.50" or "(12.00)"
+fn parse_amount(s: &str) -> Result<Money, Box<dyn Error>> {
+    let cleaned: String = s
+        .chars()
+        .filter(|c| !matches!(c, '$' | ',' | ' '))
+        .collect();
+    match cleaned
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        Some(inner) => inner.parse::<Money>()?.checked_neg(),
+        None => cleaned.parse(),
+    }
+}
+
+/// Combines separate money out and money in columns into one signed amount. Debits are
+/// taken as money out whichever sign the bank gives them.
+fn debit_credit(debit: &str, credit: &str) -> Result<Money, Box<dyn Error>> {
+    let read = |s: &str| match s.is_empty() {
+        true => Ok(Money::zero()),
+        false => parse_amount(s),
+    };
+    let (debit, credit) = (read(debit)?, read(credit)?);
+    if debit.is_zero() && credit.is_zero() {
+        return Err(Box::from("Debit and credit are both empty"));
+    }
+
+    let debit = match debit.is_negative() {
+        true => debit,
+        false => debit.checked_neg()?,
+    };
+    credit.checked_add(debit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::import::*;
+    use crate::money::Money;
+
+    fn options(options: &[&str]) -> Vec<String> {
+        options.iter().map(|o| String::from(*o)).collect()
+    }
+
+    fn lines(account: &Account) -> Vec<String> {
+        let mut transactions: Vec<_> = account.transactions().values().collect();
+        transactions.sort_by_key(|t| t.id());
+        transactions.iter().map(|t| format!("{t}")).collect()
+    }
+
+    #[test]
+    fn signed_amount() {
+        let mut account = Account::build("Savings").unwrap();
+        let statement = "Date,Description,Amount\n\
+            2024-05-01,WOOLWORTHS SYDNEY,-45.20\n\
+            2024-05-02,\"Salary, May\",\"$1,250.00\"\n\
+            \n";
+
+        let summary = import_csv(&mut account, statement, &CsvMapping::default()).unwrap();
+
+        assert_eq!(
+            format!("{summary}"),
+            "Imported 2 transactions into Savings, skipped 0 already present"
+        );
+        assert_eq!(
+            lines(&account),
+            vec![
+                "ID: 1 | Date: 01 May 2024 | Label: Woolworths Sydney | Amount: $-45.20",
+                "ID: 2 | Date: 02 May 2024 | Label: Salary, May | Amount: $1250.00",
+            ]
+        );
+    }
+
+    #[test]
+    fn debit_credit_by_name() {
+        let mut account = Account::build("Savings").unwrap();
+        let statement = "Posted,Details,Money Out,Money In\n\
+            26/05/2024,Coffee,4.50,\n\
+            26/05/2024,Coffee,4.50,\n\
+            27/05/2024,Refund,,(0.50)\n";
+        let mapping = CsvMapping::build(&options(&[
+            "date=posted",
+            "format=%d/%m/%Y",
+            "desc=Details",
+            "debit=money out",
+            "credit=4",
+        ]))
+        .unwrap();
+
+        import_csv(&mut account, statement, &mapping).unwrap();
+        assert_eq!(account.balance(), &Money::new(-950, 2));
+
+        // both coffees are already there now, but a third one on the same day is new
+        let statement = format!("{statement}26/05/2024,Coffee,4.50,\n");
+        let summary = import_csv(&mut account, &statement, &mapping).unwrap();
+
+        assert_eq!(
+            format!("{summary}"),
+            "Imported 1 transactions into Savings, skipped 3 already present"
+        );
+        assert_eq!(account.transactions().len(), 4);
+    }
+
+    #[test]
+    fn no_header() {
+        let mut account = Account::build("Savings").unwrap();
+        let mapping =
+            CsvMapping::build(&options(&["header=no", "amount=1", "date=2", "desc=3"])).unwrap();
+
+        import_csv(&mut account, "-3.00,2024-05-01,Bus", &mapping).unwrap();
+
+        assert_eq!(
+            lines(&account),
+            vec!["ID: 1 | Date: 01 May 2024 | Label: Bus | Amount: $-3.00"]
+        );
+
+        let mapping = CsvMapping::build(&options(&["header=no", "desc=Details"])).unwrap();
+        assert!(import_csv(&mut account, "2024-05-01,Bus,-3.00", &mapping).is_err());
+    }
+
+    #[test]
+    fn bad_row_adds_nothing() {
+        let mut account = Account::build("Savings").unwrap();
+        let statement = "Date,Description,Amount\n\
+            2024-05-01,Shop,-1.00\n\
+            2024-05-32,Shop,-1.00\n";
+
+        assert!(import_csv(&mut account, statement, &CsvMapping::default()).is_err());
+        assert!(account.transactions().is_empty());
+    }
+
+    #[test]
+    fn failed_row_leaves_account() {
+        let mut account = Account::build("Savings").unwrap();
+        account
+            .add_transaction("Pay", Money::new(100, 2), "2024-04-30".parse().unwrap())
+            .unwrap();
+        let before = lines(&account);
+
+        let statement = "Date,Description,Amount\n\
+            2024-05-01,Windfall,92233720368547757.00\n\
+            2024-05-02,Interest,2.00\n";
+        assert!(import_csv(&mut account, statement, &CsvMapping::default()).is_err());
+
+        let statement = "<OFX><BANKTRANLIST>\n\
+            <STMTTRN><DTPOSTED>20240501<TRNAMT>92233720368547758.00<FITID>A1<NAME>Windfall</STMTTRN>\n\
+            <STMTTRN><DTPOSTED>20240502<TRNAMT>1.00<FITID>A2<NAME>Interest</STMTTRN>\n\
+            </BANKTRANLIST></OFX>";
+        assert!(import_ofx(&mut account, statement).is_err());
+
+        assert_eq!(lines(&account), before);
+        assert_eq!(account.balance(), &Money::new(100, 2));
+    }
+
+    #[test]
+    fn ofx_skips_known_fitids() {
+        let mut account = Account::build("Savings").unwrap();
+        let statement = "<OFX><BANKTRANLIST>\n\
+            <STMTTRN><DTPOSTED>20240502<TRNAMT>-45.20<FITID>A1<NAME>WOOLWORTHS, TOWN HALL</STMTTRN>\n\
+            <STMTTRN><DTPOSTED>20240502<TRNAMT>-45.20<FITID>A2<NAME>WOOLWORTHS, TOWN HALL</STMTTRN>\n\
+            </BANKTRANLIST></OFX>";
+
+        let summary = import_ofx(&mut account, statement).unwrap();
+        assert_eq!(
+            format!("{summary}"),
+            "Imported 2 transactions into Savings, skipped 0 already present"
+        );
+        assert_eq!(
+            account.transactions().get(&1).unwrap().label(),
+            "Woolworths, Town Hall"
+        );
+        assert_eq!(
+            account.transactions().get(&2).unwrap().import_id(),
+            Some("A2")
+        );
+
+        let statement = statement.replace(
+            "</BANKTRANLIST>",
+            "<STMTTRN><DTPOSTED>20240503\
+            <TRNAMT>100<FITID>A3<MEMO>Pay</STMTTRN></BANKTRANLIST>",
+        );
+        let summary = import_ofx(&mut account, &statement).unwrap();
+
+        assert_eq!(
+            format!("{summary}"),
+            "Imported 1 transactions into Savings, skipped 2 already present"
+        );
+        assert_eq!(account.balance(), &Money::new(960, 2));
+    }
+
+    #[test]
+    fn bad_options() {
+        for bad in [
+            vec!["date"],
+            vec!["date=0"],
+            vec!["colour=red"],
+            vec!["header=maybe"],
+            vec!["debit=3"],
+            vec!["amount=3", "debit=4", "credit=5"],
+        ] {
+            assert!(CsvMapping::build(&options(&bad)).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -1,29 +1,74 @@
 use crate::account::Account;
-use chrono::NaiveDate;
+use crate::budget::Budgets;
+use crate::commands;
+use crate::history::History;
+use crate::import::{self, CsvMapping};
+use crate::journal::Journal;
+use crate::ledger::Ledger;
+use crate::money::Money;
+use crate::qif;
+use crate::recurring::{Frequency, Recurring};
+use crate::report;
+use crate::search::{self, Filter};
+use crate::storage::Storage;
+use crate::transfer;
+use chrono::{Local, NaiveDate};
 use convert_case::{Case, Casing};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
+
+/// How labels entered are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum LabelCase {
+    /// Each word capitalised, as by default
+    Title,
+    /// Exactly as typed, chosen with `--keep-case`
+    AsTyped,
+}
+
+impl LabelCase {
+    pub(super) fn apply(self, label: &str) -> String {
+        match self {
+            LabelCase::Title => label.to_case(Case::Title),
+            LabelCase::AsTyped => String::from(label),
+        }
+    }
+}
 
 pub(super) fn add_new_transaction(
     inputs: Vec<String>,
     accounts: &mut HashMap<String, Account>,
+    case: LabelCase,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 4)?;
+    check_input_length_between(&inputs, 4, 6)?;
 
     // Can unwrap freely due to check of input len
     let account_name = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let label = case.apply(inputs.get(2).unwrap());
 
     let amount = get_transaction_amount(&inputs)?;
 
+    let category = get_category(&inputs, 4);
+
+    // Today unless a date is given after the category
+    let date = match inputs.get(5) {
+        Some(_) => Some(get_date_at(&inputs, 5)?),
+        None => None,
+    };
+
     let account: &mut Account = match accounts.get_mut(&account_name) {
         Some(a) => a,
         None => return Err(Box::from(format!("Account name {account_name} invalid"))),
     };
 
-    account.add_new_transaction(&label, amount)?;
+    let id = match date {
+        Some(date) => account.add_transaction(&label, amount, date)?,
+        None => account.add_new_transaction(&label, amount)?,
+    };
+    account.edit_transaction_category(id, category)?;
 
     Ok(())
 }
@@ -31,24 +76,28 @@ pub(super) fn add_new_transaction(
 pub(super) fn add_transaction(
     inputs: Vec<String>,
     accounts: &mut HashMap<String, Account>,
+    case: LabelCase,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 5)?;
+    check_input_length_between(&inputs, 5, 6)?;
 
     // Can unwrap freely due to check of input len
     let account_name = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let label = case.apply(inputs.get(2).unwrap());
 
     let amount = get_transaction_amount(&inputs)?;
 
     let date = get_transaction_date(&inputs)?;
 
+    let category = get_category(&inputs, 5);
+
     let account: &mut Account = match accounts.get_mut(&account_name) {
         Some(a) => a,
         None => return Err(Box::from(format!("Account name {account_name} invalid"))),
     };
 
-    account.add_transaction(&label, amount, date)?;
+    let id = account.add_transaction(&label, amount, date)?;
+    account.edit_transaction_category(id, category)?;
 
     Ok(())
 }
@@ -67,10 +116,7 @@ pub(super) fn add_account(
         ));
     }
 
-    let new_account = match Account::build(&account_name) {
-        Ok(a) => a,
-        Err(e) => return Err(e),
-    };
+    let new_account = Account::build(&account_name)?;
     accounts.insert(account_name.to_lowercase(), new_account);
 
     Ok(())
@@ -90,131 +136,549 @@ pub(super) fn remove_account(
         ));
     }
 
+    transfer::unlink_account(accounts, &account_name.to_lowercase())?;
     accounts.remove(&account_name.to_lowercase());
 
     Ok(())
 }
 
-pub(super) fn edit_transaction_amount(
+pub(super) fn add_transfer(
     inputs: Vec<String>,
     accounts: &mut HashMap<String, Account>,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 6)?;
+    // date is optional and defaults to today
+    let date = match inputs.len() {
+        4 => NaiveDate::from(Local::now().naive_local()),
+        _ => {
+            check_input_length(&inputs, 5)?;
+            get_transaction_date(&inputs)?
+        }
+    };
 
     // Can unwrap freely due to check of input len
-    let account_name = get_account_name(&inputs, accounts)?;
+    let from = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let to = get_account_name_at(&inputs, 2, accounts)?;
 
     let amount = get_transaction_amount(&inputs)?;
 
-    let date = get_transaction_date(&inputs)?;
+    transfer::transfer(accounts, &from, &to, amount, date)?;
+
+    Ok(())
+}
+
+pub(super) fn edit_transaction_amount(
+    inputs: Vec<String>,
+    accounts: &mut HashMap<String, Account>,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 4)?;
 
-    let new_amount: f32 = match inputs.get(5).unwrap().parse() {
+    // Can unwrap freely due to check of input len
+    let account_name = get_account_name(&inputs, accounts)?;
+
+    let id = get_transaction_id(&inputs)?;
+
+    let new_amount: Money = match inputs.get(3).unwrap().parse() {
         Ok(f) => f,
         Err(e) => return Err(Box::from(format!("Amount entered invalid: {}", e))),
     };
 
-    let transaction_key = format!("{}-{}-{}", date, label, amount);
+    transfer::edit_transaction_amount(accounts, &account_name, id, new_amount)?;
 
-    let account: &mut Account = match accounts.get_mut(&account_name) {
-        Some(a) => a,
-        None => return Err(Box::from(format!("Account name {account_name} invalid"))),
+    Ok(())
+}
+
+pub(super) fn edit_transaction_date(
+    inputs: Vec<String>,
+    accounts: &mut HashMap<String, Account>,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 4)?;
+
+    // Can unwrap freely due to check of input len
+    let account_name = get_account_name(&inputs, accounts)?;
+
+    let id = get_transaction_id(&inputs)?;
+
+    let new_date: NaiveDate = match inputs.get(3).unwrap().parse() {
+        Ok(f) => f,
+        Err(e) => return Err(Box::from(format!("Date entered invalid: {}", e))),
     };
 
-    account.edit_transaction_amount(&transaction_key, new_amount)?;
+    transfer::edit_transaction_date(accounts, &account_name, id, new_date)?;
 
     Ok(())
 }
 
-pub(super) fn edit_transaction_date(
+pub(super) fn edit_transaction_label(
     inputs: Vec<String>,
     accounts: &mut HashMap<String, Account>,
+    case: LabelCase,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 6)?;
+    check_input_length(&inputs, 4)?;
 
     // Can unwrap freely due to check of input len
     let account_name = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let id = get_transaction_id(&inputs)?;
 
-    let amount = get_transaction_amount(&inputs)?;
+    let new_label = case.apply(inputs.get(3).unwrap());
 
-    let date = get_transaction_date(&inputs)?;
+    transfer::edit_transaction_label(accounts, &account_name, id, new_label)?;
 
-    let new_date: NaiveDate = match inputs.get(5).unwrap().parse() {
-        Ok(f) => f,
-        Err(e) => return Err(Box::from(format!("Date entered invalid: {}", e))),
-    };
+    Ok(())
+}
+
+pub(super) fn edit_transaction_category(
+    inputs: Vec<String>,
+    accounts: &mut HashMap<String, Account>,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length_between(&inputs, 3, 4)?;
+
+    // Can unwrap freely due to check of input len
+    let account_name = get_account_name(&inputs, accounts)?;
+
+    let id = get_transaction_id(&inputs)?;
 
-    let transaction_key = format!("{}-{}-{}", date, label, amount);
+    // leaving out the category clears it
+    let new_category = get_category(&inputs, 3);
 
     let account: &mut Account = match accounts.get_mut(&account_name) {
         Some(a) => a,
         None => return Err(Box::from(format!("Account name {account_name} invalid"))),
     };
 
-    account.edit_transaction_date(&transaction_key, new_date)?;
+    account.edit_transaction_category(id, new_category)?;
 
     Ok(())
 }
 
-pub(super) fn edit_transaction_label(
+pub(super) fn remove_transaction(
     inputs: Vec<String>,
     accounts: &mut HashMap<String, Account>,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 6)?;
+    check_input_length(&inputs, 3)?;
 
     // Can unwrap freely due to check of input len
     let account_name = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let id = get_transaction_id(&inputs)?;
 
-    let amount = get_transaction_amount(&inputs)?;
+    transfer::remove_transaction(accounts, &account_name, id)?;
 
-    let date = get_transaction_date(&inputs)?;
+    Ok(())
+}
 
-    let new_label = inputs.get(5).unwrap().to_case(Case::Title);
+pub(super) fn set_budget(inputs: Vec<String>, budgets: &mut Budgets) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 3)?;
 
-    let transaction_key = format!("{}-{}-{}", date, label, amount);
+    // Can unwrap freely due to check of input len
+    let category = inputs.get(1).unwrap().to_case(Case::Title);
 
-    let account: &mut Account = match accounts.get_mut(&account_name) {
-        Some(a) => a,
-        None => return Err(Box::from(format!("Account name {account_name} invalid"))),
+    let limit: Money = match inputs.get(2).unwrap().parse() {
+        Ok(f) => f,
+        Err(e) => return Err(Box::from(format!("Amount entered invalid: {}", e))),
     };
 
-    account.edit_transaction_label(&transaction_key, new_label)?;
+    budgets.set_limit(&category, limit)?;
 
     Ok(())
 }
 
-pub(super) fn remove_transaction(
+pub(super) fn remove_budget(
     inputs: Vec<String>,
-    accounts: &mut HashMap<String, Account>,
+    budgets: &mut Budgets,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 2)?;
+
+    let category = inputs.get(1).unwrap().to_case(Case::Title);
+
+    budgets.remove_limit(&category)?;
+
+    Ok(())
+}
+
+pub(super) fn add_recurring(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+    recurring: &mut Vec<Recurring>,
+    case: LabelCase,
 ) -> Result<(), Box<dyn Error>> {
-    check_input_length(&inputs, 5)?;
+    check_input_length_between(&inputs, 6, 7)?;
 
     // Can unwrap freely due to check of input len
     let account_name = get_account_name(&inputs, accounts)?;
 
-    let label = inputs.get(2).unwrap().to_case(Case::Title);
+    let label = case.apply(inputs.get(2).unwrap());
 
     let amount = get_transaction_amount(&inputs)?;
 
-    let date = get_transaction_date(&inputs)?;
+    let first = get_date_at(&inputs, 5)?;
 
-    let transaction_key = format!("{}-{}-{}", date, label, amount);
+    let frequency = Frequency::build(inputs.get(4).unwrap(), first)?;
 
-    let account: &mut Account = match accounts.get_mut(&account_name) {
-        Some(a) => a,
-        None => return Err(Box::from(format!("Account name {account_name} invalid"))),
+    let category = get_category(&inputs, 6);
+
+    // unwrap safe as get_account_name checked the account exists
+    let account = accounts.get(&account_name).unwrap();
+
+    recurring.push(Recurring::build(
+        account.name(),
+        &label,
+        amount,
+        category,
+        frequency,
+        first,
+    )?);
+
+    Ok(())
+}
+
+pub(super) fn remove_recurring(
+    inputs: Vec<String>,
+    recurring: &mut Vec<Recurring>,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 2)?;
+
+    let input = inputs.get(1).unwrap();
+    match input.parse::<usize>() {
+        Ok(number) if (1..=recurring.len()).contains(&number) => {
+            recurring.remove(number - 1);
+            Ok(())
+        }
+        _ => Err(Box::from(format!(
+            "Recurring transaction number {input} invalid"
+        ))),
+    }
+}
+
+/// Prints totals per category for the given dates, or for this month if none are given
+pub(super) fn category_report(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    let (start, end) = get_report_dates(&inputs)?;
+    let totals = report::category_totals(accounts, start, end)?;
+    report::format_category_totals(&totals, start, end)
+}
+
+/// The same report as `category_report`, reading only the transactions it needs from storage
+pub(super) fn saved_category_report(
+    inputs: Vec<String>,
+    storage: &mut dyn Storage,
+) -> Result<String, Box<dyn Error>> {
+    let (start, end) = get_report_dates(&inputs)?;
+    let totals = report::category_totals(&storage.accounts_between(start, end)?, start, end)?;
+    report::format_category_totals(&totals, start, end)
+}
+
+/// The dates a report covers, taking the start or end of this month for either not given
+fn get_report_dates(inputs: &[String]) -> Result<(NaiveDate, NaiveDate), Box<dyn Error>> {
+    check_input_length_between(inputs, 1, 3)?;
+
+    let (month_start, month_end) =
+        report::month_bounds(NaiveDate::from(Local::now().naive_local()));
+    let date_or = |index: usize, default: NaiveDate| match inputs.get(index) {
+        Some(input) if !input.is_empty() => get_date_at(inputs, index),
+        _ => Ok(default),
+    };
+    let (start, end) = (date_or(1, month_start)?, date_or(2, month_end)?);
+
+    if start > end {
+        return Err(Box::from(format!(
+            "Start date {start} is after end date {end}"
+        )));
+    }
+    Ok((start, end))
+}
+
+/// Imports a CSV, OFX or QFX bank statement into an account, returning how many
+/// transactions were added. CSV statements can be followed by column mapping options.
+pub(super) fn import_statement(
+    inputs: Vec<String>,
+    accounts: &mut HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length_at_least(&inputs, 3)?;
+
+    // Can unwrap freely due to check of input len
+    let account_name = get_account_name(&inputs, accounts)?;
+
+    let file_path = inputs.get(2).unwrap();
+    let contents = read_input_file(file_path)?;
+
+    let is_ofx = [".ofx", ".qfx"]
+        .iter()
+        .any(|extension| file_path.to_lowercase().ends_with(extension));
+    let mapping = match is_ofx {
+        true => {
+            check_input_length(&inputs, 3)?;
+            None
+        }
+        false => Some(CsvMapping::build(&inputs[3..])?),
+    };
+
+    // unwrap safe as get_account_name checked the account exists
+    let account = accounts.get_mut(&account_name).unwrap();
+
+    let summary = match mapping {
+        Some(mapping) => import::import_csv(account, &contents, &mapping)?,
+        None => import::import_ofx(account, &contents)?,
     };
+    Ok(summary.to_string())
+}
+
+/// Adds the accounts in a QIF file, describing which were added. Accounts that already
+/// exist are left as they are, and transactions put in no account go in the one given.
+pub(super) fn import_qif(
+    inputs: Vec<String>,
+    accounts: &mut HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 2, 3)?;
+
+    // Can unwrap freely due to check of input len
+    let contents = read_input_file(inputs.get(1).unwrap())?;
+
+    let default_account = inputs.get(2).map(|name| name.to_case(Case::Title));
+
+    let mut read = qif::read(&contents, default_account.as_deref())?;
+
+    let existing: Vec<String> = read
+        .keys()
+        .filter(|key| accounts.contains_key(*key))
+        .cloned()
+        .collect();
+    let mut skipped = Vec::new();
+    for key in existing {
+        transfer::unlink_account(&mut read, &key)?;
+        // unwrap safe as the key was taken from the map
+        skipped.push(String::from(read.remove(&key).unwrap().name()));
+    }
+
+    let mut message = format!(
+        "Added accounts: {}",
+        read.values()
+            .map(|account| account.name())
+            .sorted()
+            .join(", ")
+    );
+    if !skipped.is_empty() {
+        message += &format!(
+            "\nSkipped accounts that already exist: {}",
+            skipped.iter().sorted().join(", ")
+        );
+    }
+    accounts.extend(read);
+
+    Ok(message)
+}
+
+pub(super) fn export_qif(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+) -> Result<(), Box<dyn Error>> {
+    check_input_length(&inputs, 2)?;
 
-    account.remove_transaction(&transaction_key)?;
+    let file_path = inputs.get(1).unwrap();
+    if let Err(e) = fs::write(file_path, qif::write(accounts)) {
+        return Err(Box::from(format!("Failed writing to {file_path}: {e}")));
+    }
 
     Ok(())
 }
 
+/// Every account with its transactions, or just the one named
+pub(super) fn list_accounts(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 1, 2)?;
+
+    if inputs.len() == 2 {
+        let account_name = get_account_name(&inputs, accounts)?;
+        // unwrap safe as get_account_name checked the account exists
+        return Ok(accounts.get(&account_name).unwrap().to_string());
+    }
+    Ok(accounts
+        .values()
+        .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+        .join("\n\n"))
+}
+
+/// Lists the transactions in an account, or in every account, that match the options after it
+pub(super) fn find_transactions(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 1, 8)?;
+
+    let filter = Filter::build(inputs.get(2..).unwrap_or_default())?;
+    let searched: Vec<&Account> = match inputs.get(1) {
+        Some(name) if !name.is_empty() => {
+            let account_name = get_account_name(&inputs, accounts)?;
+            // unwrap safe as get_account_name checked the account exists
+            vec![accounts.get(&account_name).unwrap()]
+        }
+        _ => accounts.values().collect(),
+    };
+    Ok(search::format_matches(&search::find(searched, &filter)))
+}
+
+/// The balance of every account, or of just the one named, a tab-separated line each
+pub(super) fn show_balances(
+    inputs: Vec<String>,
+    accounts: &HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 1, 2)?;
+
+    let shown: Vec<&Account> = match inputs.len() {
+        2 => {
+            let account_name = get_account_name(&inputs, accounts)?;
+            vec![accounts.get(&account_name).unwrap()]
+        }
+        _ => accounts
+            .values()
+            .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+            .collect(),
+    };
+    Ok(shown
+        .iter()
+        .map(|account| format!("{}\t{}", account.name(), account.balance()))
+        .join("\n"))
+}
+
+/// Lists the backups of the data file, numbered oldest first for `restore`
+pub(super) fn show_backups(storage: &dyn Storage) -> Result<String, Box<dyn Error>> {
+    let backups = storage.backups()?;
+    if backups.is_empty() {
+        return Ok(String::from("No backups yet"));
+    }
+    Ok(format!(
+        "Backups:\n{}",
+        backups
+            .iter()
+            .enumerate()
+            .map(|(index, backup)| format!("\t{}. {backup}", index + 1))
+            .join("\n")
+    ))
+}
+
+/// Replaces the data file with a numbered backup, backing up the current file first so
+/// the restore can itself be undone
+pub(super) fn restore_backup(
+    inputs: Vec<String>,
+    storage: &mut dyn Storage,
+) -> Result<String, Box<dyn Error>> {
+    check_input_length(&inputs, 2)?;
+
+    let backups = storage.backups()?;
+    let input = inputs.get(1).unwrap();
+    let backup = match input.parse::<usize>() {
+        Ok(number) if (1..=backups.len()).contains(&number) => &backups[number - 1],
+        _ => return Err(Box::from(format!("Backup number {input} invalid"))),
+    };
+
+    let message = match storage.backup()? {
+        Some(current) => format!("Restored {backup}, keeping the file it replaced as {current}"),
+        None => format!("Restored {backup}"),
+    };
+    storage.restore(backup)?;
+    Ok(message)
+}
+
+/// Undoes the last change, or the given number of them
+pub(super) fn undo(
+    inputs: Vec<String>,
+    history: &mut History,
+    ledger: &mut Ledger,
+) -> Result<String, Box<dyn Error>> {
+    let count = get_count(&inputs)?;
+    let commands = history.undo(ledger, count)?;
+    Ok(format!("Undid: {}", commands.join(", ")))
+}
+
+/// Redoes the last change undone, or the given number of them
+pub(super) fn redo(
+    inputs: Vec<String>,
+    history: &mut History,
+    ledger: &mut Ledger,
+) -> Result<String, Box<dyn Error>> {
+    let count = get_count(&inputs)?;
+    let commands = history.redo(ledger, count)?;
+    Ok(format!("Redid: {}", commands.join(", ")))
+}
+
+/// Lists the changes that can be undone, most recent first, and any that can be redone
+pub(super) fn show_history(history: &History) -> String {
+    let done = history.done();
+    let undone = history.undone();
+    if done.is_empty() && undone.is_empty() {
+        return String::from("No changes yet");
+    }
+
+    let mut lines = vec![String::from("Changes, most recent first:")];
+    lines.extend(
+        done.iter()
+            .enumerate()
+            .map(|(index, command)| format!("\t{}. {command}", index + 1)),
+    );
+    if !undone.is_empty() {
+        lines.push(String::from("Undone, next to redo first:"));
+        lines.extend(
+            undone
+                .iter()
+                .enumerate()
+                .map(|(index, command)| format!("\t{}. {command}", index + 1)),
+        );
+    }
+    lines.join("\n")
+}
+
+/// Lists the changes made to an account, or to one of its transactions, as kept in the journal
+pub(super) fn show_log(inputs: Vec<String>, journal: &Journal) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 2, 3)?;
+
+    let account_name = inputs.get(1).unwrap().to_case(Case::Title);
+    let id = match inputs.len() {
+        3 => Some(get_transaction_id(&inputs)?),
+        _ => None,
+    };
+
+    journal.log(&account_name, id)
+}
+
+/// Describes one command, or lists them all as the menu does if none is given
+pub(super) fn show_help(inputs: Vec<String>) -> Result<String, Box<dyn Error>> {
+    check_input_length_between(&inputs, 1, 2)?;
+
+    match inputs.get(1).map(String::as_str) {
+        None | Some("") => Ok(commands::menu()),
+        Some(word) => match commands::find(word) {
+            Some(command) => Ok(command.help()),
+            None => Err(Box::from(format!("{word} is not a command"))),
+        },
+    }
+}
+
+/// Rebuilds the ledger from the journal, describing how much was replayed
+pub(super) fn replay_journal(
+    inputs: Vec<String>,
+    journal: &Journal,
+) -> Result<(Ledger, String), Box<dyn Error>> {
+    check_input_length(&inputs, 1)?;
+
+    let (ledger, entries) = journal.replay()?;
+    Ok((
+        ledger,
+        format!("Rebuilt the ledger from {entries} journal entries"),
+    ))
+}
+
+fn read_input_file(file_path: &str) -> Result<String, Box<dyn Error>> {
+    match fs::read_to_string(file_path) {
+        Ok(contents) => Ok(contents),
+        Err(e) => Err(Box::from(format!("Could not read {file_path}: {e}"))),
+    }
+}
+
 fn check_input_length(inputs: &[String], input_length: usize) -> Result<(), Box<dyn Error>> {
     if inputs.len() != input_length {
         return Err(Box::from(format!(
@@ -226,11 +690,46 @@ fn check_input_length(inputs: &[String], input_length: usize) -> Result<(), Box<
     Ok(())
 }
 
+fn check_input_length_between(
+    inputs: &[String],
+    min_length: usize,
+    max_length: usize,
+) -> Result<(), Box<dyn Error>> {
+    if inputs.len() < min_length || inputs.len() > max_length {
+        return Err(Box::from(format!(
+            "Wrong number of inputs. {} when it should be between {} and {}",
+            inputs.len() - 1,
+            min_length - 1,
+            max_length - 1
+        )));
+    }
+    Ok(())
+}
+
+fn check_input_length_at_least(inputs: &[String], min_length: usize) -> Result<(), Box<dyn Error>> {
+    if inputs.len() < min_length {
+        return Err(Box::from(format!(
+            "Wrong number of inputs. {} when it should be at least {}",
+            inputs.len() - 1,
+            min_length - 1
+        )));
+    }
+    Ok(())
+}
+
 fn get_account_name(
     inputs: &[String],
     accounts: &HashMap<String, Account>,
 ) -> Result<String, Box<dyn Error>> {
-    let account_name = inputs.get(1).unwrap().to_lowercase();
+    get_account_name_at(inputs, 1, accounts)
+}
+
+fn get_account_name_at(
+    inputs: &[String],
+    index: usize,
+    accounts: &HashMap<String, Account>,
+) -> Result<String, Box<dyn Error>> {
+    let account_name = inputs.get(index).unwrap().to_lowercase();
     if !accounts.keys().contains(&account_name) {
         return Err(Box::from(format!(
             "Account name {} not present.",
@@ -240,16 +739,50 @@ fn get_account_name(
     Ok(account_name)
 }
 
-fn get_transaction_amount(inputs: &[String]) -> Result<f32, Box<dyn Error>> {
-    let amount: f32 = match inputs.get(3).unwrap().parse() {
+/// The number of changes to undo or redo, one if not given
+fn get_count(inputs: &[String]) -> Result<usize, Box<dyn Error>> {
+    check_input_length_between(inputs, 1, 2)?;
+    match inputs.get(1) {
+        None => Ok(1),
+        Some(input) => match input.parse::<usize>() {
+            Ok(count) => Ok(count),
+            Err(_) => Err(Box::from(format!("Number of changes {input} invalid"))),
+        },
+    }
+}
+
+fn get_transaction_amount(inputs: &[String]) -> Result<Money, Box<dyn Error>> {
+    let amount: Money = match inputs.get(3).unwrap().parse() {
         Ok(f) => f,
         Err(e) => return Err(Box::from(format!("Amount entered invalid: {}", e))),
     };
     Ok(amount)
 }
 
+/// Reads a transaction ID, accepting it either bare ("3") or as shown in the list ("#3")
+fn get_transaction_id(inputs: &[String]) -> Result<u32, Box<dyn Error>> {
+    let input = inputs.get(2).unwrap();
+    let id: u32 = match input.trim_start_matches('#').parse() {
+        Ok(id) => id,
+        Err(e) => return Err(Box::from(format!("Transaction ID {input} invalid: {e}"))),
+    };
+    Ok(id)
+}
+
+/// The category at `index`, if one is given that is not empty
+fn get_category(inputs: &[String], index: usize) -> Option<String> {
+    inputs
+        .get(index)
+        .filter(|category| !category.is_empty())
+        .map(|category| category.to_case(Case::Title))
+}
+
 fn get_transaction_date(inputs: &[String]) -> Result<NaiveDate, Box<dyn Error>> {
-    let date: NaiveDate = match inputs.get(4).unwrap().parse() {
+    get_date_at(inputs, 4)
+}
+
+fn get_date_at(inputs: &[String], index: usize) -> Result<NaiveDate, Box<dyn Error>> {
+    let date: NaiveDate = match inputs.get(index).unwrap().parse() {
         Ok(f) => f,
         Err(e) => return Err(Box::from(format!("Date entered invalid: {}", e))),
     };
@@ -259,18 +792,27 @@ fn get_transaction_date(inputs: &[String]) -> Result<NaiveDate, Box<dyn Error>>
 #[cfg(test)]
 mod tests {
     use crate::account::Account;
+    use crate::budget::Budgets;
+    use crate::history::{changes_between, History};
     use crate::input_processing::{
-        add_account, add_new_transaction, add_transaction, edit_transaction_amount,
-        edit_transaction_date, edit_transaction_label, remove_account, remove_transaction,
+        add_account, add_new_transaction, add_transaction, add_transfer, category_report,
+        edit_transaction_amount, edit_transaction_category, edit_transaction_date,
+        edit_transaction_label, remove_account, remove_budget, remove_transaction, set_budget,
+        LabelCase,
     };
+    use crate::input_processing::{add_recurring, import_statement, remove_recurring};
+    use crate::input_processing::{export_qif, import_qif, saved_category_report};
+    use crate::input_processing::{find_transactions, list_accounts, redo, show_balances};
+    use crate::input_processing::{restore_backup, show_backups, show_help};
+    use crate::input_processing::{show_history, undo};
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::report;
+    use crate::storage::{MemoryStorage, Storage};
     use chrono::format::{DelayedFormat, StrftimeItems};
     use chrono::{Local, NaiveDate};
     use std::collections::HashMap;
 
-    fn today() -> NaiveDate {
-        NaiveDate::from(Local::now().naive_local())
-    }
-
     fn today_formatted<'a>() -> DelayedFormat<StrftimeItems<'a>> {
         NaiveDate::from(Local::now().naive_local()).format("%d %b %Y")
     }
@@ -284,7 +826,7 @@ mod tests {
         let account = form_account();
 
         assert_eq!(account.transactions().len(), 0);
-        assert_eq!(account._balance(), &0f32);
+        assert_eq!(account.balance(), &Money::zero());
 
         let mut account_map = HashMap::new();
         account_map.insert(account.name().to_lowercase(), account);
@@ -298,7 +840,7 @@ mod tests {
             past_date.to_string(),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        add_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
 
         assert_eq!(account_map.len(), 1);
 
@@ -307,12 +849,15 @@ mod tests {
             &format!(
                 "Name: Savings | Balance: $-10.00\n\
                    Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $-10.00",
+                   ID: 1 | Date: {} | Label: Transaction 1 | Amount: $-10.00",
                 past_date.format("%d %b %Y")
             )
         );
 
-        assert_eq!(account_map.get("savings").unwrap()._balance(), &-10f32);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(-1000, 2)
+        );
 
         let inputs = vec![
             String::from("at"),
@@ -321,18 +866,21 @@ mod tests {
             String::from("30.00"),
         ];
 
-        add_new_transaction(inputs, &mut account_map).unwrap();
+        add_new_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
 
         assert_eq!(account_map.get("savings").unwrap().transactions().len(), 2);
-        assert_eq!(account_map.get("savings").unwrap()._balance(), &20f32);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(2000, 2)
+        );
 
         assert_eq!(
             &format!("{}", account_map.get("savings").unwrap()),
             &format!(
                 "Name: Savings | Balance: $20.00\n\
                    Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $-10.00\n\
-                   Date: {} | Label: Transaction 2 | Amount: $30.00",
+                   ID: 1 | Date: {} | Label: Transaction 1 | Amount: $-10.00\n\
+                   ID: 2 | Date: {} | Label: Transaction 2 | Amount: $30.00",
                 past_date.format("%d %b %Y"),
                 today_formatted()
             )
@@ -353,7 +901,7 @@ mod tests {
             String::from("transaction1"),
         ];
 
-        add_new_transaction(inputs, &mut account_map).unwrap();
+        add_new_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
     }
 
     #[test]
@@ -371,7 +919,7 @@ mod tests {
             String::from("-10.00"),
         ];
 
-        add_new_transaction(inputs, &mut account_map).unwrap();
+        add_new_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
     }
 
     #[test]
@@ -379,7 +927,7 @@ mod tests {
         let account = form_account();
 
         assert_eq!(account.transactions().len(), 0);
-        assert_eq!(account._balance(), &0f32);
+        assert_eq!(account.balance(), &Money::zero());
 
         let mut account_map = HashMap::new();
         account_map.insert(account.name().to_lowercase(), account);
@@ -392,7 +940,7 @@ mod tests {
             String::from("2024-05-26"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        add_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
 
         assert_eq!(account_map.len(), 1);
 
@@ -403,23 +951,53 @@ mod tests {
             &format!(
                 "Name: Savings | Balance: $-10.00\n\
                    Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $-10.00",
+                   ID: 1 | Date: {} | Label: Transaction 1 | Amount: $-10.00",
                 date
             )
         );
 
-        assert_eq!(account_map.get("savings").unwrap()._balance(), &-10f32);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(-1000, 2)
+        );
     }
 
     #[test]
-    fn add_account_test() {
-        let mut account_map = HashMap::new();
-        let inputs = vec![String::from("aa"), String::from("Savings")];
-
-        add_account(inputs, &mut account_map).unwrap();
+    fn add_with_optional_date() {
+        let mut account_map = HashMap::from([(String::from("savings"), form_account())]);
+
+        // as arranged from at savings coffee -4.5 --date 2024-05-26
+        let inputs = vec![
+            String::from("at"),
+            String::from("Savings"),
+            String::from("coffee"),
+            String::from("-4.50"),
+            String::new(),
+            String::from("2024-05-26"),
+        ];
+
+        add_new_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
+
+        assert_eq!(
+            &format!("{}", account_map.get("savings").unwrap()),
+            "Name: Savings | Balance: $-4.50\n\
+            Transactions:\n\
+            ID: 1 | Date: 26 May 2024 | Label: Coffee | Amount: $-4.50"
+        );
+    }
+
+    #[test]
+    fn add_account_test() {
+        let mut account_map = HashMap::new();
+        let inputs = vec![String::from("aa"), String::from("Savings")];
+
+        add_account(inputs, &mut account_map).unwrap();
 
         assert_eq!(account_map.len(), 1);
-        assert_eq!(account_map.get("savings").unwrap()._balance(), &0f32);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::zero()
+        );
         assert_eq!(account_map.get("savings").unwrap().transactions().len(), 0);
 
         assert_eq!(
@@ -462,38 +1040,60 @@ mod tests {
         remove_account(inputs, &mut account_map).unwrap();
     }
 
-    #[test]
-    fn edit_transaction_amount_test() {
+    fn account_map_with_transaction() -> HashMap<String, Account> {
         let mut account_map = HashMap::new();
         account_map.insert(String::from("savings"), form_account());
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("atd"),
             String::from("Savings"),
             String::from("transaction1"),
             String::from("10.00"),
-            format!("{}", today()),
+            String::from("2024-05-25"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        add_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
 
         assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
         assert_eq!(
             &format!("{}", account_map.get("savings").unwrap()),
-            &format!(
-                "Name: Savings | Balance: $10.00\n\
-                   Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $10.00",
-                today_formatted()
-            )
+            "Name: Savings | Balance: $10.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Transaction 1 | Amount: $10.00"
         );
 
-        inputs = vec![
-            String::from("rt"),
+        account_map
+    }
+
+    #[test]
+    fn add_duplicate_transaction() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("atd"),
             String::from("Savings"),
             String::from("transaction1"),
             String::from("10.00"),
-            format!("{}", today()),
+            String::from("2024-05-25"),
+        ];
+
+        add_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
+
+        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 2);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(2000, 2)
+        );
+    }
+
+    #[test]
+    fn edit_transaction_amount_test() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("eta"),
+            String::from("Savings"),
+            String::from("1"),
             String::from("20.00"),
         ];
 
@@ -502,320 +1102,654 @@ mod tests {
         assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
         assert_eq!(
             &format!("{}", account_map.get("savings").unwrap()),
-            &format!(
-                "Name: Savings | Balance: $20.00\n\
-                   Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $20.00",
-                today_formatted()
-            )
+            "Name: Savings | Balance: $20.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Transaction 1 | Amount: $20.00"
         );
     }
 
     #[test]
     #[should_panic]
     fn edit_transaction_amount_doesnt_exist() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+        let mut account_map = account_map_with_transaction();
 
         let inputs = vec![
-            String::from("rt"),
+            String::from("eta"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
+            String::from("2"),
+            String::from("20.00"),
         ];
 
         edit_transaction_amount(inputs, &mut account_map).unwrap();
     }
 
     #[test]
-    #[should_panic]
-    fn edit_transaction_amount_collision() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn edit_transaction_date_test() {
+        let mut account_map = account_map_with_transaction();
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("etd"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
+            String::from("#1"),
+            String::from("2024-05-26"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        edit_transaction_date(inputs, &mut account_map).unwrap();
 
-        inputs = vec![
-            String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction1"),
-            String::from("20.00"),
-            format!("{}", today()),
-        ];
+        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
+        assert_eq!(
+            &format!("{}", account_map.get("savings").unwrap()),
+            "Name: Savings | Balance: $10.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 26 May 2024 | Label: Transaction 1 | Amount: $10.00"
+        );
+    }
 
-        add_transaction(inputs, &mut account_map).unwrap();
+    #[test]
+    #[should_panic]
+    fn edit_transaction_date_doesnt_exist() {
+        let mut account_map = account_map_with_transaction();
 
-        inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("etd"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
-            String::from("20.00"),
+            String::from("2"),
+            String::from("2024-05-26"),
         ];
 
-        edit_transaction_amount(inputs, &mut account_map).unwrap();
+        edit_transaction_date(inputs, &mut account_map).unwrap();
     }
 
     #[test]
-    fn edit_transaction_date_test() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn edit_transaction_label_test() {
+        let mut account_map = account_map_with_transaction();
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("etl"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-25"),
+            String::from("1"),
+            String::from("transaction2"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        edit_transaction_label(inputs, &mut account_map, LabelCase::Title).unwrap();
 
         assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
         assert_eq!(
             &format!("{}", account_map.get("savings").unwrap()),
-            &"Name: Savings | Balance: $10.00\n\
+            "Name: Savings | Balance: $10.00\n\
             Transactions:\n\
-            Date: 25 May 2024 | Label: Transaction 1 | Amount: $10.00"
-                .to_string()
+            ID: 1 | Date: 25 May 2024 | Label: Transaction 2 | Amount: $10.00"
         );
+    }
 
-        inputs = vec![
-            String::from("rt"),
+    #[test]
+    fn edit_transaction_label_keep_case() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("etl"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-25"),
-            String::from("2024-05-26"),
+            String::from("1"),
+            String::from("McDonald's drive-thru"),
         ];
 
-        edit_transaction_date(inputs, &mut account_map).unwrap();
-
-        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
+        edit_transaction_label(inputs.clone(), &mut account_map, LabelCase::AsTyped).unwrap();
         assert_eq!(
-            &format!("{}", account_map.get("savings").unwrap()),
-            &"Name: Savings | Balance: $10.00\n\
-            Transactions:\n\
-            Date: 26 May 2024 | Label: Transaction 1 | Amount: $10.00"
-                .to_string()
+            account_map["savings"].transactions()[&1].label(),
+            "McDonald's drive-thru"
+        );
+
+        edit_transaction_label(inputs, &mut account_map, LabelCase::Title).unwrap();
+        assert_ne!(
+            account_map["savings"].transactions()[&1].label(),
+            "McDonald's drive-thru"
         );
     }
 
     #[test]
     #[should_panic]
-    fn edit_transaction_date_doesnt_exist() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn edit_transaction_label_doesnt_exist() {
+        let mut account_map = account_map_with_transaction();
 
         let inputs = vec![
-            String::from("rt"),
+            String::from("etl"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-25"),
-            String::from("2024-05-26"),
+            String::from("2"),
+            String::from("transaction2"),
         ];
 
-        edit_transaction_date(inputs, &mut account_map).unwrap();
+        edit_transaction_label(inputs, &mut account_map, LabelCase::Title).unwrap();
     }
 
     #[test]
     #[should_panic]
-    fn edit_transaction_date_collision() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn edit_transaction_bad_id() {
+        let mut account_map = account_map_with_transaction();
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("etl"),
             String::from("Savings"),
             String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-25"),
+            String::from("transaction2"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        edit_transaction_label(inputs, &mut account_map, LabelCase::Title).unwrap();
+    }
+
+    #[test]
+    fn remove_transaction_test() {
+        let mut account_map = account_map_with_transaction();
 
-        inputs = vec![
+        let inputs = vec![
             String::from("rt"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-26"),
+            String::from("1"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        remove_transaction(inputs, &mut account_map).unwrap();
+        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 0);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::zero()
+        );
+    }
 
-        inputs = vec![
-            String::from("rt"),
+    #[test]
+    fn category_test() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("atd"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            String::from("2024-05-25"),
+            String::from("shop"),
+            String::from("-5.00"),
             String::from("2024-05-26"),
+            String::from("groceries"),
         ];
 
-        edit_transaction_date(inputs, &mut account_map).unwrap();
-    }
-
-    #[test]
-    fn edit_transaction_label_test() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+        add_transaction(inputs, &mut account_map, LabelCase::Title).unwrap();
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("etc"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
+            String::from("1"),
+            String::from("eating out"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        edit_transaction_category(inputs, &mut account_map).unwrap();
 
-        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
         assert_eq!(
             &format!("{}", account_map.get("savings").unwrap()),
-            &format!(
-                "Name: Savings | Balance: $10.00\n\
-                   Transactions:\n\
-                   Date: {} | Label: Transaction 1 | Amount: $10.00",
-                today_formatted()
-            )
+            "Name: Savings | Balance: $5.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Transaction 1 | Amount: $10.00 | Category: Eating Out\n\
+            ID: 2 | Date: 26 May 2024 | Label: Shop | Amount: $-5.00 | Category: Groceries"
         );
 
-        inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("cr"),
+            String::from("2024-05-26"),
+            String::from("2024-05-31"),
+        ];
+
+        assert_eq!(
+            category_report(inputs, &account_map).unwrap(),
+            "Category totals 26 May 2024 - 31 May 2024:\n\
+            \tGroceries: $-5.00\n\
+            \tTotal: $-5.00"
+        );
+
+        let inputs = vec![
+            String::from("etc"),
             String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
-            String::from("transaction2"),
+            String::from("1"),
         ];
 
-        edit_transaction_label(inputs, &mut account_map).unwrap();
+        edit_transaction_category(inputs, &mut account_map).unwrap();
 
-        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
         assert_eq!(
-            &format!("{}", account_map.get("savings").unwrap()),
-            &format!(
-                "Name: Savings | Balance: $10.00\n\
-                   Transactions:\n\
-                   Date: {} | Label: Transaction 2 | Amount: $10.00",
-                today_formatted()
-            )
+            account_map
+                .get("savings")
+                .unwrap()
+                .transactions()
+                .get(&1)
+                .unwrap()
+                .category(),
+            None
         );
     }
 
     #[test]
     #[should_panic]
-    fn edit_transaction_label_doesnt_exist() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn category_report_backwards() {
+        let inputs = vec![
+            String::from("cr"),
+            String::from("2024-05-31"),
+            String::from("2024-05-26"),
+        ];
+
+        category_report(inputs, &account_map_with_transaction()).unwrap();
+    }
+
+    #[test]
+    fn category_report_to_month_end() {
+        let inputs = vec![String::from("cr"), String::from("2024-05-26")];
+        let end = report::month_bounds(NaiveDate::from(Local::now().naive_local())).1;
+
+        assert!(category_report(inputs, &account_map_with_transaction())
+            .unwrap()
+            .starts_with(&format!(
+                "Category totals 26 May 2024 - {}",
+                end.format("%d %b %Y")
+            )));
+    }
+
+    #[test]
+    fn help_test() {
+        assert!(show_help(vec![String::from("help")])
+            .unwrap()
+            .starts_with("\taa  [account] - add new account"));
+        assert!(show_help(vec![
+            String::from("help"),
+            String::from("add-dated-transaction")
+        ])
+        .unwrap()
+        .starts_with("atd [account] [label] [amount] [date] [category]? - "));
+        assert!(show_help(vec![String::from("help"), String::from("fly")]).is_err());
+    }
+
+    #[test]
+    fn saved_category_report_test() {
+        let mut storage = MemoryStorage::default();
+        storage
+            .save(&Ledger::from(account_map_with_transaction()))
+            .unwrap();
 
         let inputs = vec![
-            String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
-            String::from("transaction2"),
+            String::from("cr"),
+            String::from("2024-05-25"),
+            String::from("2024-05-31"),
         ];
 
-        edit_transaction_label(inputs, &mut account_map).unwrap();
+        assert_eq!(
+            saved_category_report(inputs, &mut storage).unwrap(),
+            "Category totals 25 May 2024 - 31 May 2024:\n\
+            \tUncategorised: $10.00\n\
+            \tTotal: $10.00"
+        );
     }
 
     #[test]
-    #[should_panic]
-    fn edit_transaction_label_collision() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn restore_test() {
+        let mut storage = MemoryStorage::default();
+        assert_eq!(show_backups(&storage).unwrap(), "No backups yet");
+
+        storage
+            .save(&Ledger::from(account_map_with_transaction()))
+            .unwrap();
+        storage.backup().unwrap();
+        storage.save(&Ledger::default()).unwrap();
+        assert_eq!(show_backups(&storage).unwrap(), "Backups:\n\t1. backup 1");
+
+        let inputs = vec![String::from("restore"), String::from("1")];
+        assert_eq!(
+            restore_backup(inputs, &mut storage).unwrap(),
+            "Restored backup 1, keeping the file it replaced as backup 2"
+        );
+        assert_eq!(storage.load().unwrap().accounts.len(), 1);
 
-        let mut inputs = vec![
-            String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
+        let inputs = vec![String::from("restore"), String::from("3")];
+        assert!(restore_backup(inputs, &mut storage).is_err());
+    }
+
+    #[test]
+    fn list_and_balance_test() {
+        let mut accounts = account_map_with_transaction();
+        accounts.insert(String::from("cash"), Account::build("Cash").unwrap());
+
+        let balances = show_balances(vec![String::from("balance")], &accounts).unwrap();
+        assert_eq!(balances, "Cash\t0.00\nSavings\t10.00");
+        let inputs = vec![String::from("balance"), String::from("CASH")];
+        assert_eq!(show_balances(inputs, &accounts).unwrap(), "Cash\t0.00");
+        let inputs = vec![String::from("balance"), String::from("card")];
+        assert!(show_balances(inputs, &accounts).is_err());
+
+        let inputs = vec![String::from("list"), String::from("savings")];
+        assert!(list_accounts(inputs, &accounts)
+            .unwrap()
+            .starts_with("Name: Savings | Balance: $10.00\nTransactions:\n"));
+        let listed = list_accounts(vec![String::from("list")], &accounts).unwrap();
+        assert!(listed.starts_with("Name: Cash"));
+    }
+
+    #[test]
+    fn find_transactions_test() {
+        let mut accounts = account_map_with_transaction();
+        accounts.insert(String::from("cash"), Account::build("Cash").unwrap());
+
+        // as arranged from find --label transaction
+        let inputs = vec![
+            String::from("find"),
+            String::new(),
+            String::from("transaction"),
+        ];
+        assert!(find_transactions(inputs, &accounts)
+            .unwrap()
+            .contains("\nSavings  #1  25 May 2024  Transaction 1  $10.00\n1 transaction found"));
+        let inputs = vec![String::from("find"), String::from("cash")];
+        assert_eq!(
+            find_transactions(inputs, &accounts).unwrap(),
+            "No transactions found"
+        );
+        let inputs = vec![String::from("find"), String::from("card")];
+        assert!(find_transactions(inputs, &accounts).is_err());
+    }
+
+    #[test]
+    fn undo_redo_test() {
+        let mut history = History::default();
+        let mut ledger = Ledger::default();
+        assert_eq!(show_history(&history), "No changes yet");
+
+        let before = ledger.clone();
+        add_account(
+            vec![String::from("aa"), String::from("savings")],
+            &mut ledger.accounts,
+        )
+        .unwrap();
+        history.record("aa savings", changes_between(&before, &ledger));
+
+        let before = ledger.clone();
+        let inputs = vec![
+            String::from("at"),
+            String::from("savings"),
+            String::from("coffee"),
+            String::from("-3"),
         ];
+        add_new_transaction(inputs, &mut ledger.accounts, LabelCase::Title).unwrap();
+        history.record("at savings coffee -3", changes_between(&before, &ledger));
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        let inputs = vec![String::from("undo")];
+        assert_eq!(
+            undo(inputs, &mut history, &mut ledger).unwrap(),
+            "Undid: at savings coffee -3"
+        );
+        assert!(ledger.accounts["savings"].transactions().is_empty());
+        assert_eq!(
+            show_history(&history),
+            "Changes, most recent first:\n\t1. aa savings\n\
+            Undone, next to redo first:\n\t1. at savings coffee -3"
+        );
 
-        inputs = vec![
-            String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction2"),
-            String::from("10.00"),
-            format!("{}", today()),
+        let inputs = vec![String::from("redo"), String::from("2")];
+        assert!(redo(inputs, &mut history, &mut ledger).is_err());
+        let inputs = vec![String::from("redo"), String::from("one")];
+        assert!(redo(inputs, &mut history, &mut ledger).is_err());
+
+        let inputs = vec![String::from("redo"), String::from("1")];
+        assert_eq!(
+            redo(inputs, &mut history, &mut ledger).unwrap(),
+            "Redid: at savings coffee -3"
+        );
+        assert_eq!(ledger.accounts["savings"].transactions().len(), 1);
+    }
+
+    #[test]
+    fn budget_test() {
+        let mut budgets = Budgets::default();
+
+        let inputs = vec![
+            String::from("ab"),
+            String::from("eating out"),
+            String::from("120"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        set_budget(inputs, &mut budgets).unwrap();
 
-        inputs = vec![
-            String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction1"),
-            String::from("10.00"),
-            format!("{}", today()),
-            String::from("transaction2"),
+        assert_eq!(
+            budgets.limits().get("Eating Out").unwrap(),
+            &Money::new(12000, 2)
+        );
+
+        let inputs = vec![String::from("rb"), String::from("Eating out")];
+
+        remove_budget(inputs, &mut budgets).unwrap();
+
+        assert!(budgets.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn budget_bad_limit() {
+        let inputs = vec![
+            String::from("ab"),
+            String::from("groceries"),
+            String::from("-50"),
         ];
 
-        edit_transaction_date(inputs, &mut account_map).unwrap();
+        set_budget(inputs, &mut Budgets::default()).unwrap();
     }
 
     #[test]
-    fn remove_transaction_test() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+    fn recurring_test() {
+        let account_map = account_map_with_transaction();
+        let mut recurring = Vec::new();
 
-        let mut inputs = vec![
-            String::from("rt"),
+        let inputs = vec![
+            String::from("ar"),
+            String::from("savings"),
+            String::from("rent"),
+            String::from("-1000"),
+            String::from("monthly"),
+            String::from("2024-05-31"),
+            String::from("housing"),
+        ];
+
+        add_recurring(inputs, &account_map, &mut recurring, LabelCase::Title).unwrap();
+
+        assert_eq!(
+            &format!("{}", recurring[0]),
+            "Account: Savings | Label: Rent | Amount: $-1000.00 | Repeats: monthly:31 | \
+            Next: 31 May 2024 | Category: Housing"
+        );
+
+        let inputs = vec![
+            String::from("ar"),
+            String::from("savings"),
+            String::from("rent"),
+            String::from("-1000"),
+            String::from("fortnightly"),
+            String::from("2024-05-31"),
+        ];
+
+        assert!(add_recurring(inputs, &account_map, &mut recurring, LabelCase::Title).is_err());
+
+        let inputs = vec![String::from("rr"), String::from("2")];
+        assert!(remove_recurring(inputs, &mut recurring).is_err());
+
+        let inputs = vec![String::from("rr"), String::from("1")];
+        remove_recurring(inputs, &mut recurring).unwrap();
+        assert!(recurring.is_empty());
+    }
+
+    #[test]
+    fn add_transfer_test() {
+        let mut account_map = account_map_with_transaction();
+        account_map.insert(
+            String::from("checking"),
+            Account::build("Checking").unwrap(),
+        );
+
+        let inputs = vec![
+            String::from("tr"),
             String::from("Savings"),
-            String::from("transaction2"),
-            String::from("10.00"),
+            String::from("checking"),
+            String::from("4.00"),
+        ];
+
+        add_transfer(inputs, &mut account_map).unwrap();
+
+        let inputs = vec![
+            String::from("tr"),
+            String::from("Checking"),
+            String::from("savings"),
+            String::from("1.00"),
             String::from("2024-05-26"),
         ];
 
-        add_transaction(inputs, &mut account_map).unwrap();
+        add_transfer(inputs, &mut account_map).unwrap();
 
-        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 1);
+        assert_eq!(
+            &format!("{}", account_map.get("checking").unwrap()),
+            &format!(
+                "Name: Checking | Balance: $3.00\n\
+                   Transactions:\n\
+                   ID: 2 | Date: 26 May 2024 | Label: Transfer | Amount: $-1.00 | Transfer to Savings #3\n\
+                   ID: 1 | Date: {} | Label: Transfer | Amount: $4.00 | Transfer from Savings #2",
+                today_formatted()
+            )
+        );
 
-        inputs = vec![
+        let inputs = vec![
             String::from("rt"),
-            String::from("Savings"),
-            String::from("transaction2"),
-            String::from("10.00"),
-            String::from("2024-05-26"),
+            String::from("Checking"),
+            String::from("1"),
         ];
 
         remove_transaction(inputs, &mut account_map).unwrap();
-        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 0);
-        assert_eq!(account_map.get("savings").unwrap()._balance(), &0f32);
+
+        assert_eq!(account_map.get("savings").unwrap().transactions().len(), 2);
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(1100, 2)
+        );
     }
 
     #[test]
     #[should_panic]
     fn remove_transaction_does_not_exist() {
-        let mut account_map = HashMap::new();
-        account_map.insert(String::from("savings"), form_account());
+        let mut account_map = account_map_with_transaction();
 
         let inputs = vec![
             String::from("rt"),
             String::from("Savings"),
-            String::from("transaction2"),
-            String::from("10.00"),
-            String::from("2024-05-26"),
+            String::from("2"),
         ];
 
         remove_transaction(inputs, &mut account_map).unwrap();
     }
+
+    #[test]
+    fn import_statement_test() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("import"),
+            String::from("savings"),
+            String::from("src/test-files/import-statement.csv"),
+            String::from("date=Date"),
+            String::from("format=%d/%m/%Y"),
+            String::from("desc=Description"),
+            String::from("debit=Debit"),
+            String::from("credit=Credit"),
+        ];
+
+        assert_eq!(
+            import_statement(inputs.clone(), &mut account_map).unwrap(),
+            "Imported 2 transactions into Savings, skipped 1 already present"
+        );
+        assert_eq!(
+            import_statement(inputs, &mut account_map).unwrap(),
+            "Imported 0 transactions into Savings, skipped 3 already present"
+        );
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(-3050, 2)
+        );
+    }
+
+    #[test]
+    fn import_ofx_statement_test() {
+        let mut account_map = account_map_with_transaction();
+
+        let inputs = vec![
+            String::from("import"),
+            String::from("savings"),
+            String::from("src/test-files/import-statement.ofx"),
+        ];
+
+        assert_eq!(
+            import_statement(inputs.clone(), &mut account_map).unwrap(),
+            "Imported 2 transactions into Savings, skipped 0 already present"
+        );
+        assert_eq!(
+            import_statement(inputs.clone(), &mut account_map).unwrap(),
+            "Imported 0 transactions into Savings, skipped 2 already present"
+        );
+        assert_eq!(
+            account_map.get("savings").unwrap().balance(),
+            &Money::new(-3020, 2)
+        );
+
+        // OFX statements say where everything is, so take no column mapping
+        let mut inputs = inputs;
+        inputs.push(String::from("date=1"));
+        assert!(import_statement(inputs, &mut account_map).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn import_statement_missing_file() {
+        let inputs = vec![
+            String::from("import"),
+            String::from("savings"),
+            String::from("src/test-files/does-not-exist.csv"),
+        ];
+
+        import_statement(inputs, &mut account_map_with_transaction()).unwrap();
+    }
+
+    #[test]
+    fn qif_test() {
+        let mut account_map = account_map_with_transaction();
+        account_map.insert(
+            String::from("checking"),
+            Account::build("Checking").unwrap(),
+        );
+
+        let inputs = vec![
+            String::from("tr"),
+            String::from("savings"),
+            String::from("checking"),
+            String::from("4.00"),
+            String::from("2024-05-26"),
+        ];
+        add_transfer(inputs, &mut account_map).unwrap();
+
+        let inputs = vec![
+            String::from("qe"),
+            String::from("src/test-files/export-test.qif"),
+        ];
+        export_qif(inputs, &account_map).unwrap();
+
+        // savings is already there, so only checking comes back, no longer linked to it
+        let mut read_back = HashMap::new();
+        read_back.insert(String::from("savings"), form_account());
+        let inputs = vec![
+            String::from("qi"),
+            String::from("src/test-files/export-test.qif"),
+        ];
+
+        assert_eq!(
+            import_qif(inputs, &mut read_back).unwrap(),
+            "Added accounts: Checking\nSkipped accounts that already exist: Savings"
+        );
+        assert_eq!(
+            &format!("{}", read_back.get("checking").unwrap()),
+            "Name: Checking | Balance: $4.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 26 May 2024 | Label: Transfer | Amount: $4.00"
+        );
+        assert_eq!(read_back.get("savings").unwrap().transactions().len(), 0);
+    }
 }
@@ -0,0 +1,418 @@
+use crate::document::{self, AccountRecord, BudgetRecord, RecurringRecord, TransactionRecord};
+use crate::history::{self, Change};
+use crate::ledger::Ledger;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+/// A command that changed the ledger, as written to the journal on one line of JSON
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Local time the command was entered, "YYYY-MM-DD HH:MM:SS"
+    time: String,
+    user: String,
+    command: String,
+    changes: Vec<ChangeRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum ChangeRecord {
+    Account {
+        before: Option<AccountRecord>,
+        after: Option<AccountRecord>,
+    },
+    Transaction {
+        account: String,
+        id: u32,
+        before: Option<Box<TransactionRecord>>,
+        after: Option<Box<TransactionRecord>>,
+    },
+    Budgets {
+        before: Vec<BudgetRecord>,
+        after: Vec<BudgetRecord>,
+    },
+    Recurring {
+        before: Vec<RecurringRecord>,
+        after: Vec<RecurringRecord>,
+    },
+}
+
+/// Every change made to a data file, appended to `[file].journal` beside it as it is saved
+pub struct Journal {
+    file_path: String,
+    /// Entries for changes not saved yet
+    pending: Vec<String>,
+}
+
+impl Journal {
+    pub fn new(data_path: &str) -> Journal {
+        Journal {
+            file_path: format!("{data_path}.journal"),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Starts the journal with everything in the ledger if nothing has been written to it,
+    /// so replaying it gives back what was there before it was kept
+    pub fn start(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        if fs::metadata(&self.file_path).is_ok_and(|metadata| metadata.len() > 0)
+            || !self.pending.is_empty()
+        {
+            return Ok(());
+        }
+        let changes = history::changes_between(&Ledger::default(), ledger);
+        self.record("start", &changes, ledger)
+    }
+
+    /// Notes the changes a command made, to be written out with the next `flush`.
+    /// `ledger` is as the command left it.
+    pub fn record(
+        &mut self,
+        command: &str,
+        changes: &[Change],
+        ledger: &Ledger,
+    ) -> Result<(), Box<dyn Error>> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let entry = Entry {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            user: current_user(),
+            command: String::from(command),
+            changes: changes
+                .iter()
+                .map(|change| change_record(change, ledger))
+                .collect(),
+        };
+        self.pending.push(serde_json::to_string(&entry)?);
+        Ok(())
+    }
+
+    /// Appends the changes recorded since the last flush, once they have been saved
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .and_then(|mut file| {
+                for line in &self.pending {
+                    writeln!(file, "{line}")?;
+                }
+                file.sync_all()
+            });
+        if let Err(e) = result {
+            return Err(Box::from(format!(
+                "Failed writing to {}: {e}",
+                self.file_path
+            )));
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Drops the changes recorded since the last flush, as they will not be saved
+    pub fn discard(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Deletes the journal along with anything not yet written to it, returning where it was
+    /// if there was one
+    pub fn remove(&mut self) -> Result<Option<&str>, Box<dyn Error>> {
+        self.pending.clear();
+        match fs::remove_file(&self.file_path) {
+            Ok(()) => Ok(Some(&self.file_path)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Box::from(format!(
+                "Failed removing {}: {e}",
+                self.file_path
+            ))),
+        }
+    }
+
+    /// Every entry, saved or not, oldest first
+    fn entries(&self) -> Result<Vec<Entry>, Box<dyn Error>> {
+        // no journal has been written yet if it cannot be read
+        let saved = fs::read_to_string(&self.file_path).unwrap_or_default();
+
+        let mut entries = Vec::new();
+        for (index, line) in saved
+            .lines()
+            .chain(self.pending.iter().map(String::as_str))
+            .enumerate()
+        {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    return Err(Box::from(format!(
+                        "{} line {}: {e}",
+                        self.file_path,
+                        index + 1
+                    )))
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Rebuilds the ledger by making every change in the journal in turn
+    pub fn replay(&self) -> Result<(Ledger, usize), Box<dyn Error>> {
+        let entries = self.entries()?;
+        if entries.is_empty() {
+            return Err(Box::from(format!("{} has no entries yet", self.file_path)));
+        }
+
+        let count = entries.len();
+        let mut ledger = Ledger::default();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let result = entry
+                .changes
+                .into_iter()
+                .try_for_each(|record| read_change(record)?.apply(&mut ledger, false));
+            if let Err(e) = result {
+                return Err(Box::from(format!(
+                    "Could not replay entry {} ({} at {}): {e}",
+                    index + 1,
+                    entry.command,
+                    entry.time
+                )));
+            }
+        }
+        Ok((ledger, count))
+    }
+
+    /// Describes each change to an account, or to one of its transactions, oldest first
+    pub fn log(&self, account: &str, id: Option<u32>) -> Result<String, Box<dyn Error>> {
+        let key = account.to_lowercase();
+        let subject = match id {
+            Some(id) => format!("{account} #{id}"),
+            None => String::from(account),
+        };
+
+        let mut lines = Vec::new();
+        for entry in self.entries()? {
+            let mut described = Vec::new();
+            for record in entry.changes {
+                if let Some(description) = describe(read_change(record)?, &key, id) {
+                    described.push(description);
+                }
+            }
+            if !described.is_empty() {
+                lines.push(format!("{} {}: {}", entry.time, entry.user, entry.command));
+                lines.extend(described);
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(format!("No changes to {subject} in the journal"));
+        }
+        Ok(format!("Changes to {subject}:\n{}", lines.join("\n")))
+    }
+}
+
+/// Who is making the changes, as the operating system knows them
+fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("unknown"))
+}
+
+fn change_record(change: &Change, ledger: &Ledger) -> ChangeRecord {
+    match change {
+        Change::Account { before, after, .. } => ChangeRecord::Account {
+            before: before.as_ref().map(document::account_record),
+            after: after.as_ref().map(document::account_record),
+        },
+        Change::Transaction {
+            key,
+            id,
+            before,
+            after,
+        } => ChangeRecord::Transaction {
+            // the account is in the ledger, as only transactions of accounts kept are listed
+            account: ledger
+                .accounts
+                .get(key)
+                .map_or_else(|| key.clone(), |account| String::from(account.name())),
+            id: *id,
+            before: before
+                .as_ref()
+                .map(|t| Box::new(document::transaction_record(t))),
+            after: after
+                .as_ref()
+                .map(|t| Box::new(document::transaction_record(t))),
+        },
+        Change::Budgets { before, after } => ChangeRecord::Budgets {
+            before: document::budget_records(before),
+            after: document::budget_records(after),
+        },
+        Change::Recurring { before, after } => ChangeRecord::Recurring {
+            before: document::recurring_records(before),
+            after: document::recurring_records(after),
+        },
+    }
+}
+
+fn read_change(record: ChangeRecord) -> Result<Change, Box<dyn Error>> {
+    Ok(match record {
+        ChangeRecord::Account { before, after } => {
+            let before = before.map(document::read_account).transpose()?;
+            let after = after.map(document::read_account).transpose()?;
+            let key = match before.as_ref().or(after.as_ref()) {
+                Some(account) => account.name().to_lowercase(),
+                None => return Err(Box::from("Account change without an account")),
+            };
+            Change::Account { key, before, after }
+        }
+        ChangeRecord::Transaction {
+            account,
+            id,
+            before,
+            after,
+        } => Change::Transaction {
+            key: account.to_lowercase(),
+            id,
+            before: before
+                .map(|t| document::read_transaction(&account, *t))
+                .transpose()?,
+            after: after
+                .map(|t| document::read_transaction(&account, *t))
+                .transpose()?,
+        },
+        ChangeRecord::Budgets { before, after } => Change::Budgets {
+            before: document::read_budgets(before)?,
+            after: document::read_budgets(after)?,
+        },
+        ChangeRecord::Recurring { before, after } => Change::Recurring {
+            before: document::read_recurring(before)?,
+            after: document::read_recurring(after)?,
+        },
+    })
+}
+
+/// A line describing a change if it is to the given account, or to one of its transactions
+fn describe(change: Change, key: &str, id: Option<u32>) -> Option<String> {
+    match change {
+        Change::Account {
+            key: changed,
+            before,
+            after,
+        } if changed == key && id.is_none() => match (before, after) {
+            (None, Some(account)) => Some(format!("\tadded account {}", account.name())),
+            (Some(account), None) => Some(format!("\tremoved account {}", account.name())),
+            _ => None,
+        },
+        Change::Transaction {
+            key: changed,
+            id: changed_id,
+            before,
+            after,
+        } if changed == key && id.is_none_or(|id| id == changed_id) => match (before, after) {
+            (None, Some(t)) => Some(format!("\tadded {t}")),
+            (Some(t), None) => Some(format!("\tremoved {t}")),
+            (Some(was), Some(now)) => Some(format!("\twas {was}\n\tnow {now}")),
+            (None, None) => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::history::changes_between;
+    use crate::journal::Journal;
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::transfer::transfer;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn journal_test() {
+        let data_path = "src/test-files/journal-test.csv";
+        let _ = fs::remove_file("src/test-files/journal-test.csv.journal");
+
+        let mut ledger = Ledger::from(HashMap::from([(
+            String::from("savings"),
+            Account::build("Savings").unwrap(),
+        )]));
+        ledger
+            .budgets
+            .set_limit("Fun", Money::new(5000, 2))
+            .unwrap();
+
+        let mut journal = Journal::new(data_path);
+        journal.start(&ledger).unwrap();
+
+        let before = ledger.clone();
+        ledger
+            .accounts
+            .insert(String::from("cash"), Account::build("Cash").unwrap());
+        transfer(
+            &mut ledger.accounts,
+            "savings",
+            "cash",
+            Money::new(500, 2),
+            date("2024-05-25"),
+        )
+        .unwrap();
+        let changes = changes_between(&before, &ledger);
+        journal
+            .record("tr savings cash 5", &changes, &ledger)
+            .unwrap();
+        journal.flush().unwrap();
+
+        let before = ledger.clone();
+        ledger
+            .accounts
+            .get_mut("savings")
+            .unwrap()
+            .edit_transaction_label(1, String::from("Moved"))
+            .unwrap();
+        let changes = changes_between(&before, &ledger);
+        journal
+            .record("etl savings 1 moved", &changes, &ledger)
+            .unwrap();
+
+        // a new session picks up where the saved journal left off
+        let mut reopened = Journal::new(data_path);
+        reopened.start(&Ledger::default()).unwrap();
+        assert!(reopened.replay().unwrap().0.accounts.contains_key("cash"));
+        assert_eq!(reopened.replay().unwrap().1, 2);
+
+        let (replayed, entries) = journal.replay().unwrap();
+        assert_eq!(entries, 3);
+        assert!(changes_between(&ledger, &replayed).is_empty());
+
+        let log = journal.log("Savings", Some(1)).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "Changes to Savings #1:");
+        assert!(lines[1].ends_with(": tr savings cash 5"));
+        assert!(lines[2].starts_with("\tadded ID: 1 | Date: 25 May 2024 | Label: Transfer"));
+        assert!(lines[3].ends_with(": etl savings 1 moved"));
+        assert!(lines[4].starts_with("\twas ID: 1 | Date: 25 May 2024 | Label: Transfer"));
+        assert!(lines[5].starts_with("\tnow ID: 1 | Date: 25 May 2024 | Label: Moved"));
+
+        assert!(journal
+            .log("cash", None)
+            .unwrap()
+            .contains("\tadded account Cash"));
+        assert_eq!(
+            journal.log("Savings", Some(2)).unwrap(),
+            "No changes to Savings #2 in the journal"
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use crate::account::Account;
+use crate::budget::Budgets;
+use crate::recurring::Recurring;
+use std::collections::HashMap;
+
+/// Everything stored in a single data file: the accounts, keyed by lowercase name,
+/// and the settings that apply across them
+#[derive(Clone, Default)]
+pub struct Ledger {
+    pub accounts: HashMap<String, Account>,
+    pub budgets: Budgets,
+    pub recurring: Vec<Recurring>,
+}
+
+impl From<HashMap<String, Account>> for Ledger {
+    fn from(accounts: HashMap<String, Account>) -> Ledger {
+        Ledger {
+            accounts,
+            budgets: Budgets::default(),
+            recurring: Vec::new(),
+        }
+    }
+}
@@ -1,13 +1,53 @@
 use crate::account::Account;
+use crate::budget::Budgets;
+use crate::completion::MenuCompleter;
+use crate::encryption::Cipher;
+use crate::file_processing::FileFormat;
+use crate::history::{Change, History};
+use crate::journal::Journal;
+use crate::ledger::Ledger;
+use crate::lock::Lock;
+use crate::merge::MergeSummary;
+use crate::recurring::Recurring;
+use crate::storage::Storage;
+use chrono::{Local, NaiveDate, TimeDelta};
 use input_processing::*;
 use itertools::Itertools;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Editor};
 use std::collections::HashMap;
 use std::error::Error;
-use std::io;
+use std::fs;
+use std::io::{self, Read};
 
 mod account;
+mod brace_format;
+mod budget;
+mod commands;
+mod completion;
+mod csv;
+mod document;
+mod encryption;
 mod file_processing;
+mod history;
+mod import;
 mod input_processing;
+mod journal;
+mod ledger;
+mod lock;
+mod merge;
+mod money;
+mod ofx;
+mod qif;
+mod recurring;
+mod report;
+mod search;
+mod sqlite;
+mod storage;
+mod tokenizer;
+mod transfer;
+mod tui;
 
 fn display_accounts(accounts: &HashMap<String, Account>) {
     println!("---------------------------------------------------------------------------------------------------------");
@@ -20,35 +60,456 @@ fn display_accounts(accounts: &HashMap<String, Account>) {
     println!("---------------------------------------------------------------------------------------------------------");
 }
 
+fn display_budgets(budgets: &Budgets, accounts: &HashMap<String, Account>) {
+    let today = NaiveDate::from(Local::now().naive_local());
+    println!("\tBudgets for {}:", today.format("%B %Y"));
+    for line in budget_lines(budgets, accounts, today) {
+        println!("\t\t{line}");
+    }
+    println!("---------------------------------------------------------------------------------------------------------");
+}
+
+/// A line for each budget in the month `today` falls in, or the error that stopped them
+/// being worked out, so that the menu is shown either way
+fn budget_lines(
+    budgets: &Budgets,
+    accounts: &HashMap<String, Account>,
+    today: NaiveDate,
+) -> Vec<String> {
+    match budgets.status(accounts, today) {
+        Ok(statuses) => statuses.iter().map(ToString::to_string).collect(),
+        Err(e) => vec![format!("Error: {e}")],
+    }
+}
+
+fn display_recurring(recurring: &[Recurring]) {
+    println!("\tRecurring transactions:");
+    for (number, template) in recurring.iter().enumerate() {
+        println!("\t\t{}. {template}", number + 1);
+    }
+    println!("---------------------------------------------------------------------------------------------------------");
+}
+
 fn display_menu() {
     println!("\n\
   ---------------------------------------------------------------------------------------------------------\n\
-        \taa  [account] - add new account\n\
-        \tra  [account] - remove an account\n\
-        \tat  [account] [label] [amount] - add transaction from today to account\n\
-        \tatd [account] [label] [amount] [date (YYYY-MM-DD)] - add transaction from another day to account\n\
-        \teta [account] [label] [amount] [date (YYYY-MM-DD)] [new amount] - edit amount of transaction\n\
-        \tetd [account] [label] [amount] [date (YYYY-MM-DD)] [new date] - edit date of transaction\n\
-        \tetl [account] [label] [amount] [date (YYYY-MM-DD)] [new label] - edit label of transaction\n\
-        \trt  [account] [label] [amount] [date (YYYY-MM-DD)] - remove a transaction\n\
-        \ts   - save changes\n\
-        \tu   - undo all changes since last save\n\
-        \tq   - exit program\n\
-  ---------------------------------------------------------------------------------------------------------\n");
-}
-
-pub fn run(file_path: &str) -> Result<(), Box<dyn Error>> {
-    let mut accounts =
-        file_processing::read_from_string(file_processing::get_file_contents(file_path)?)?;
-
-    let mut message_buffer: String = String::new();
-
-    loop {
+        {}\n\
+        \thelp [command] - describe a command, whose arguments can also be given by name, e.g. at savings coffee -4.5 --date 2024-05-01\n\
+        \tPut labels, names or paths with spaces in quotes, e.g. at savings \"weekly groceries\" -80\n\
+  ---------------------------------------------------------------------------------------------------------\n",
+        commands::menu()
+    );
+}
+
+/// Posts any recurring transactions that have come due, describing them a line each
+fn post_recurring(ledger: &mut Ledger) -> Result<String, Box<dyn Error>> {
+    let today = NaiveDate::from(Local::now().naive_local());
+    let posted = recurring::post_due(&mut ledger.accounts, &mut ledger.recurring, today)?;
+
+    Ok(posted.join("\n"))
+}
+
+/// How recent a backup has to be for commands given on the command line not to make another,
+/// so that a script running many of them does not push out every backup made before it
+const COMMAND_BACKUP_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+/// The data file being worked on and what is known about it
+struct Session {
+    file_path: String,
+    storage: Box<dyn Storage>,
+    /// Held while the file is open, or why it could not be locked and is read-only
+    lock: Result<Lock, String>,
+    /// The ledger as last loaded or saved, to merge with changes saved elsewhere
+    base: Ledger,
+    /// Whether what was stored has been backed up since the program started
+    backed_up: bool,
+    journal: Journal,
+    label_case: LabelCase,
+}
+
+impl Session {
+    /// Opens and locks the data file, bringing it up to date if it can be written. Returns a
+    /// note of what was done to it or why it is read-only, if either.
+    fn open(config: &Config) -> Result<(Session, Option<String>), Box<dyn Error>> {
+        let cipher = unlock(&config.file_path)?;
+        let mut session = Session {
+            file_path: config.file_path.clone(),
+            storage: storage::open(&config.file_path, config.format, cipher)?,
+            lock: lock::acquire(&config.file_path)?,
+            base: Ledger::default(),
+            backed_up: false,
+            journal: Journal::new(&config.file_path),
+            label_case: config.label_case,
+        };
+
+        let note = match session.check_writable() {
+            Ok(()) => session.storage.upgrade()?,
+            Err(e) => Some(e.to_string()),
+        };
+        Ok((session, note))
+    }
+
+    fn check_writable(&self) -> Result<(), Box<dyn Error>> {
+        match &self.lock {
+            Ok(_) => Ok(()),
+            Err(reason) => Err(Box::from(format!(
+                "{reason}, so it has been opened read-only"
+            ))),
+        }
+    }
+
+    fn load(&mut self) -> Result<Ledger, Box<dyn Error>> {
+        let ledger = self.storage.load()?;
+        self.base = ledger.clone();
+        if self.lock.is_ok() && !self.storage.encrypted() {
+            self.journal.start(&ledger)?;
+        }
+        Ok(ledger)
+    }
+
+    /// Notes the changes a command made in the journal, to be written when they are saved.
+    /// Encrypted files keep no journal.
+    fn record(
+        &mut self,
+        command: &str,
+        changes: &[Change],
+        ledger: &Ledger,
+    ) -> Result<(), Box<dyn Error>> {
+        match self.storage.encrypted() {
+            true => Ok(()),
+            false => self.journal.record(command, changes, ledger),
+        }
+    }
+
+    fn journal(&self) -> Result<&Journal, Box<dyn Error>> {
+        match self.storage.encrypted() {
+            true => Err(Box::from(
+                "Encrypted files keep no journal, as it would hold their contents unencrypted",
+            )),
+            false => Ok(&self.journal),
+        }
+    }
+
+    /// Encrypts the data file and any unencrypted copies of it with a new passphrase,
+    /// removing the journal as it would hold the file's contents unencrypted
+    fn encrypt(&mut self, passphrase: &str) -> Result<String, Box<dyn Error>> {
+        let copies = self.storage.change_passphrase(passphrase)?;
+        let mut message = format!(
+            "{} is now encrypted with the new passphrase. Backups already encrypted \
+            keep the passphrase they were made with",
+            self.file_path
+        );
+        if !copies.is_empty() {
+            message += &format!(
+                ". These unencrypted copies of it have been encrypted too: {}",
+                copies.join(", ")
+            );
+        }
+        match self.journal.remove() {
+            Ok(Some(journal_path)) => {
+                message += &format!(
+                    ". The journal {journal_path} held its contents unencrypted, so it has been removed"
+                )
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return Err(Box::from(format!(
+                    "{message}, but its journal could not be removed: {e}"
+                )))
+            }
+        }
+        Ok(message)
+    }
+
+    /// Replaces the data file with a backup and loads it. As the file has already changed,
+    /// the journal is written at once, leaving out changes made here but never saved.
+    fn restore(&mut self, inputs: Vec<String>) -> Result<(Ledger, String), Box<dyn Error>> {
+        self.check_writable()?;
+        let command = inputs.iter().map(|word| tokenizer::quote(word)).join(" ");
+        let saved = self.base.clone();
+        self.journal.discard();
+        if !self.storage.encrypted() {
+            self.journal.start(&saved)?;
+        }
+
+        let message = restore_backup(inputs, self.storage.as_mut())?;
+        let ledger = self.storage.load()?;
+        self.base = ledger.clone();
+        self.backed_up = true;
+
+        self.record(
+            &command,
+            &history::changes_between(&saved, &ledger),
+            &ledger,
+        )?;
+        if !self.storage.encrypted() {
+            if let Err(e) = self.journal.flush() {
+                return Err(Box::from(format!(
+                    "{message}, but it was not added to the journal: {e}"
+                )));
+            }
+        }
+        Ok((ledger, message))
+    }
+
+    /// Saves the ledger unless the file has changed since it was loaded, first backing up
+    /// what was stored if that has not been done since the program started
+    fn save(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        if self.storage.changed()? {
+            return Err(Box::from(format!(
+                "{} has been changed elsewhere since it was loaded. Enter reload to load it, \
+                losing the changes made here, or merge to add them to it",
+                self.file_path
+            )));
+        }
+        if !self.backed_up {
+            self.storage.backup()?;
+            self.backed_up = true;
+        }
+        self.storage.save(ledger)?;
+        self.base = ledger.clone();
+        if !self.storage.encrypted() {
+            if let Err(e) = self.journal.flush() {
+                return Err(Box::from(format!(
+                    "Saved, but not added to the journal: {e}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts any recurring transactions that have come due, as `post_recurring` does,
+    /// noting them in the journal
+    fn post_recurring(&mut self, ledger: &mut Ledger) -> Result<String, Box<dyn Error>> {
+        let before = ledger.clone();
+        let posted = post_recurring(ledger)?;
+        if let Err(e) = self.record(
+            "post recurring",
+            &history::changes_between(&before, ledger),
+            ledger,
+        ) {
+            *ledger = before;
+            return Err(e);
+        }
+        Ok(posted)
+    }
+
+    /// Adds the changes made here to what is stored now
+    fn merge(&mut self, ledger: &Ledger) -> Result<(Ledger, MergeSummary), Box<dyn Error>> {
+        let theirs = self.storage.load()?;
+        let stored = theirs.clone();
+        let merged = merge::merge(&self.base, ledger, theirs)?;
+        self.base = stored;
+        Ok(merged)
+    }
+}
+
+/// Asks for the passphrase of the data file if it is encrypted, giving a few tries
+fn unlock(file_path: &str) -> Result<Option<Cipher>, Box<dyn Error>> {
+    let contents = match fs::read(file_path) {
+        Ok(contents) if encryption::is_encrypted(&contents) => contents,
+        _ => return Ok(None),
+    };
+    for _ in 0..3 {
+        let passphrase = rpassword::prompt_password(format!("Passphrase for {file_path}: "))?;
+        match Cipher::open(&passphrase, &contents) {
+            Ok(cipher) => return Ok(Some(cipher)),
+            Err(e) => println!("{e}"),
+        }
+    }
+    Err(Box::from(format!("Could not unlock {file_path}")))
+}
+
+/// Asks for a new passphrase twice, without showing it
+fn prompt_new_passphrase() -> Result<String, Box<dyn Error>> {
+    let passphrase = rpassword::prompt_password("New passphrase: ")?;
+    if passphrase != rpassword::prompt_password("Repeat new passphrase: ")? {
+        return Err(Box::from("Passphrases did not match"));
+    }
+    Ok(passphrase)
+}
+
+/// Carries out one command other than quitting, returning anything it has to say.
+/// Category reports read from storage unless there are `unsaved` changes.
+fn execute(
+    inputs: Vec<String>,
+    session: &mut Session,
+    ledger: &mut Ledger,
+    history: &mut History,
+    unsaved: bool,
+) -> Result<String, Box<dyn Error>> {
+    let word = inputs.first().cloned().unwrap_or_default();
+    let command = match commands::find(&word) {
+        Some(command) => command,
+        None => return Err(Box::from(format!("{word} is not a command"))),
+    };
+    let inputs = command.arrange(&inputs)?;
+
+    match command.code {
+        "at" => add_new_transaction(inputs, &mut ledger.accounts, session.label_case)?,
+        "atd" => add_transaction(inputs, &mut ledger.accounts, session.label_case)?,
+        "tr" => add_transfer(inputs, &mut ledger.accounts)?,
+        "aa" => add_account(inputs, &mut ledger.accounts)?,
+        "ra" => remove_account(inputs, &mut ledger.accounts)?,
+        "eta" => edit_transaction_amount(inputs, &mut ledger.accounts)?,
+        "etd" => edit_transaction_date(inputs, &mut ledger.accounts)?,
+        "etl" => edit_transaction_label(inputs, &mut ledger.accounts, session.label_case)?,
+        "etc" => edit_transaction_category(inputs, &mut ledger.accounts)?,
+        "rt" => remove_transaction(inputs, &mut ledger.accounts)?,
+        "cr" if unsaved => return category_report(inputs, &ledger.accounts),
+        "cr" => return saved_category_report(inputs, session.storage.as_mut()),
+        "ab" => set_budget(inputs, &mut ledger.budgets)?,
+        "rb" => remove_budget(inputs, &mut ledger.budgets)?,
+        "ar" => {
+            // the new transaction is only kept if what it makes due can be posted
+            let mut working = ledger.clone();
+            add_recurring(
+                inputs,
+                &working.accounts,
+                &mut working.recurring,
+                session.label_case,
+            )?;
+            let posted = post_recurring(&mut working)?;
+            *ledger = working;
+            return Ok(posted);
+        }
+        "rr" => remove_recurring(inputs, &mut ledger.recurring)?,
+        "import" => return import_statement(inputs, &mut ledger.accounts),
+        "qi" => return import_qif(inputs, &mut ledger.accounts),
+        "qe" => export_qif(inputs, &ledger.accounts)?,
+        "list" => return list_accounts(inputs, &ledger.accounts),
+        "find" => return find_transactions(inputs, &ledger.accounts),
+        "balance" => return show_balances(inputs, &ledger.accounts),
+        "b" => {
+            return match session.storage.backup()? {
+                Some(backup_path) => {
+                    session.backed_up = true;
+                    Ok(format!("Backed up to {backup_path}"))
+                }
+                None => Ok(String::from("Nothing has been saved to back up yet")),
+            }
+        }
+        "restore" if inputs.len() == 1 => return show_backups(session.storage.as_ref()),
+        "restore" => {
+            let (restored, message) = session.restore(inputs)?;
+            *ledger = restored;
+            return Ok(message);
+        }
+        "merge" => {
+            let (merged, summary) = session.merge(ledger)?;
+            *ledger = merged;
+            return Ok(summary.to_string());
+        }
+        "pw" => {
+            session.check_writable()?;
+            let passphrase = prompt_new_passphrase()?;
+            return session.encrypt(&passphrase);
+        }
+        "undo" => return undo(inputs, history, ledger),
+        "redo" => return redo(inputs, history, ledger),
+        "history" => return Ok(show_history(history)),
+        "help" => return show_help(inputs),
+        "log" => return show_log(inputs, session.journal()?),
+        "replay" => {
+            let (replayed, message) = replay_journal(inputs, session.journal()?)?;
+            *ledger = replayed;
+            return Ok(message);
+        }
+        "s" => session.save(ledger)?,
+        "reload" => *ledger = session.load()?,
+        "batch" => return Err(Box::from("A batch can only be run from the command line")),
+        _ => return Err(Box::from(format!("{word} is not a command"))),
+    }
+    Ok(String::new())
+}
+
+/// Carries out a command entered in the menu or the TUI, noting what it changed in the
+/// journal and the history, and whether anything is left unsaved
+fn perform(
+    entered: &str,
+    session: &mut Session,
+    ledger: &mut Ledger,
+    history: &mut History,
+    unsaved: &mut bool,
+) -> Result<String, Box<dyn Error>> {
+    let inputs = tokenizer::tokenize(entered)?;
+    let code = commands::find(inputs.first().map_or("", String::as_str)).map_or("", |c| c.code);
+    let input_len = inputs.len();
+    let before = ledger.clone();
+
+    let result = execute(inputs, session, ledger, history, *unsaved);
+
+    match code {
+        "s" | "reload" => *unsaved = *unsaved && result.is_err(),
+        "restore" if result.is_ok() => *unsaved = *unsaved && input_len == 1,
+        "cr" | "qe" | "list" | "balance" | "b" | "restore" | "pw" | "history" | "log" | "help"
+        | "find" => {}
+        _ => *unsaved = true,
+    }
+
+    let changes = history::changes_between(&before, ledger);
+    // a restore writes its own journal entry
+    if code != "restore" {
+        session.record(entered, &changes, ledger)?;
+    }
+    // undo and redo move through the history rather than adding to it
+    if !matches!(code, "undo" | "redo") {
+        history.record(entered, changes);
+    }
+    result
+}
+
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let (mut session, note) = Session::open(config)?;
+
+    let mut ledger = session.load()?;
+    // a failure is only described, so that the ledger can still be opened to fix it, and
+    // reports read from storage when it holds everything shown
+    let (posted, mut unsaved) = match session.post_recurring(&mut ledger) {
+        Ok(posted) => {
+            let unsaved = !posted.is_empty();
+            (posted, unsaved)
+        }
+        Err(e) => (
+            format!("Recurring transactions could not be posted: {e}"),
+            false,
+        ),
+    };
+    let message = note
+        .into_iter()
+        .chain([posted])
+        .filter(|message| !message.is_empty())
+        .join("\n");
+
+    if config.tui {
+        return tui::run(&mut session, &mut ledger, message, unsaved);
+    }
+
+    let mut message_buffer = String::new();
+    if !message.is_empty() {
+        message_buffer += &format!("\n{message}");
+    }
+    let mut history = History::default();
+    let mut editor = line_editor()?;
+    // what is typed is kept beside the data file, unless that is encrypted
+    let history_path = format!("{}.history", session.file_path);
+    if !session.storage.encrypted() {
+        let _ = editor.load_history(&history_path);
+    }
+
+    let result = 'menu: loop {
         clearscreen::clear().expect("Failed to clear screen");
 
         display_menu();
-        if !accounts.is_empty() {
-            display_accounts(&accounts);
+        if !ledger.accounts.is_empty() {
+            display_accounts(&ledger.accounts);
+        }
+        if !ledger.budgets.is_empty() {
+            display_budgets(&ledger.budgets, &ledger.accounts);
+        }
+        if !ledger.recurring.is_empty() {
+            display_recurring(&ledger.recurring);
         }
         if !message_buffer.is_empty() {
             println!("{message_buffer}\n");
@@ -57,69 +518,580 @@ pub fn run(file_path: &str) -> Result<(), Box<dyn Error>> {
         }
         message_buffer = String::new();
 
-        let mut trimmed_input: String;
-        loop {
-            let mut user_input = String::new();
-            io::stdin().read_line(&mut user_input)?;
-            trimmed_input = String::from(user_input.trim_end_matches('\n'));
-            if !trimmed_input.is_empty() {
-                break;
+        if let Some(completer) = editor.helper_mut() {
+            completer.update(&ledger);
+        }
+        let trimmed_input = loop {
+            match editor.readline("> ") {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => break line,
+                Err(ReadlineError::Interrupted) => {
+                    message_buffer += "\nEnter q to save and exit, or q! to exit without saving";
+                    continue 'menu;
+                }
+                // there is nothing more to read, so leave as q would
+                Err(ReadlineError::Eof) => match session.check_writable() {
+                    Ok(()) => break 'menu session.save(&ledger),
+                    Err(_) => break 'menu Ok(()),
+                },
+                Err(e) => break 'menu Err(Box::from(e)),
             }
+        };
+
+        let command = match tokenizer::tokenize(&trimmed_input) {
+            Ok(words) => words.into_iter().next().unwrap_or_default(),
+            Err(e) => {
+                message_buffer += &format!("\nError: {e}");
+                continue;
+            }
+        };
+        let result = match command.as_str() {
+            "q" if session.check_writable().is_err() => break Ok(()),
+            "q" => match session.save(&ledger) {
+                Ok(()) => break Ok(()),
+                Err(e) => Err(e),
+            },
+            "q!" => break Ok(()),
+            _ if commands::find(&command).is_none() => {
+                message_buffer += "\nPlease enter a valid input";
+                continue;
+            }
+            _ => perform(
+                &trimmed_input,
+                &mut session,
+                &mut ledger,
+                &mut history,
+                &mut unsaved,
+            ),
+        };
+
+        match result {
+            Ok(message) if !message.is_empty() => message_buffer += &format!("\n{message}"),
+            Ok(_) => {}
+            Err(e) => message_buffer += &format!("\nError: {e}"),
+        }
+    };
+
+    // the file may have been encrypted since the history was read
+    if session.storage.encrypted() {
+        let _ = fs::remove_file(&history_path);
+    } else {
+        editor.save_history(&history_path)?;
+    }
+    result
+}
+
+/// Reads menu commands with line editing, completion, and the commands entered before
+fn line_editor() -> Result<Editor<MenuCompleter, DefaultHistory>, Box<dyn Error>> {
+    let config = rustyline::Config::builder()
+        .auto_add_history(true)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::with_config(config)?;
+    editor.set_helper(Some(MenuCompleter::default()));
+    Ok(editor)
+}
+
+/// Carries out the single command given on the command line, saving any change it makes,
+/// and returns what it has to say
+pub fn run_command(config: &Config) -> Result<String, Box<dyn Error>> {
+    let (mut session, note) = Session::open(config)?;
+    if let Some(note) = note {
+        eprintln!("{note}");
+    }
+    session.backed_up = recently_backed_up(&session)?;
+
+    let mut ledger = session.load()?;
+    // a failure is only described, so that commands fixing it can still be run
+    let mut output = match session.post_recurring(&mut ledger) {
+        Ok(posted) => posted,
+        Err(e) => {
+            eprintln!("Recurring transactions could not be posted: {e}");
+            String::new()
+        }
+    };
+    // recurring transactions are only posted for good if the file can be written
+    let mut save = !output.is_empty() && session.check_writable().is_ok();
+
+    let before = ledger.clone();
+    let message = match config.command[0].as_str() {
+        "batch" => run_batch(config.command.clone(), &mut session, &mut ledger)?,
+        _ => {
+            let message = execute(
+                config.command.clone(),
+                &mut session,
+                &mut ledger,
+                &mut History::default(),
+                true,
+            )?;
+            let changes = history::changes_between(&before, &ledger);
+            // a restore writes its own journal entry
+            if commands::find(&config.command[0]).is_none_or(|c| c.code != "restore") {
+                let entered = config
+                    .command
+                    .iter()
+                    .map(|word| tokenizer::quote(word))
+                    .join(" ");
+                session.record(&entered, &changes, &ledger)?;
+            }
+            message
+        }
+    };
+    save |= !history::changes_between(&before, &ledger).is_empty();
+
+    if save && !history::changes_between(&session.base, &ledger).is_empty() {
+        session.save(&ledger)?;
+    }
+
+    if !message.is_empty() {
+        if !output.is_empty() {
+            output += "\n";
+        }
+        output += &message;
+    }
+    Ok(output)
+}
+
+/// Whether the newest backup of the data file was made less than `COMMAND_BACKUP_INTERVAL`
+/// ago, in which case a command given on the command line makes no other
+fn recently_backed_up(session: &Session) -> Result<bool, Box<dyn Error>> {
+    let prefix = format!("{}.", session.file_path);
+    let newest = session.storage.backups()?.pop();
+    let made = newest.and_then(|backup| storage::backup_time(backup.strip_prefix(&prefix)?));
+    Ok(made.is_some_and(|made| Local::now().naive_local() - made < COMMAND_BACKUP_INTERVAL))
+}
+
+/// Runs the commands in a file, or standard input if none is named, one per line as they
+/// would be entered in the menu. The ledger is only changed if every line succeeds.
+fn run_batch(
+    inputs: Vec<String>,
+    session: &mut Session,
+    ledger: &mut Ledger,
+) -> Result<String, Box<dyn Error>> {
+    let contents = read_batch(inputs)?;
+
+    let mut working = ledger.clone();
+    let mut history = History::default();
+    let mut output = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        // blank lines and comments are skipped
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
         }
+        let fail =
+            |e: Box<dyn Error>| format!("Nothing was saved, as line {} failed: {e}", index + 1);
 
-        let split_input = trimmed_input
-            .split(' ')
-            .map(String::from)
-            .collect::<Vec<String>>();
-
-        let mut result = Ok(());
-
-        match split_input.first().unwrap_or(&"".to_string()).as_str() {
-            "at" => result = add_new_transaction(split_input, &mut accounts),
-            "atd" => result = add_transaction(split_input, &mut accounts),
-            "aa" => result = add_account(split_input, &mut accounts),
-            "ra" => result = remove_account(split_input, &mut accounts),
-            "eta" => result = edit_transaction_amount(split_input, &mut accounts),
-            "etd" => result = edit_transaction_date(split_input, &mut accounts),
-            "etl" => result = edit_transaction_label(split_input, &mut accounts),
-            "rt" => result = remove_transaction(split_input, &mut accounts),
-            "s" => result = file_processing::write_to_file(file_path, &accounts),
-            "u" => {
-                accounts = file_processing::read_from_string(file_processing::get_file_contents(
-                    file_path,
-                )?)?
+        let split_input = tokenizer::tokenize(line).map_err(fail)?;
+        match commands::find(&split_input[0]) {
+            Some(command) if command.batch => {}
+            _ => {
+                return Err(Box::from(fail(Box::from(format!(
+                    "{} cannot be run in a batch",
+                    split_input[0]
+                )))))
             }
-            "q" => break,
-            _ => message_buffer += "\nPlease enter a valid input",
         }
 
-        if let Err(ref e) = result {
-            message_buffer += &format!("\nError: {e}");
+        let before = working.clone();
+        let message =
+            execute(split_input, session, &mut working, &mut history, true).map_err(fail)?;
+        // journal entries are only written once the batch has been saved
+        session.record(line, &history::changes_between(&before, &working), &working)?;
+        if !message.is_empty() {
+            output.push(message);
         }
     }
 
-    file_processing::write_to_file(file_path, &accounts)?;
-    Ok(())
+    *ledger = working;
+    Ok(output.join("\n"))
 }
 
-pub fn parse_args(args: &[String]) -> Result<&str, Box<dyn Error>> {
-    let file_type = ".csv";
-    if args.len() != 2 {
+/// The contents of the file named in a batch command, or of standard input if it is `-` or none
+fn read_batch(inputs: Vec<String>) -> Result<String, Box<dyn Error>> {
+    if inputs.len() > 2 {
+        return Err(Box::from("Usage: batch [file]?"));
+    }
+    let mut contents = String::new();
+    match inputs.get(1).map(String::as_str) {
+        None | Some("-") => {
+            io::stdin().read_to_string(&mut contents)?;
+        }
+        Some(file_path) => match fs::read_to_string(file_path) {
+            Ok(read) => contents = read,
+            Err(e) => return Err(Box::from(format!("Could not read {file_path}: {e}"))),
+        },
+    }
+    Ok(contents)
+}
+
+/// What the program was started with: the data file, how it is stored, and the command
+/// to carry out instead of starting the menu, if one was given
+pub struct Config {
+    file_path: String,
+    format: FileFormat,
+    /// Starting with the command's menu code
+    command: Vec<String>,
+    /// Whether to show the full-screen terminal UI rather than the menu
+    tui: bool,
+    label_case: LabelCase,
+}
+
+impl Config {
+    /// Whether a command was given to carry out without the menu
+    pub fn has_command(&self) -> bool {
+        !self.command.is_empty()
+    }
+}
+
+/// Reads `[--tui]? [--keep-case]? [--format csv|json|toml|sqlite]? [file] [command]
+/// [arguments]...`, taking the format from the file's extension when no flag is given
+pub fn parse_args(args: &[String]) -> Result<Config, Box<dyn Error>> {
+    let usage = format!(
+        "Usage: [--tui]? [--keep-case]? [--format {}]? [filename{}] [command [arguments]...]?",
+        FileFormat::NAMES.join("|"),
+        FileFormat::EXTENSIONS.join("|")
+    );
+
+    let mut args = args.get(1..).unwrap_or_default();
+    let (mut tui, mut label_case) = (false, LabelCase::Title);
+    while let [flag, rest @ ..] = args {
+        match flag.as_str() {
+            "--tui" => tui = true,
+            "--keep-case" => label_case = LabelCase::AsTyped,
+            _ => break,
+        }
+        args = rest;
+    }
+    let (format_name, file_path, rest) = match args {
+        [flag, format, file_path, rest @ ..] | [file_path, flag, format, rest @ ..]
+            if flag == "--format" =>
+        {
+            (Some(format), file_path, rest)
+        }
+        [file_path, rest @ ..] => (None, file_path, rest),
+        [] => return Err(Box::from(usage)),
+    };
+
+    let command = match rest.split_first() {
+        None => Vec::new(),
+        Some((word, arguments)) => match commands::find(word) {
+            Some(command) => [String::from(command.code)]
+                .into_iter()
+                .chain(arguments.iter().cloned())
+                .collect(),
+            None => return Err(Box::from(format!("Unknown command {word}\n{usage}"))),
+        },
+    };
+    if tui && !command.is_empty() {
         return Err(Box::from(format!(
-            "Enter only one argument: [filename{}]",
-            file_type
+            "--tui cannot be given with a command\n{usage}"
         )));
+    }
+
+    let format = match format_name {
+        Some(name) => FileFormat::from_name(name),
+        None => FileFormat::from_path(file_path),
     };
+    match format {
+        Ok(format) => Ok(Config {
+            file_path: file_path.clone(),
+            format,
+            command,
+            tui,
+            label_case,
+        }),
+        Err(e) => Err(Box::from(format!("{e}\n{usage}"))),
+    }
+}
 
-    let file_path = match args.get(1) {
-        Some(file_path) if file_path.ends_with(file_type) => file_path,
-        _ => {
-            return Err(Box::from(format!(
-                "Enter a valid file name: [filename{}]",
-                file_type
-            )))
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::budget::Budgets;
+    use crate::file_processing::FileFormat;
+    use crate::history::History;
+    use crate::input_processing::LabelCase;
+    use crate::journal::Journal;
+    use crate::money::Money;
+    use crate::{budget_lines, encryption, parse_args, perform, run_command, storage, Session};
+    use itertools::Itertools;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let config = parse_args(&args(&["budget", "data.toml"])).unwrap();
+        assert_eq!(
+            (config.file_path.as_str(), config.format),
+            ("data.toml", FileFormat::Toml)
+        );
+
+        for given in [
+            ["budget", "--format", "sqlite", "data"],
+            ["budget", "data", "--format", "sqlite"],
+        ] {
+            let config = parse_args(&args(&given)).unwrap();
+            assert_eq!(
+                (config.file_path.as_str(), config.format),
+                ("data", FileFormat::Sqlite)
+            );
         }
-    };
 
-    Ok(file_path)
+        assert!(!parse_args(&args(&["budget", "data.csv"])).unwrap().tui);
+        let config = parse_args(&args(&["budget", "--tui", "--format", "csv", "data"])).unwrap();
+        assert!(config.tui && !config.has_command());
+        assert_eq!(config.label_case, LabelCase::Title);
+
+        let config = parse_args(&args(&["budget", "--keep-case", "--tui", "data.csv"])).unwrap();
+        assert!(config.tui);
+        assert_eq!(config.label_case, LabelCase::AsTyped);
+    }
+
+    #[test]
+    fn parse_args_command() {
+        let config = parse_args(&args(&[
+            "budget",
+            "data.csv",
+            "add-transaction",
+            "savings",
+            "iced coffee",
+            "-4.5",
+        ]))
+        .unwrap();
+        assert!(config.has_command());
+        assert_eq!(config.command, ["at", "savings", "iced coffee", "-4.5"]);
+
+        let config = parse_args(&args(&["budget", "data", "--format", "json", "balance"])).unwrap();
+        assert_eq!(
+            (config.format, config.command),
+            (FileFormat::Json, vec![String::from("balance")])
+        );
+
+        assert!(!parse_args(&args(&["budget", "data.csv"]))
+            .unwrap()
+            .has_command());
+    }
+
+    #[test]
+    fn batch_test() {
+        let file_path = "src/test-files/batch-test.csv";
+        let _ = fs::remove_file(file_path);
+        let _ = fs::remove_file("src/test-files/batch-test.csv.journal");
+
+        let bad = parse_args(&args(&[
+            "budget",
+            file_path,
+            "batch",
+            "src/test-files/batch-bad.txt",
+        ]))
+        .unwrap();
+        assert_eq!(
+            run_command(&bad).err().unwrap().to_string(),
+            "Nothing was saved, as line 3 failed: Account name Checking not present."
+        );
+        assert_eq!(fs::read_to_string(file_path).unwrap(), "");
+
+        let ok = parse_args(&args(&[
+            "budget",
+            file_path,
+            "batch",
+            "src/test-files/batch-ok.txt",
+        ]))
+        .unwrap();
+        assert_eq!(run_command(&ok).unwrap(), "Savings\t-56.60");
+
+        let balance = parse_args(&args(&["budget", file_path, "balance"])).unwrap();
+        assert_eq!(run_command(&balance).unwrap(), "Savings\t-56.60");
+    }
+
+    #[test]
+    fn encrypt_leaves_no_plaintext() {
+        let file_path = "src/test-files/encrypt-test.csv";
+        let journal_path = "src/test-files/encrypt-test.csv.journal";
+        let copy_path = "src/test-files/encrypt-test.csv.v1";
+        for backup in storage::list_backups(file_path).unwrap() {
+            fs::remove_file(backup).unwrap();
+        }
+        let _ = fs::remove_file(file_path);
+        let _ = fs::remove_file(journal_path);
+
+        for name in ["savings", "checking"] {
+            let add = parse_args(&args(&["budget", file_path, "aa", name])).unwrap();
+            run_command(&add).unwrap();
+        }
+        assert!(fs::metadata(journal_path).is_ok());
+        fs::copy(file_path, copy_path).unwrap();
+        let mut copies = storage::list_backups(file_path).unwrap();
+        copies.push(String::from(copy_path));
+        assert_eq!(copies.len(), 2);
+
+        let (mut session, _) =
+            Session::open(&parse_args(&args(&["budget", file_path])).unwrap()).unwrap();
+        let message = session.encrypt("correct horse").unwrap();
+        assert!(message.contains(&format!("The journal {journal_path} held its contents")));
+        assert!(message.contains(&format!("have been encrypted too: {}", copies.join(", "))));
+        assert!(fs::metadata(journal_path).is_err());
+        assert!(session.journal().is_err());
+        for copy in &copies {
+            assert!(encryption::is_encrypted(&fs::read(copy).unwrap()));
+        }
+
+        // there is nothing left to remove or encrypt once encrypted
+        let message = session.encrypt("battery staple").unwrap();
+        assert!(!message.contains("journal"));
+        assert!(!message.contains("copies"));
+        drop(session);
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(copy_path).unwrap();
+    }
+
+    #[test]
+    fn restore_writes_journal() {
+        let file_path = "src/test-files/restore-journal-test.csv";
+        for backup in storage::list_backups(file_path).unwrap() {
+            fs::remove_file(backup).unwrap();
+        }
+        let _ = fs::remove_file(file_path);
+        let _ = fs::remove_file("src/test-files/restore-journal-test.csv.journal");
+        let replayed = || {
+            let (ledger, _) = Journal::new(file_path).replay().unwrap();
+            ledger.accounts.into_keys().sorted().collect::<Vec<_>>()
+        };
+
+        // in the menu, leaving without saving after a restore still keeps it in the journal
+        let (mut session, _) =
+            Session::open(&parse_args(&args(&["budget", file_path])).unwrap()).unwrap();
+        let mut ledger = session.load().unwrap();
+        let (mut history, mut unsaved) = (History::default(), false);
+        for entered in ["aa A", "s", "b", "aa B", "s", "aa C", "restore 1"] {
+            perform(
+                entered,
+                &mut session,
+                &mut ledger,
+                &mut history,
+                &mut unsaved,
+            )
+            .unwrap();
+        }
+        assert_eq!(ledger.accounts.len(), 1);
+        drop(session);
+        assert_eq!(replayed(), ["a"]);
+
+        // a restore given on the command line changes nothing left to save, so it is kept
+        // in the journal without a save
+        let add = parse_args(&args(&["budget", file_path, "aa", "D"])).unwrap();
+        run_command(&add).unwrap();
+        assert_eq!(replayed(), ["a", "d"]);
+        let restore = parse_args(&args(&["budget", file_path, "restore", "1"])).unwrap();
+        run_command(&restore).unwrap();
+        assert_eq!(replayed(), ["a"]);
+    }
+
+    #[test]
+    fn recurring_failure_opens() {
+        let file_path = "src/test-files/recurring-failure-test.csv";
+        let _ = fs::remove_file("src/test-files/recurring-failure-test.csv.journal");
+        fs::write(
+            file_path,
+            "budgeting-app,2\n\
+            record,account,id,date,label,amount,category,transfer account,transfer id,import id,frequency\n\
+            account,Savings,2,,,,,,,,\n\
+            transaction,Savings,1,2024-01-01,Debt,-92233720368547758.00,,,,,\n\
+            recurring,Savings,,2024-01-01,Rent,-1.00,,,,,daily\n",
+        )
+        .unwrap();
+
+        // the ledger still opens, and adding another that cannot be posted changes nothing
+        let (mut session, _) =
+            Session::open(&parse_args(&args(&["budget", file_path])).unwrap()).unwrap();
+        let mut ledger = session.load().unwrap();
+        assert!(session.post_recurring(&mut ledger).is_err());
+        let (mut history, mut unsaved) = (History::default(), false);
+        assert!(perform(
+            "ar savings gym -1 daily 2024-01-01",
+            &mut session,
+            &mut ledger,
+            &mut history,
+            &mut unsaved,
+        )
+        .is_err());
+        assert_eq!(ledger.recurring.len(), 1);
+        assert_eq!(ledger.accounts["savings"].transactions().len(), 1);
+        assert!(history.done().is_empty());
+        drop(session);
+
+        let remove = parse_args(&args(&["budget", file_path, "rr", "1"])).unwrap();
+        assert_eq!(run_command(&remove).unwrap(), "");
+        let balance = parse_args(&args(&["budget", file_path, "balance"])).unwrap();
+        assert_eq!(
+            run_command(&balance).unwrap(),
+            "Savings\t-92233720368547758.00"
+        );
+    }
+
+    #[test]
+    fn commands_share_backups() {
+        let file_path = "src/test-files/command-test.csv";
+        let journal_path = "src/test-files/command-test.csv.journal";
+        for backup in storage::list_backups(file_path).unwrap() {
+            fs::remove_file(backup).unwrap();
+        }
+        let _ = fs::remove_file(file_path);
+        let _ = fs::remove_file(journal_path);
+
+        for name in ["credit card", "savings", "checking", "loan"] {
+            let add = parse_args(&args(&["budget", file_path, "aa", name])).unwrap();
+            run_command(&add).unwrap();
+        }
+        // only the first command that had anything to back up made a backup
+        assert_eq!(storage::list_backups(file_path).unwrap().len(), 1);
+        assert!(fs::read_to_string(journal_path)
+            .unwrap()
+            .contains(r#""command":"aa \"credit card\"""#));
+    }
+
+    #[test]
+    fn budget_errors_shown() {
+        let today = "2024-05-25".parse().unwrap();
+        let mut budgets = Budgets::default();
+        budgets.set_limit("Food", Money::new(10000, 2)).unwrap();
+        let mut accounts = HashMap::new();
+        for name in ["A", "B"] {
+            let mut account = Account::build(name).unwrap();
+            let id = account
+                .add_transaction("Feast", Money::new(i64::MIN + 1, 2), today)
+                .unwrap();
+            account
+                .edit_transaction_category(id, Some(String::from("Food")))
+                .unwrap();
+            accounts.insert(name.to_lowercase(), account);
+        }
+
+        let lines = budget_lines(&budgets, &accounts, today);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("Error: Overflow adding"));
+
+        accounts.remove("b");
+        assert!(budget_lines(&budgets, &accounts, today)[0].starts_with("Food: $"));
+    }
+
+    #[test]
+    fn parse_args_invalid() {
+        for given in [
+            &["budget"][..],
+            &["budget", "data.txt"],
+            &["budget", "a.csv", "b.csv"],
+            &["budget", "a.csv", "q"],
+            &["budget", "--tui", "a.csv", "balance"],
+            &["budget", "--format", "yaml", "data"],
+            &["budget", "--fmt", "csv", "data"],
+        ] {
+            assert!(parse_args(&args(given)).is_err());
+        }
+    }
 }
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::process;
+
+/// An advisory lock on a data file, held on a `.lock` file beside it until dropped. The lock
+/// file itself is left behind, as removing it would let two sessions lock different files.
+pub struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Locks the data file for this session. Returns `Err` holding a message saying who has it
+/// open if another session already does.
+pub fn acquire(file_path: &str) -> Result<Result<Lock, String>, Box<dyn Error>> {
+    let lock_path = format!("{file_path}.lock");
+    let mut file = match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+    {
+        Ok(file) => file,
+        Err(e) => return Err(Box::from(format!("Could not open {lock_path}: {e}"))),
+    };
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = match fs::read_to_string(&lock_path) {
+                Ok(pid) if !pid.trim().is_empty() => format!(" (process {})", pid.trim()),
+                _ => String::new(),
+            };
+            return Ok(Err(format!(
+                "{file_path} is open in another session{holder}"
+            )));
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(Box::from(format!("Could not lock {lock_path}: {e}")))
+        }
+    }
+
+    // the process ID is only there to say who holds the lock
+    file.set_len(0)?;
+    write!(file, "{}", process::id())?;
+    Ok(Ok(Lock { file }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock::acquire;
+
+    #[test]
+    fn lock_test() {
+        let file_path = "src/test-files/lock-test.csv";
+
+        let lock = acquire(file_path).unwrap().unwrap();
+        let held = acquire(file_path).unwrap().err().unwrap();
+        assert_eq!(
+            held,
+            format!(
+                "src/test-files/lock-test.csv is open in another session (process {})",
+                std::process::id()
+            )
+        );
+
+        drop(lock);
+        assert!(acquire(file_path).unwrap().is_ok());
+    }
+}
@@ -1,18 +1,35 @@
 use std::env;
 use std::process::exit;
 
+/// Exit status when a command given on the command line fails
+const EXIT_FAILED: i32 = 1;
+/// Exit status when the arguments cannot be understood
+const EXIT_USAGE: i32 = 2;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let file_path = match budgeting_app::parse_args(&args) {
-        Ok(file_path) => file_path,
+    let config = match budgeting_app::parse_args(&args) {
+        Ok(config) => config,
         Err(e) => {
-            println!("{e}");
-            exit(-1);
+            eprintln!("{e}");
+            exit(EXIT_USAGE);
         }
     };
 
-    if let Err(e) = budgeting_app::run(file_path) {
+    if config.has_command() {
+        match budgeting_app::run_command(&config) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                exit(EXIT_FAILED);
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = budgeting_app::run(&config) {
         println!("Application error: {e}");
         exit(-1);
     }
@@ -0,0 +1,333 @@
+use crate::account::{Account, TransferLink};
+use crate::ledger::Ledger;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// What `merge` had to decide for the user
+#[derive(Default)]
+pub struct MergeSummary {
+    /// Transactions changed both here and elsewhere, where the version here was kept
+    conflicts: usize,
+    /// Transactions added both here and elsewhere under the same ID, renumbered to keep both
+    renumbered: usize,
+}
+
+impl fmt::Display for MergeSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Merged in the changes saved elsewhere")?;
+        if self.conflicts > 0 {
+            write!(
+                f,
+                "\n{} transactions were also changed elsewhere, keeping the changes made here",
+                self.conflicts
+            )?;
+        }
+        if self.renumbered > 0 {
+            write!(
+                f,
+                "\n{} transactions added here were given new IDs, as the same IDs were added elsewhere",
+                self.renumbered
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Combines the changes made in this session, from `base` as loaded to `mine`, with what
+/// is stored now, `theirs`. Where both changed the same thing the change made here wins.
+pub fn merge(
+    base: &Ledger,
+    mine: &Ledger,
+    theirs: Ledger,
+) -> Result<(Ledger, MergeSummary), Box<dyn Error>> {
+    let mut merged = theirs;
+    let mut summary = MergeSummary::default();
+    // transactions copied in from here, by account key and ID
+    let mut copied: Vec<(String, u32)> = Vec::new();
+    // account key, old ID and new ID of each renumbered transaction
+    let mut renumbered: Vec<(String, u32, u32)> = Vec::new();
+
+    for key in base.accounts.keys() {
+        if !mine.accounts.contains_key(key) {
+            merged.accounts.remove(key);
+        }
+    }
+
+    for (key, account) in &mine.accounts {
+        let unchanged = Account::build(account.name())?;
+        let base_account = base.accounts.get(key).unwrap_or(&unchanged);
+
+        let merged_account = match merged.accounts.get_mut(key) {
+            Some(merged_account) => merged_account,
+            // either added here, or removed elsewhere and so kept only if changed here
+            None => {
+                if !base.accounts.contains_key(key) || !same_transactions(base_account, account) {
+                    merged.accounts.insert(key.clone(), account.clone());
+                }
+                continue;
+            }
+        };
+        // IDs given out on either side are not given out again
+        merged_account.set_next_id(account.next_id());
+
+        let ids: BTreeSet<u32> = base_account
+            .transactions()
+            .keys()
+            .chain(account.transactions().keys())
+            .copied()
+            .collect();
+
+        for id in ids {
+            let before = base_account.transactions().get(&id);
+            let here = account.transactions().get(&id);
+            if before == here {
+                continue;
+            }
+            let elsewhere = merged_account.transactions().get(&id);
+
+            match here {
+                None => {
+                    if elsewhere.is_some() {
+                        if elsewhere != before {
+                            summary.conflicts += 1;
+                        }
+                        merged_account.remove_transaction(id)?;
+                    }
+                }
+                Some(transaction) if elsewhere == Some(transaction) => {}
+                Some(transaction) if elsewhere == before => {
+                    merged_account.put_transaction(id, transaction)?;
+                    copied.push((key.clone(), id));
+                }
+                Some(transaction) if before.is_none() => {
+                    let new_id = merged_account.next_id();
+                    merged_account.put_transaction(new_id, transaction)?;
+                    copied.push((key.clone(), new_id));
+                    renumbered.push((key.clone(), id, new_id));
+                    summary.renumbered += 1;
+                }
+                Some(transaction) => {
+                    merged_account.put_transaction(id, transaction)?;
+                    copied.push((key.clone(), id));
+                    summary.conflicts += 1;
+                }
+            }
+        }
+    }
+
+    relink(&mut merged, &copied, &renumbered)?;
+
+    let categories: BTreeSet<&String> = base
+        .budgets
+        .limits()
+        .keys()
+        .chain(mine.budgets.limits().keys())
+        .collect();
+    for category in categories {
+        let here = mine.budgets.limits().get(category);
+        if base.budgets.limits().get(category) == here {
+            continue;
+        }
+        match here {
+            Some(limit) => merged.budgets.set_limit(category, *limit)?,
+            None => {
+                if merged.budgets.limits().contains_key(category) {
+                    merged.budgets.remove_limit(category)?;
+                }
+            }
+        }
+    }
+
+    if mine.recurring != base.recurring {
+        merged.recurring = mine.recurring.clone();
+    }
+
+    Ok((merged, summary))
+}
+
+fn same_transactions(a: &Account, b: &Account) -> bool {
+    a.transactions() == b.transactions()
+}
+
+/// Points transfers copied in from here at the new IDs of any renumbered transactions
+fn relink(
+    merged: &mut Ledger,
+    copied: &[(String, u32)],
+    renumbered: &[(String, u32, u32)],
+) -> Result<(), Box<dyn Error>> {
+    for (key, id) in copied {
+        // unwraps safe as these were just put in
+        let account = merged.accounts.get_mut(key).unwrap();
+        let link = match account.transactions().get(id).unwrap().transfer() {
+            Some(link) => link.clone(),
+            None => continue,
+        };
+        if let Some((_, _, new_id)) = renumbered.iter().find(|(other_key, old_id, _)| {
+            *other_key == link.account().to_lowercase() && *old_id == link.id()
+        }) {
+            account.link_transaction(*id, Some(TransferLink::new(link.account(), *new_id)))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::ledger::Ledger;
+    use crate::merge::merge;
+    use crate::money::Money;
+    use crate::transfer::transfer;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn form_base() -> Ledger {
+        let mut savings = Account::build("Savings").unwrap();
+        for (label, amount) in [("a", 100), ("b", 200), ("c", 300)] {
+            savings
+                .add_transaction(label, Money::new(amount, 2), date("2024-05-25"))
+                .unwrap();
+        }
+        let mut ledger = Ledger::from(HashMap::from([
+            (String::from("savings"), savings),
+            (String::from("cash"), Account::build("Cash").unwrap()),
+        ]));
+        ledger
+            .budgets
+            .set_limit("Fun", Money::new(5000, 2))
+            .unwrap();
+        ledger
+    }
+
+    fn labels(ledger: &Ledger, key: &str) -> Vec<(u32, String)> {
+        let mut labels: Vec<(u32, String)> = ledger.accounts[key]
+            .transactions()
+            .values()
+            .map(|t| (t.id(), String::from(t.label())))
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn separate_changes() {
+        let base = form_base();
+
+        let mut mine = base.clone();
+        let savings = mine.accounts.get_mut("savings").unwrap();
+        savings
+            .edit_transaction_label(1, String::from("mine"))
+            .unwrap();
+        savings.remove_transaction(3).unwrap();
+        mine.budgets.remove_limit("Fun").unwrap();
+        mine.accounts
+            .insert(String::from("card"), Account::build("Card").unwrap());
+
+        let mut theirs = base.clone();
+        let savings = theirs.accounts.get_mut("savings").unwrap();
+        savings
+            .edit_transaction_label(2, String::from("theirs"))
+            .unwrap();
+        theirs
+            .accounts
+            .insert(String::from("loan"), Account::build("Loan").unwrap());
+
+        let (merged, summary) = merge(&base, &mine, theirs).unwrap();
+
+        assert_eq!(
+            labels(&merged, "savings"),
+            vec![(1, String::from("mine")), (2, String::from("theirs"))]
+        );
+        assert_eq!(merged.accounts["savings"].balance(), &Money::new(300, 2));
+        assert_eq!(merged.accounts.len(), 4);
+        assert!(merged.budgets.is_empty());
+        assert_eq!(summary.to_string(), "Merged in the changes saved elsewhere");
+    }
+
+    #[test]
+    fn conflicting_changes() {
+        let base = form_base();
+
+        let mut mine = base.clone();
+        let savings = mine.accounts.get_mut("savings").unwrap();
+        savings
+            .edit_transaction_label(1, String::from("mine"))
+            .unwrap();
+        savings
+            .add_transaction("new mine", Money::new(1, 2), date("2024-05-26"))
+            .unwrap();
+        transfer(
+            &mut mine.accounts,
+            "savings",
+            "cash",
+            Money::new(50, 2),
+            date("2024-05-26"),
+        )
+        .unwrap();
+
+        let mut theirs = base.clone();
+        let savings = theirs.accounts.get_mut("savings").unwrap();
+        savings
+            .edit_transaction_label(1, String::from("theirs"))
+            .unwrap();
+        savings
+            .add_transaction("new theirs", Money::new(2, 2), date("2024-05-26"))
+            .unwrap();
+        savings
+            .add_transaction("newer theirs", Money::new(3, 2), date("2024-05-26"))
+            .unwrap();
+
+        let (merged, summary) = merge(&base, &mine, theirs).unwrap();
+
+        assert_eq!(
+            labels(&merged, "savings"),
+            vec![
+                (1, String::from("mine")),
+                (2, String::from("b")),
+                (3, String::from("c")),
+                (4, String::from("new theirs")),
+                (5, String::from("newer theirs")),
+                (6, String::from("new mine")),
+                (7, String::from("Transfer")),
+            ]
+        );
+        let link = merged.accounts["cash"].transactions()[&1]
+            .transfer()
+            .unwrap()
+            .clone();
+        assert_eq!((link.account(), link.id()), ("Savings", 7));
+        assert_eq!(
+            summary.to_string(),
+            "Merged in the changes saved elsewhere\n\
+            1 transactions were also changed elsewhere, keeping the changes made here\n\
+            2 transactions added here were given new IDs, as the same IDs were added elsewhere"
+        );
+    }
+
+    #[test]
+    fn account_removed_elsewhere() {
+        let base = form_base();
+
+        let mut mine = base.clone();
+        mine.accounts
+            .get_mut("cash")
+            .unwrap()
+            .add_transaction("kept", Money::new(100, 2), date("2024-05-26"))
+            .unwrap();
+
+        let mut theirs = base.clone();
+        theirs.accounts.remove("cash");
+        theirs.accounts.remove("savings");
+
+        let (merged, _) = merge(&base, &mine, theirs).unwrap();
+
+        assert!(!merged.accounts.contains_key("savings"));
+        assert_eq!(labels(&merged, "cash"), vec![(1, String::from("kept"))]);
+    }
+}
@@ -0,0 +1,273 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+const DEFAULT_SCALE: u32 = 2;
+/// The most decimal places an amount can have, which keeps amounts of any two scales well
+/// within i64 when they are brought to the same scale to be added
+const MAX_SCALE: usize = 4;
+
+/// A fixed-point amount of money, stored as a whole number of minor units
+/// (e.g. cents) along with the number of decimal places those units represent.
+#[derive(Clone, Copy, Debug)]
+pub struct Money {
+    minor: i64,
+    scale: u32,
+}
+
+impl Money {
+    pub fn new(minor: i64, scale: u32) -> Money {
+        Money { minor, scale }
+    }
+
+    pub fn zero() -> Money {
+        Self::new(0, DEFAULT_SCALE)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor == 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, Box<dyn Error>> {
+        let sum = Self::align(self, other)
+            .and_then(|(a, b)| Some(Self::new(a.minor.checked_add(b.minor)?, a.scale)));
+        match sum {
+            Some(sum) => Ok(sum),
+            None => Err(Box::from(format!("Overflow adding {} to {}", other, self))),
+        }
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, Box<dyn Error>> {
+        let difference = Self::align(self, other)
+            .and_then(|(a, b)| Some(Self::new(a.minor.checked_sub(b.minor)?, a.scale)));
+        match difference {
+            Some(difference) => Ok(difference),
+            None => Err(Box::from(format!(
+                "Overflow subtracting {} from {}",
+                other, self
+            ))),
+        }
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money, Box<dyn Error>> {
+        match self.minor.checked_mul(factor) {
+            Some(minor) => Ok(Self::new(minor, self.scale)),
+            None => Err(Box::from(format!(
+                "Overflow multiplying {} by {}",
+                self, factor
+            ))),
+        }
+    }
+
+    pub fn checked_neg(self) -> Result<Money, Box<dyn Error>> {
+        match self.minor.checked_neg() {
+            Some(minor) => Ok(Self::new(minor, self.scale)),
+            None => Err(Box::from(format!("Overflow negating {}", self))),
+        }
+    }
+
+    fn rescale(self, scale: u32) -> Option<Money> {
+        let factor = 10i64.checked_pow(scale.checked_sub(self.scale)?)?;
+        Some(Self::new(self.minor.checked_mul(factor)?, scale))
+    }
+
+    /// Brings both amounts to the larger of their two scales, if neither overflows
+    fn align(a: Money, b: Money) -> Option<(Money, Money)> {
+        let scale = a.scale.max(b.scale);
+        Some((a.rescale(scale)?, b.rescale(scale)?))
+    }
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl PartialEq for Money {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Money {}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Money {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare in i128 so that differing scales rarely overflow
+        let scale = self.scale.max(other.scale);
+        let widen = |m: &Money| match m.minor {
+            0 => Some(0),
+            minor => 10i128
+                .checked_pow(scale - m.scale)
+                .and_then(|factor| (minor as i128).checked_mul(factor)),
+        };
+        match (widen(self), widen(other)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            // only the amount with fewer decimal places can overflow, which puts it further
+            // from zero than the other
+            (None, _) => self.minor.cmp(&0),
+            (_, None) => 0.cmp(&other.minor),
+        }
+    }
+}
+
+impl FromStr for Money {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Box::from(format!("{} is not a valid amount", s));
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (digits, ""),
+        };
+
+        if whole.is_empty() && fraction.is_empty()
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        if fraction.len() > MAX_SCALE {
+            return Err(Box::from(format!(
+                "Amount {s} has more than {MAX_SCALE} decimal places"
+            )));
+        }
+
+        let scale = DEFAULT_SCALE.max(fraction.len() as u32);
+        let padded = format!("{whole}{fraction:0<width$}", width = scale as usize);
+
+        let mut minor: i64 = match padded.parse() {
+            Ok(val) => val,
+            Err(_) => return Err(Box::from(format!("Amount {} is too large", s))),
+        };
+        if negative {
+            minor = -minor;
+        }
+
+        Ok(Self::new(minor, scale))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let magnitude = self.minor.unsigned_abs();
+
+        if self.scale == 0 {
+            return write!(f, "{sign}{magnitude}");
+        }
+
+        // past 10^19 the divisor is more than any magnitude, leaving it all as the fraction
+        let (whole, fraction) = match 10u64.checked_pow(self.scale) {
+            Some(divisor) => (magnitude / divisor, magnitude % divisor),
+            None => (0, magnitude),
+        };
+        write!(
+            f,
+            "{sign}{whole}.{fraction:0width$}",
+            width = self.scale as usize
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::Money;
+
+    #[test]
+    fn parse_and_display() {
+        assert_eq!("10".parse::<Money>().unwrap().to_string(), "10.00");
+        assert_eq!("-4.5".parse::<Money>().unwrap().to_string(), "-4.50");
+        assert_eq!("+0.07".parse::<Money>().unwrap().to_string(), "0.07");
+        assert_eq!(".5".parse::<Money>().unwrap().to_string(), "0.50");
+        assert_eq!("1.125".parse::<Money>().unwrap().to_string(), "1.125");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for input in ["", "-", ".", "1.2.3", "abc", "1e5", "--1", "0.00001"] {
+            assert!(input.parse::<Money>().is_err(), "{input} should not parse");
+        }
+    }
+
+    #[test]
+    fn large_scales() {
+        let finest: Money = "0.0001".parse().unwrap();
+        assert_eq!(finest.to_string(), "0.0001");
+        assert_eq!(
+            "92233720368547.75"
+                .parse::<Money>()
+                .unwrap()
+                .checked_add(finest)
+                .unwrap(),
+            Money::new(922337203685477501, 4)
+        );
+        assert_eq!(
+            Money::new(5, 30).to_string(),
+            "0.000000000000000000000000000005"
+        );
+        assert!(Money::new(1, 0) > Money::new(i64::MAX, 40));
+        assert!(Money::new(-1, 0) < Money::new(i64::MIN, 40));
+        assert!(Money::new(0, 0) > Money::new(-1, 40));
+        assert_eq!(
+            Money::new(1, 0)
+                .checked_add(Money::new(1, 40))
+                .unwrap_err()
+                .to_string(),
+            "Overflow adding 0.0000000000000000000000000000000000000001 to 1"
+        );
+    }
+
+    #[test]
+    fn exact_sums() {
+        let mut total = Money::zero();
+        let cent: Money = "0.01".parse().unwrap();
+        for _ in 0..100_000 {
+            total = total.checked_add(cent).unwrap();
+        }
+        assert_eq!(total, Money::new(1000, 0));
+    }
+
+    #[test]
+    fn mixed_scales() {
+        let a = Money::new(150, 2);
+        let b = Money::new(1250, 3);
+        assert_eq!(a.checked_add(b).unwrap(), Money::new(2750, 3));
+        assert_eq!(a.checked_sub(b).unwrap(), Money::new(25, 2));
+        assert!(b < a);
+        assert_eq!(a.checked_mul(-3).unwrap(), Money::new(-450, 2));
+    }
+
+    #[test]
+    fn overflow() {
+        let max = Money::new(i64::MAX, 2);
+        assert!(max.checked_add(Money::new(1, 2)).is_err());
+        assert!(Money::new(i64::MIN, 2).checked_neg().is_err());
+        assert!(max.checked_mul(2).is_err());
+        assert!(Money::new(i64::MIN, 2)
+            .checked_sub(Money::new(1, 2))
+            .is_err());
+        assert!("92233720368547758.08".parse::<Money>().is_err());
+    }
+}
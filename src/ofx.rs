@@ -0,0 +1,179 @@
+use crate::money::Money;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// One STMTTRN entry of an OFX statement
+pub struct StatementTransaction {
+    fitid: String,
+    date: NaiveDate,
+    amount: Money,
+    label: String,
+}
+
+impl StatementTransaction {
+    /// The bank's ID for the transaction, unique within the account it came from
+    pub fn fitid(&self) -> &str {
+        &self.fitid
+    }
+    pub fn date(&self) -> &NaiveDate {
+        &self.date
+    }
+    pub fn amount(&self) -> &Money {
+        &self.amount
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Reads every transaction from an OFX or QFX statement. Works for both OFX 1.x, which is
+/// SGML and leaves elements such as <TRNAMT> unclosed, and OFX 2.x, which is XML.
+pub fn parse(contents: &str) -> Result<Vec<StatementTransaction>, Box<dyn Error>> {
+    // everything before <OFX> is the header, which differs between versions
+    let body = match contents.find("<OFX>") {
+        Some(start) => &contents[start..],
+        None => return Err(Box::from("Malformed OFX - No <OFX> element")),
+    };
+
+    let mut transactions = Vec::new();
+    let mut fields: Option<HashMap<String, String>> = None;
+
+    for piece in body.split('<').skip(1) {
+        let (tag, text) = match piece.split_once('>') {
+            Some((tag, text)) => (tag.trim().to_uppercase(), text.trim()),
+            None => {
+                return Err(Box::from(format!(
+                    "Malformed OFX - Tag <{piece} not closed"
+                )))
+            }
+        };
+
+        match tag.as_str() {
+            "STMTTRN" => {
+                if fields.is_some() {
+                    return Err(Box::from("Malformed OFX - <STMTTRN> inside <STMTTRN>"));
+                }
+                fields = Some(HashMap::new());
+            }
+            "/STMTTRN" => match fields.take() {
+                Some(f) => transactions.push(build_transaction(&f, transactions.len() + 1)?),
+                None => return Err(Box::from("Malformed OFX - </STMTTRN> without <STMTTRN>")),
+            },
+            _ => {
+                // closing tags of 2.x elements carry no text of their own
+                if let Some(f) = fields.as_mut() {
+                    if !tag.starts_with('/') && !text.is_empty() {
+                        f.insert(tag, decode_entities(text));
+                    }
+                }
+            }
+        }
+    }
+
+    if fields.is_some() {
+        return Err(Box::from("Malformed OFX - <STMTTRN> not closed"));
+    }
+
+    Ok(transactions)
+}
+
+fn build_transaction(
+    fields: &HashMap<String, String>,
+    number: usize,
+) -> Result<StatementTransaction, Box<dyn Error>> {
+    let field = |name: &str| match fields.get(name) {
+        Some(value) => Ok(value.as_str()),
+        None => Err(Box::<dyn Error>::from(format!(
+            "Statement transaction {number} has no {name}"
+        ))),
+    };
+
+    // dates are YYYYMMDD, optionally followed by a time and time zone
+    let posted = field("DTPOSTED")?;
+    let date = match posted
+        .get(..8)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+    {
+        Some(date) => date,
+        None => return Err(Box::from(format!("Date {posted} not valid"))),
+    };
+
+    // some banks write a leading plus or a decimal comma
+    let trnamt = field("TRNAMT")?;
+    let amount: Money = match trnamt.trim_start_matches('+').replace(',', ".").parse() {
+        Ok(amount) => amount,
+        Err(e) => return Err(Box::from(format!("Amount {trnamt} not valid: {e}"))),
+    };
+
+    let label = field("NAME")
+        .or_else(|_| field("MEMO"))
+        .or_else(|_| field("TRNTYPE"))?;
+
+    Ok(StatementTransaction {
+        fitid: String::from(field("FITID")?),
+        date,
+        amount,
+        label: String::from(label),
+    })
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::Money;
+    use crate::ofx::parse;
+
+    #[test]
+    fn sgml() {
+        let statement = "OFXHEADER:100\r\nDATA:OFXSGML\r\nVERSION:102\r\n\r\n\
+            <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>\r\n\
+            <DTSTART>20240501\r\n\
+            <STMTTRN>\r\n<TRNTYPE>DEBIT\r\n<DTPOSTED>20240502120000.000[-5:EST]\r\n\
+            <TRNAMT>-45.20\r\n<FITID>A1\r\n<NAME>WOOLWORTHS\r\n<MEMO>Card 1234\r\n</STMTTRN>\r\n\
+            <STMTTRN>\r\n<TRNTYPE>INT\r\n<DTPOSTED>20240531\r\n\
+            <TRNAMT>+0,35\r\n<FITID>A2\r\n</STMTTRN>\r\n\
+            </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+        let transactions = parse(statement).unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].fitid(), "A1");
+        assert_eq!(transactions[0].date(), &"2024-05-02".parse().unwrap());
+        assert_eq!(transactions[0].amount(), &Money::new(-4520, 2));
+        assert_eq!(transactions[0].label(), "WOOLWORTHS");
+        assert_eq!(transactions[1].amount(), &Money::new(35, 2));
+        assert_eq!(transactions[1].label(), "INT");
+    }
+
+    #[test]
+    fn xml() {
+        let statement = "<?xml version=\"1.0\"?>\n<?OFX OFXHEADER=\"200\" VERSION=\"220\"?>\n\
+            <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>\n\
+            <STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240502</DTPOSTED>\
+            <TRNAMT>-12.00</TRNAMT><FITID>X9</FITID><NAME>Fish &amp; Chips</NAME></STMTTRN>\n\
+            </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n";
+
+        let transactions = parse(statement).unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].fitid(), "X9");
+        assert_eq!(transactions[0].label(), "Fish & Chips");
+        assert_eq!(transactions[0].amount(), &Money::new(-1200, 2));
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("no statement here").is_err());
+        assert!(parse("<OFX><STMTTRN><DTPOSTED>20240502<TRNAMT>1<NAME>x</STMTTRN></OFX>").is_err());
+        assert!(parse("<OFX><STMTTRN><FITID>1<DTPOSTED>2024<TRNAMT>1</STMTTRN></OFX>").is_err());
+        assert!(parse("<OFX><STMTTRN><FITID>1<DTPOSTED>20240502<TRNAMT>1</OFX>").is_err());
+    }
+}
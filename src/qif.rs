@@ -0,0 +1,354 @@
+use crate::account::{Account, TransferLink};
+use crate::money::Money;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// Section headers for the account types that hold plain transactions
+const TRANSACTION_TYPES: [&str; 5] = ["bank", "cash", "ccard", "oth a", "oth l"];
+
+/// Writes every account as a QIF bank account. Transfers are written as a category naming
+/// the other account in brackets, the way other finance software records them.
+pub fn write(accounts: &HashMap<String, Account>) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for account in accounts
+        .values()
+        .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+    {
+        lines.push(String::from("!Account"));
+        lines.push(format!("N{}", account.name()));
+        lines.push(String::from("TBank"));
+        lines.push(String::from("^"));
+        lines.push(String::from("!Type:Bank"));
+
+        for transaction in account
+            .transactions()
+            .values()
+            .sorted_by(|a, b| Ord::cmp(&(a.date(), a.id()), &(b.date(), b.id())))
+        {
+            lines.push(format!("D{}", transaction.date().format("%m/%d/%Y")));
+            lines.push(format!("T{}", transaction.amount()));
+            lines.push(format!("P{}", transaction.label()));
+            match (transaction.transfer(), transaction.category()) {
+                (Some(link), _) => lines.push(format!("L[{}]", link.account())),
+                (None, Some(category)) => lines.push(format!("L{category}")),
+                (None, None) => {}
+            }
+            lines.push(String::from("^"));
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Reads the accounts in a QIF file, keyed by lowercase name. Transactions listed before
+/// any account is named go in `default_account`. A transaction whose category is another
+/// account in brackets is linked as a transfer when that account has the matching side.
+pub fn read(
+    contents: &str,
+    default_account: Option<&str>,
+) -> Result<HashMap<String, Account>, Box<dyn Error>> {
+    let mut accounts: HashMap<String, Account> = HashMap::new();
+    let mut current: Option<String> = default_account.map(String::from);
+    let mut in_account_list = false;
+    let mut in_transactions = false;
+    let mut record: HashMap<char, String> = HashMap::new();
+    // transactions naming another account, to be paired up with their other side
+    let mut transfers: Vec<(String, u32, String)> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('!') {
+            if !record.is_empty() {
+                return Err(Box::from(format!(
+                    "Line {line_number}: record not ended with ^"
+                )));
+            }
+            in_account_list = header.eq_ignore_ascii_case("account");
+            in_transactions = header
+                .split_once(':')
+                .filter(|(kind, _)| kind.eq_ignore_ascii_case("type"))
+                .is_some_and(|(_, kind)| TRANSACTION_TYPES.contains(&kind.to_lowercase().trim()));
+            if in_transactions && current.is_none() {
+                return Err(Box::from(format!(
+                    "Line {line_number}: no account named for transactions"
+                )));
+            }
+            continue;
+        }
+
+        if line != "^" {
+            let mut chars = line.chars();
+            // unwrap safe as empty lines were skipped
+            let code = chars.next().unwrap();
+            record
+                .entry(code)
+                .or_insert_with(|| chars.as_str().trim().to_string());
+            continue;
+        }
+
+        let fields = std::mem::take(&mut record);
+        if in_account_list {
+            let name = match fields.get(&'N') {
+                Some(name) => name.clone(),
+                None => {
+                    return Err(Box::from(format!(
+                        "Line {line_number}: account has no name"
+                    )))
+                }
+            };
+            get_or_add_account(&mut accounts, &name)?;
+            current = Some(name);
+        } else if in_transactions && !fields.is_empty() {
+            // unwrap safe as transactions cannot start without an account
+            let name = current.as_deref().unwrap();
+            let account = get_or_add_account(&mut accounts, name)?;
+            let (id, other) =
+                add_record(account, &fields).map_err(|e| format!("Line {line_number}: {e}"))?;
+            if let Some(other) = other {
+                transfers.push((name.to_lowercase(), id, other));
+            }
+        }
+    }
+
+    if !record.is_empty() {
+        return Err(Box::from("Last record not ended with ^"));
+    }
+
+    link_transfers(&mut accounts, &transfers)?;
+    Ok(accounts)
+}
+
+fn get_or_add_account<'a>(
+    accounts: &'a mut HashMap<String, Account>,
+    name: &str,
+) -> Result<&'a mut Account, Box<dyn Error>> {
+    match accounts.entry(name.to_lowercase()) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => Ok(entry.insert(Account::build(name)?)),
+    }
+}
+
+/// Adds the transaction in a record, returning its ID and the account it is a transfer with
+fn add_record(
+    account: &mut Account,
+    fields: &HashMap<char, String>,
+) -> Result<(u32, Option<String>), Box<dyn Error>> {
+    let date = match fields.get(&'D') {
+        Some(date) => parse_date(date)?,
+        None => return Err(Box::from("Transaction has no date")),
+    };
+
+    let amount: Money = match fields.get(&'T').or(fields.get(&'U')) {
+        Some(amount) => match amount.replace(',', "").parse() {
+            Ok(amount) => amount,
+            Err(e) => return Err(Box::from(format!("Amount {amount} not valid: {e}"))),
+        },
+        None => return Err(Box::from("Transaction has no amount")),
+    };
+
+    let label = match fields.get(&'P').or(fields.get(&'M')) {
+        Some(label) if !label.is_empty() => label,
+        _ => return Err(Box::from("Transaction has no payee")),
+    };
+
+    // categories may carry a class after a slash, which has no equivalent here
+    let category = fields
+        .get(&'L')
+        .map(|l| l.split('/').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty());
+    let other = category
+        .and_then(|l| l.strip_prefix('['))
+        .and_then(|l| l.strip_suffix(']'))
+        .map(String::from);
+
+    let id = account.add_transaction(label, amount, date)?;
+    if other.is_none() {
+        account.edit_transaction_category(id, category.map(String::from))?;
+    }
+    Ok((id, other))
+}
+
+/// Links each transfer to a transaction in the other account that names this one,
+/// on the same day and for the opposite amount
+fn link_transfers(
+    accounts: &mut HashMap<String, Account>,
+    transfers: &[(String, u32, String)],
+) -> Result<(), Box<dyn Error>> {
+    let mut linked: HashSet<(&str, u32)> = HashSet::new();
+
+    for (account, id, other) in transfers {
+        if linked.contains(&(account.as_str(), *id)) {
+            continue;
+        }
+        // unwraps safe as every transfer was recorded from a transaction that was added
+        let transaction = accounts
+            .get(account)
+            .unwrap()
+            .transactions()
+            .get(id)
+            .unwrap();
+        let (date, amount) = (*transaction.date(), transaction.amount().checked_neg()?);
+
+        let counterpart = transfers.iter().find(|(b, b_id, b_other)| {
+            *b == other.to_lowercase()
+                && b_other.to_lowercase() == *account
+                && !linked.contains(&(b.as_str(), *b_id))
+                && accounts[b]
+                    .transactions()
+                    .get(b_id)
+                    .is_some_and(|t| *t.date() == date && *t.amount() == amount)
+        });
+
+        if let Some((other_key, other_id, _)) = counterpart {
+            let name = String::from(accounts[account].name());
+            let other_name = String::from(accounts[other_key].name());
+            // unwraps safe as the counterpart was found in these accounts
+            accounts
+                .get_mut(account)
+                .unwrap()
+                .link_transaction(*id, Some(TransferLink::new(&other_name, *other_id)))?;
+            accounts
+                .get_mut(other_key)
+                .unwrap()
+                .link_transaction(*other_id, Some(TransferLink::new(&name, *id)))?;
+            linked.insert((account.as_str(), *id));
+            linked.insert((other_key.as_str(), *other_id));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the date formats QIF files are found with: "05/26/2024", "5/26'24", "5/26/24"
+/// and "2024-05-26"
+fn parse_date(s: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    let invalid = || Box::<dyn Error>::from(format!("Date {s} not valid"));
+
+    // an apostrophe before the year marks years from 2000
+    let (rest, year_2000s) = match s.replace(' ', "").split_once('\'') {
+        Some((rest, year)) => (format!("{rest}/{year}"), true),
+        None => (s.replace(' ', ""), false),
+    };
+    let parts: Vec<u32> = match rest.split(['/', '-']).map(|p| p.parse()).collect() {
+        Ok(parts) => parts,
+        Err(_) => return Err(invalid()),
+    };
+
+    let (year, month, day) = match parts[..] {
+        [year, month, day] if year > 999 => (year, month, day),
+        [month, day, year] if year > 999 => (year, month, day),
+        [month, day, year] if year < 100 && (year_2000s || year < 70) => (2000 + year, month, day),
+        [month, day, year] if year < 100 => (1900 + year, month, day),
+        _ => return Err(invalid()),
+    };
+    NaiveDate::from_ymd_opt(year as i32, month, day).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::money::Money;
+    use crate::qif::*;
+    use crate::transfer::transfer;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn form_accounts() -> HashMap<String, Account> {
+        let mut savings = Account::build("Savings").unwrap();
+        let id = savings
+            .add_transaction("Shop", Money::new(-2000, 2), date("2024-05-01"))
+            .unwrap();
+        savings
+            .edit_transaction_category(id, Some(String::from("Groceries")))
+            .unwrap();
+        savings
+            .add_transaction("Pay", Money::new(100000, 2), date("2024-05-15"))
+            .unwrap();
+
+        let mut accounts = HashMap::from([
+            (String::from("savings"), savings),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ]);
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(5000, 2),
+            date("2024-05-20"),
+        )
+        .unwrap();
+        accounts
+    }
+
+    #[test]
+    fn write_test() {
+        assert_eq!(
+            write(&form_accounts()),
+            "!Account\nNChecking\nTBank\n^\n!Type:Bank\n\
+            D05/20/2024\nT50.00\nPTransfer\nL[Savings]\n^\n\
+            !Account\nNSavings\nTBank\n^\n!Type:Bank\n\
+            D05/01/2024\nT-20.00\nPShop\nLGroceries\n^\n\
+            D05/15/2024\nT1000.00\nPPay\n^\n\
+            D05/20/2024\nT-50.00\nPTransfer\nL[Checking]\n^\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let accounts = form_accounts();
+        let read_back = read(&write(&accounts), None).unwrap();
+
+        assert_eq!(read_back.len(), 2);
+        for (key, account) in &accounts {
+            assert_eq!(
+                format!("{}", read_back.get(key).unwrap()),
+                format!("{account}")
+            );
+        }
+    }
+
+    #[test]
+    fn other_software() {
+        let contents = "!Type:Bank\r\n\
+            D5/26'24\r\nU-1,234.50\r\nT-1,234.50\r\nN101\r\nPRent, May\r\nLHousing:Rent/Home\r\n^\r\n\
+            D05/27/24\r\nT20\r\nMCash deposit\r\nCX\r\n^\r\n\
+            D2024-05-28\r\nT-5\r\nPTo savings\r\nL[Savings]\r\n^\r\n\
+            !Type:Cat\r\nNHousing\r\nE\r\n^\r\n";
+
+        let accounts = read(contents, Some("Everyday")).unwrap();
+        let everyday = accounts.get("everyday").unwrap();
+
+        assert_eq!(
+            format!("{everyday}"),
+            "Name: Everyday | Balance: $-1219.50\n\
+            Transactions:\n\
+            ID: 1 | Date: 26 May 2024 | Label: Rent, May | Amount: $-1234.50 | Category: Housing:Rent\n\
+            ID: 2 | Date: 27 May 2024 | Label: Cash deposit | Amount: $20.00\n\
+            ID: 3 | Date: 28 May 2024 | Label: To savings | Amount: $-5.00"
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        // transactions with no account to go in
+        assert!(read("!Type:Bank\nD05/26/2024\nT1\nPx\n^\n", None).is_err());
+        assert!(read("!Type:Bank\nD13/26/2024\nT1\nPx\n^\n", Some("a")).is_err());
+        assert!(read("!Type:Bank\nD05/26/2024\nTone\nPx\n^\n", Some("a")).is_err());
+        assert!(read("!Type:Bank\nD05/26/2024\nT1\n^\n", Some("a")).is_err());
+        assert!(read("!Type:Bank\nD05/26/2024\nT1\nPx\n", Some("a")).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::account::Account;
+use crate::money::Money;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    /// On the given day of every month, or the last day for months that are too short
+    Monthly(u32),
+    /// On the given month and day every year, or 28 Feb for 29 Feb outside leap years
+    Yearly(u32, u32),
+}
+
+impl Frequency {
+    /// Builds a frequency from its name, taking the day to repeat on from the first occurrence
+    pub fn build(name: &str, first: NaiveDate) -> Result<Frequency, Box<dyn Error>> {
+        match name.to_lowercase().as_str() {
+            "daily" => Ok(Frequency::Daily),
+            "weekly" => Ok(Frequency::Weekly),
+            "monthly" => Ok(Frequency::Monthly(first.day())),
+            "yearly" => Ok(Frequency::Yearly(first.month(), first.day())),
+            _ => Err(Box::from(format!(
+                "Frequency {name} invalid. Use daily, weekly, monthly or yearly"
+            ))),
+        }
+    }
+
+    /// The occurrence after `date`
+    fn next(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match *self {
+            Frequency::Daily => date.checked_add_days(Days::new(1)),
+            Frequency::Weekly => date.checked_add_days(Days::new(7)),
+            Frequency::Monthly(day) => {
+                let next_month = date.with_day(1)?.checked_add_months(Months::new(1))?;
+                clamp_day(next_month.year(), next_month.month(), day)
+            }
+            Frequency::Yearly(month, day) => clamp_day(date.year() + 1, month, day),
+        }
+    }
+}
+
+/// The given day of a month, or the last day of that month if it is too short
+fn clamp_day(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..=day)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Daily => write!(f, "daily"),
+            Frequency::Weekly => write!(f, "weekly"),
+            Frequency::Monthly(day) => write!(f, "monthly:{day}"),
+            Frequency::Yearly(month, day) => write!(f, "yearly:{month}-{day}"),
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = Box<dyn Error>;
+
+    /// Reads back the form written by `Display`, e.g. "monthly:15" or "yearly:2-29"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Box::from(format!("Frequency {} not valid", s));
+
+        let (name, day) = s.split_once(':').unwrap_or((s, ""));
+        let frequency = match name {
+            "daily" if day.is_empty() => Frequency::Daily,
+            "weekly" if day.is_empty() => Frequency::Weekly,
+            "monthly" => match day.parse() {
+                Ok(day) if (1..=31).contains(&day) => Frequency::Monthly(day),
+                _ => return Err(invalid()),
+            },
+            "yearly" => match day.split_once('-').map(|(m, d)| (m.parse(), d.parse())) {
+                Some((Ok(month), Ok(day)))
+                    if NaiveDate::from_ymd_opt(2000, month, day).is_some() =>
+                {
+                    Frequency::Yearly(month, day)
+                }
+                _ => return Err(invalid()),
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(frequency)
+    }
+}
+
+/// A transaction that is posted to an account automatically every time it comes due
+#[derive(Clone, PartialEq)]
+pub struct Recurring {
+    account: String,
+    label: String,
+    amount: Money,
+    category: Option<String>,
+    frequency: Frequency,
+    next_due: NaiveDate,
+}
+
+impl Recurring {
+    pub fn build(
+        account: &str,
+        label: &str,
+        amount: Money,
+        category: Option<String>,
+        frequency: Frequency,
+        next_due: NaiveDate,
+    ) -> Result<Recurring, Box<dyn Error>> {
+        if account.is_empty() {
+            return Err(Box::from("Recurring transaction must have an account"));
+        }
+        if label.is_empty() {
+            return Err(Box::from("No name provided for transaction"));
+        }
+        Ok(Recurring {
+            account: String::from(account),
+            label: String::from(label),
+            amount,
+            category,
+            frequency,
+            next_due,
+        })
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn amount(&self) -> &Money {
+        &self.amount
+    }
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+    pub fn frequency(&self) -> &Frequency {
+        &self.frequency
+    }
+    pub fn next_due(&self) -> &NaiveDate {
+        &self.next_due
+    }
+}
+
+impl fmt::Display for Recurring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Account: {} | Label: {} | Amount: ${} | Repeats: {} | Next: {}",
+            self.account,
+            self.label,
+            self.amount,
+            self.frequency,
+            self.next_due.format("%d %b %Y")
+        )?;
+        if let Some(category) = &self.category {
+            write!(f, " | Category: {}", category)?;
+        }
+        Ok(())
+    }
+}
+
+/// Posts every occurrence of every recurring transaction due on or before `today`,
+/// returning a line describing each one posted. Nothing is posted if any of them fail.
+pub fn post_due(
+    accounts: &mut HashMap<String, Account>,
+    recurring: &mut [Recurring],
+    today: NaiveDate,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    // posted into copies, which are only kept once every occurrence has been posted
+    let mut posted_accounts = accounts.clone();
+    let mut posted_recurring = recurring.to_vec();
+    let mut posted = Vec::new();
+
+    for template in posted_recurring.iter_mut() {
+        let account = match posted_accounts.get_mut(&template.account.to_lowercase()) {
+            Some(a) => a,
+            // left due so it posts once the account exists again
+            None if template.next_due <= today => {
+                posted.push(format!(
+                    "Skipped {}: account {} does not exist",
+                    template.label, template.account
+                ));
+                continue;
+            }
+            None => continue,
+        };
+
+        while template.next_due <= today {
+            let id =
+                account.add_transaction(&template.label, template.amount, template.next_due)?;
+            account.edit_transaction_category(id, template.category.clone())?;
+            posted.push(format!(
+                "Posted {} to {} on {} (${})",
+                template.label,
+                account.name(),
+                template.next_due.format("%d %b %Y"),
+                template.amount
+            ));
+
+            template.next_due = match template.frequency.next(template.next_due) {
+                Some(date) => date,
+                None => return Err(Box::from(format!("{} has no next date", template.label))),
+            };
+        }
+    }
+
+    *accounts = posted_accounts;
+    recurring.clone_from_slice(&posted_recurring);
+    Ok(posted)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::money::Money;
+    use crate::recurring::*;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn occurrences(frequency: Frequency, first: &str, count: usize) -> Vec<NaiveDate> {
+        let mut dates = vec![date(first)];
+        while dates.len() < count {
+            dates.push(frequency.next(*dates.last().unwrap()).unwrap());
+        }
+        dates
+    }
+
+    #[test]
+    fn monthly_clamps_to_month_end() {
+        let frequency = Frequency::build("Monthly", date("2024-01-31")).unwrap();
+        assert_eq!(
+            occurrences(frequency, "2024-01-31", 4),
+            vec![
+                date("2024-01-31"),
+                date("2024-02-29"),
+                date("2024-03-31"),
+                date("2024-04-30")
+            ]
+        );
+    }
+
+    #[test]
+    fn yearly_leap_day() {
+        let frequency = Frequency::build("yearly", date("2024-02-29")).unwrap();
+        assert_eq!(
+            occurrences(frequency, "2024-02-29", 5),
+            vec![
+                date("2024-02-29"),
+                date("2025-02-28"),
+                date("2026-02-28"),
+                date("2027-02-28"),
+                date("2028-02-29")
+            ]
+        );
+    }
+
+    #[test]
+    fn frequency_round_trip() {
+        for frequency in [
+            Frequency::Daily,
+            Frequency::Weekly,
+            Frequency::Monthly(31),
+            Frequency::Yearly(2, 29),
+        ] {
+            assert_eq!(
+                frequency.to_string().parse::<Frequency>().unwrap(),
+                frequency
+            );
+        }
+        for bad in [
+            "fortnightly",
+            "monthly:0",
+            "monthly:32",
+            "yearly:2-30",
+            "daily:1",
+        ] {
+            assert!(bad.parse::<Frequency>().is_err(), "{bad} should not parse");
+        }
+        assert!(Frequency::build("hourly", date("2024-01-01")).is_err());
+    }
+
+    #[test]
+    fn post_due_test() {
+        let mut accounts =
+            HashMap::from([(String::from("savings"), Account::build("Savings").unwrap())]);
+        let mut recurring = vec![
+            Recurring::build(
+                "Savings",
+                "Rent",
+                Money::new(-100000, 2),
+                Some(String::from("Housing")),
+                Frequency::Monthly(1),
+                date("2024-04-01"),
+            )
+            .unwrap(),
+            Recurring::build(
+                "Savings",
+                "Holiday",
+                Money::new(-5000, 2),
+                None,
+                Frequency::Yearly(12, 25),
+                date("2024-12-25"),
+            )
+            .unwrap(),
+            Recurring::build(
+                "Checking",
+                "Coffee",
+                Money::new(-450, 2),
+                None,
+                Frequency::Daily,
+                date("2024-05-01"),
+            )
+            .unwrap(),
+        ];
+
+        let posted = post_due(&mut accounts, &mut recurring, date("2024-06-15")).unwrap();
+
+        assert_eq!(
+            posted,
+            vec![
+                "Posted Rent to Savings on 01 Apr 2024 ($-1000.00)",
+                "Posted Rent to Savings on 01 May 2024 ($-1000.00)",
+                "Posted Rent to Savings on 01 Jun 2024 ($-1000.00)",
+                "Skipped Coffee: account Checking does not exist",
+            ]
+        );
+        assert_eq!(recurring[0].next_due(), &date("2024-07-01"));
+        assert_eq!(recurring[1].next_due(), &date("2024-12-25"));
+        assert_eq!(recurring[2].next_due(), &date("2024-05-01"));
+
+        let savings = accounts.get("savings").unwrap();
+        assert_eq!(savings.transactions().len(), 3);
+        assert_eq!(savings.balance(), &Money::new(-300000, 2));
+        assert!(savings
+            .transactions()
+            .values()
+            .all(|t| t.category() == Some("Housing")));
+
+        // nothing is posted if any occurrence cannot be
+        let mut overdrawn = accounts.clone();
+        overdrawn
+            .get_mut("savings")
+            .unwrap()
+            .add_transaction("Debt", Money::new(i64::MIN + 450000, 2), date("2024-06-01"))
+            .unwrap();
+        let mut templates = recurring.clone();
+        assert!(post_due(&mut overdrawn, &mut templates, date("2024-08-15")).is_err());
+        assert_eq!(overdrawn.get("savings").unwrap().transactions().len(), 4);
+        assert_eq!(templates[0].next_due(), &date("2024-07-01"));
+
+        // nothing new is due on a second run the same day
+        assert!(post_due(&mut accounts, &mut recurring, date("2024-06-15"))
+            .unwrap()
+            .iter()
+            .all(|line| line.starts_with("Skipped")));
+    }
+}
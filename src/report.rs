@@ -0,0 +1,145 @@
+use crate::account::Account;
+use crate::money::Money;
+use chrono::{Datelike, Months, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+pub const UNCATEGORISED: &str = "Uncategorised";
+
+/// Sums every transaction between `start` and `end` (inclusive) across all accounts,
+/// grouped by category. Transfers only move money between accounts, so they are left out.
+pub fn category_totals(
+    accounts: &HashMap<String, Account>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<BTreeMap<String, Money>, Box<dyn Error>> {
+    let mut totals: BTreeMap<String, Money> = BTreeMap::new();
+
+    for transaction in accounts
+        .values()
+        .flat_map(|account| account.transactions().values())
+        .filter(|t| t.transfer().is_none() && (start..=end).contains(t.date()))
+    {
+        let category = transaction.category().unwrap_or(UNCATEGORISED);
+        let total = totals.entry(String::from(category)).or_default();
+        *total = total.checked_add(*transaction.amount())?;
+    }
+
+    Ok(totals)
+}
+
+pub fn format_category_totals(
+    totals: &BTreeMap<String, Money>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<String, Box<dyn Error>> {
+    let mut lines = vec![format!(
+        "Category totals {} - {}:",
+        start.format("%d %b %Y"),
+        end.format("%d %b %Y")
+    )];
+
+    let mut sum = Money::zero();
+    for (category, total) in totals {
+        lines.push(format!("\t{category}: ${total}"));
+        sum = sum.checked_add(*total)?;
+    }
+    lines.push(format!("\tTotal: ${sum}"));
+
+    Ok(lines.join("\n"))
+}
+
+/// The first and last day of the month `date` falls in
+pub fn month_bounds(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    // unwraps safe as every month has a first day, and dates are nowhere near the end of time
+    let start = date.with_day(1).unwrap();
+    let end = start
+        .checked_add_months(Months::new(1))
+        .unwrap()
+        .pred_opt()
+        .unwrap();
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::money::Money;
+    use crate::report::*;
+    use crate::transfer::transfer;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn form_accounts() -> HashMap<String, Account> {
+        let mut accounts = HashMap::from([
+            (String::from("savings"), Account::build("Savings").unwrap()),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ]);
+
+        for (key, label, amount, day, category) in [
+            ("savings", "Shop", -2000, "2024-05-01", Some("Groceries")),
+            ("savings", "Market", -550, "2024-05-31", Some("Groceries")),
+            ("savings", "Pay", 100000, "2024-05-15", None),
+            ("checking", "Shop", -1000, "2024-05-10", Some("Groceries")),
+            ("checking", "Cinema", -1500, "2024-05-11", Some("Fun")),
+            ("checking", "Shop", -9900, "2024-06-01", Some("Groceries")),
+        ] {
+            let account = accounts.get_mut(key).unwrap();
+            let id = account
+                .add_transaction(label, Money::new(amount, 2), date(day))
+                .unwrap();
+            account
+                .edit_transaction_category(id, category.map(String::from))
+                .unwrap();
+        }
+
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(5000, 2),
+            date("2024-05-20"),
+        )
+        .unwrap();
+        accounts
+    }
+
+    #[test]
+    fn totals_in_range() {
+        let (start, end) = month_bounds(date("2024-05-17"));
+        let totals = category_totals(&form_accounts(), start, end).unwrap();
+
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals.get("Groceries").unwrap(), &Money::new(-3550, 2));
+        assert_eq!(totals.get("Fun").unwrap(), &Money::new(-1500, 2));
+        assert_eq!(totals.get(UNCATEGORISED).unwrap(), &Money::new(100000, 2));
+
+        assert_eq!(
+            format_category_totals(&totals, start, end).unwrap(),
+            "Category totals 01 May 2024 - 31 May 2024:\n\
+            \tFun: $-15.00\n\
+            \tGroceries: $-35.50\n\
+            \tUncategorised: $1000.00\n\
+            \tTotal: $949.50"
+        );
+    }
+
+    #[test]
+    fn month_bounds_test() {
+        assert_eq!(
+            month_bounds(date("2024-02-10")),
+            (date("2024-02-01"), date("2024-02-29"))
+        );
+        assert_eq!(
+            month_bounds(date("2024-12-31")),
+            (date("2024-12-01"), date("2024-12-31"))
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::account::{Account, Transaction};
+use crate::money::Money;
+use crate::report::UNCATEGORISED;
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use std::error::Error;
+
+/// What a transaction has to match to be found. Anything not given matches every transaction.
+pub struct Filter {
+    label: Option<Regex>,
+    min: Option<Money>,
+    max: Option<Money>,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    category: Option<String>,
+}
+
+impl Filter {
+    /// Builds a filter from the label, least and greatest amount, first and last date and
+    /// category, in that order, any of which can be empty or left out. The label is found
+    /// anywhere in a transaction's label, in any case, or is a regular expression between
+    /// slashes such as "/^iced/".
+    pub fn build(options: &[String]) -> Result<Filter, Box<dyn Error>> {
+        let given = |index: usize| options.get(index).filter(|option| !option.is_empty());
+
+        let label = match given(0) {
+            Some(label) => {
+                let pattern = match label.strip_prefix('/').and_then(|l| l.strip_suffix('/')) {
+                    Some(pattern) => String::from(pattern),
+                    None => regex::escape(label),
+                };
+                match RegexBuilder::new(&pattern).case_insensitive(true).build() {
+                    Ok(regex) => Some(regex),
+                    Err(e) => return Err(Box::from(format!("Label pattern {label} invalid: {e}"))),
+                }
+            }
+            None => None,
+        };
+        let amount = |index: usize| -> Result<Option<Money>, Box<dyn Error>> {
+            match given(index).map(|amount| amount.parse::<Money>()) {
+                Some(Ok(amount)) => Ok(Some(amount)),
+                Some(Err(e)) => Err(Box::from(format!("Amount entered invalid: {e}"))),
+                None => Ok(None),
+            }
+        };
+        let date = |index: usize| -> Result<Option<NaiveDate>, Box<dyn Error>> {
+            match given(index).map(|date| date.parse::<NaiveDate>()) {
+                Some(Ok(date)) => Ok(Some(date)),
+                Some(Err(e)) => Err(Box::from(format!("Date entered invalid: {e}"))),
+                None => Ok(None),
+            }
+        };
+
+        let filter = Filter {
+            label,
+            min: amount(1)?,
+            max: amount(2)?,
+            start: date(3)?,
+            end: date(4)?,
+            category: given(5).map(|category| category.to_lowercase()),
+        };
+        if let (Some(min), Some(max)) = (filter.min, filter.max) {
+            if min > max {
+                return Err(Box::from(format!("Least amount {min} is more than {max}")));
+            }
+        }
+        if let (Some(start), Some(end)) = (filter.start, filter.end) {
+            if start > end {
+                return Err(Box::from(format!(
+                    "Start date {start} is after end date {end}"
+                )));
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether the transaction meets every part of the filter. As in category reports,
+    /// transfers have no category to be found under, and other transactions with no
+    /// category are found under "Uncategorised".
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        let category = transaction.category().unwrap_or(UNCATEGORISED);
+        self.label
+            .as_ref()
+            .is_none_or(|label| label.is_match(transaction.label()))
+            && self.min.is_none_or(|min| transaction.amount() >= &min)
+            && self.max.is_none_or(|max| transaction.amount() <= &max)
+            && self.start.is_none_or(|start| transaction.date() >= &start)
+            && self.end.is_none_or(|end| transaction.date() <= &end)
+            && self.category.as_ref().is_none_or(|wanted| {
+                transaction.transfer().is_none() && category.to_lowercase() == *wanted
+            })
+    }
+}
+
+/// Every transaction in the accounts that matches the filter, oldest first, with its account
+pub fn find<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    filter: &Filter,
+) -> Vec<(&'a Account, &'a Transaction)> {
+    let mut found: Vec<(&Account, &Transaction)> = accounts
+        .into_iter()
+        .flat_map(|account| {
+            account
+                .transactions()
+                .values()
+                .map(move |transaction| (account, transaction))
+        })
+        .filter(|(_, transaction)| filter.matches(transaction))
+        .collect();
+    found.sort_by(|(a, t), (b, u)| (t.date(), a.name(), t.id()).cmp(&(u.date(), b.name(), u.id())));
+    found
+}
+
+/// The transactions found as a table, each with the account and ID edit commands take
+pub fn format_matches(found: &[(&Account, &Transaction)]) -> String {
+    if found.is_empty() {
+        return String::from("No transactions found");
+    }
+
+    let header = ["Account", "ID", "Date", "Label", "Amount", "Category"].map(String::from);
+    let rows: Vec<[String; 6]> = found
+        .iter()
+        .map(|(account, transaction)| {
+            [
+                String::from(account.name()),
+                format!("#{}", transaction.id()),
+                transaction.date().format("%d %b %Y").to_string(),
+                String::from(transaction.label()),
+                format!("${}", transaction.amount()),
+                String::from(transaction.category().unwrap_or("")),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.clone().map(|heading| heading.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut lines: Vec<String> = std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let [account, id, date, label, amount, category] = row;
+            format!(
+                "{account:<0$}  {id:<1$}  {date:<2$}  {label:<3$}  {amount:>4$}  {category}",
+                widths[0], widths[1], widths[2], widths[3], widths[4]
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect();
+    lines.push(match rows.len() {
+        1 => String::from("1 transaction found"),
+        count => format!("{count} transactions found"),
+    });
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::{Account, TransferLink};
+    use crate::money::Money;
+    use crate::search::{find, format_matches, Filter};
+
+    fn options(options: &[&str]) -> Vec<String> {
+        options.iter().map(|option| String::from(*option)).collect()
+    }
+
+    fn form_accounts() -> Vec<Account> {
+        let mut accounts = vec![
+            Account::build("Savings").unwrap(),
+            Account::build("Checking").unwrap(),
+        ];
+        for (index, label, amount, day, category) in [
+            (0, "Iced Coffee", -450, "2024-05-02", Some("Food")),
+            (0, "Pay", 100000, "2024-05-15", None),
+            (1, "Coffee Beans", -1800, "2024-05-10", Some("Food")),
+            (1, "Cinema", -1500, "2024-06-11", Some("Fun")),
+        ] {
+            let account = &mut accounts[index];
+            let id = account
+                .add_transaction(label, Money::new(amount, 2), day.parse().unwrap())
+                .unwrap();
+            account
+                .edit_transaction_category(id, category.map(String::from))
+                .unwrap();
+        }
+        accounts
+    }
+
+    fn found(accounts: &[Account], given: &[&str]) -> Vec<String> {
+        find(accounts, &Filter::build(&options(given)).unwrap())
+            .iter()
+            .map(|(account, t)| format!("{} #{}", account.name(), t.id()))
+            .collect()
+    }
+
+    #[test]
+    fn find_test() {
+        let accounts = form_accounts();
+
+        assert_eq!(found(&accounts, &[]).len(), 4);
+        assert_eq!(found(&accounts, &["coffee"]), ["Savings #1", "Checking #1"]);
+        assert_eq!(found(&accounts, &["/^c/"]), ["Checking #1", "Checking #2"]);
+        assert_eq!(
+            found(&accounts, &["", "-20", "-5"]),
+            ["Checking #1", "Checking #2"]
+        );
+        assert_eq!(
+            found(&accounts, &["", "", "", "2024-05-10", "2024-06-30"]),
+            ["Checking #1", "Savings #2", "Checking #2"]
+        );
+        assert_eq!(
+            found(&accounts, &["", "", "", "", "", "uncategorised"]),
+            ["Savings #2"]
+        );
+        assert_eq!(
+            found(&accounts, &["coffee", "", "", "", "", "FOOD"]).len(),
+            2
+        );
+        assert!(found(&accounts[..1], &["cinema"]).is_empty());
+    }
+
+    #[test]
+    fn transfers_have_no_category() {
+        let mut accounts = form_accounts();
+        let savings = &mut accounts[0];
+        let id = savings
+            .add_transaction(
+                "Transfer",
+                Money::new(-5000, 2),
+                "2024-05-20".parse().unwrap(),
+            )
+            .unwrap();
+        savings
+            .link_transaction(id, Some(TransferLink::new("Checking", 3)))
+            .unwrap();
+
+        assert_eq!(found(&accounts, &["transfer"]), ["Savings #3"]);
+        assert_eq!(
+            found(&accounts, &["", "", "", "", "", "uncategorised"]),
+            ["Savings #2"]
+        );
+    }
+
+    #[test]
+    fn find_invalid() {
+        for given in [
+            &["/(/"][..],
+            &["", "lots"],
+            &["", "-5", "-20"],
+            &["", "", "", "2024-05-31", "2024-05-01"],
+            &["", "", "", "May"],
+        ] {
+            assert!(Filter::build(&options(given)).is_err());
+        }
+    }
+
+    #[test]
+    fn format_matches_test() {
+        let accounts = form_accounts();
+        let filter = Filter::build(&options(&["coffee"])).unwrap();
+
+        assert_eq!(
+            format_matches(&find(&accounts, &filter)),
+            "Account   ID  Date         Label          Amount  Category\n\
+            Savings   #1  02 May 2024  Iced Coffee    $-4.50  Food\n\
+            Checking  #1  10 May 2024  Coffee Beans  $-18.00  Food\n\
+            2 transactions found"
+        );
+        assert_eq!(format_matches(&[]), "No transactions found");
+    }
+}
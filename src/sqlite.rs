@@ -0,0 +1,667 @@
+use crate::account::{Account, TransferLink};
+use crate::file_processing::FORMAT_VERSION;
+use crate::ledger::Ledger;
+use crate::recurring::Recurring;
+use crate::storage::{self, Storage};
+use chrono::NaiveDate;
+use rusqlite::backup::Progress;
+use rusqlite::{params, Connection, DatabaseName, Row};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Amounts are kept as decimal text such as "-12.50" so they never pass through floating
+/// point, and dates as "YYYY-MM-DD" so they sort and compare as text. An account's next_id is
+/// the ID its next transaction will get.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        key TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        next_id INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS transactions (
+        account TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        date TEXT NOT NULL,
+        label TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        category TEXT,
+        transfer_account TEXT,
+        transfer_id INTEGER,
+        import_id TEXT,
+        PRIMARY KEY (account, id)
+    );
+    CREATE INDEX IF NOT EXISTS transactions_by_date ON transactions (date);
+    CREATE INDEX IF NOT EXISTS transactions_by_account_date ON transactions (account, date);
+    CREATE TABLE IF NOT EXISTS budgets (
+        category TEXT PRIMARY KEY,
+        monthly_limit TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS recurring (
+        position INTEGER PRIMARY KEY,
+        account TEXT NOT NULL,
+        label TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        category TEXT,
+        frequency TEXT NOT NULL,
+        next_due TEXT NOT NULL
+    );";
+
+const TRANSACTION_COLUMNS: &str =
+    "account, id, date, label, amount, category, transfer_account, transfer_id, import_id";
+
+/// One row of the transactions table, as last loaded or saved
+#[derive(Clone, PartialEq)]
+struct TransactionRow {
+    date: String,
+    label: String,
+    amount: String,
+    category: Option<String>,
+    transfer_account: Option<String>,
+    transfer_id: Option<u32>,
+    import_id: Option<String>,
+}
+
+/// Transactions by account key and ID
+type TransactionRows = HashMap<(String, u32), TransactionRow>;
+
+/// Account names and the IDs their next transactions will get, by account key
+type AccountRows = HashMap<String, (String, u32)>;
+
+/// An account's key, name and next ID, as read back
+type StoredAccount = (String, String, Option<u32>);
+
+/// A SQLite database. Saving only writes the accounts and transactions that changed since
+/// the last load or save, so a ledger should be loaded before one is saved over it, and
+/// date range queries read just the rows they need.
+pub struct SqliteStorage {
+    connection: Connection,
+    file_path: String,
+    /// Accounts as stored
+    saved_accounts: AccountRows,
+    /// Transactions as stored
+    saved_transactions: TransactionRows,
+    /// SQLite's count of changes made by other connections, as of the last load or save
+    data_version: Option<i64>,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `file_path`, creating it and its tables if needed
+    pub fn open(file_path: &str) -> Result<SqliteStorage, Box<dyn Error>> {
+        match Connection::open(file_path) {
+            Ok(connection) => Self::build(connection, file_path),
+            Err(e) => Err(Box::from(format!("Could not open {file_path}: {e}"))),
+        }
+    }
+
+    fn build(connection: Connection, file_path: &str) -> Result<SqliteStorage, Box<dyn Error>> {
+        let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > FORMAT_VERSION {
+            return Err(Box::from(format!(
+                "File is format version {version}, which is newer than this program supports"
+            )));
+        }
+        connection.execute_batch(SCHEMA)?;
+        // databases made before the next ID was kept have no column for it
+        let has_next_id: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('accounts') WHERE name = 'next_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_next_id {
+            connection.execute("ALTER TABLE accounts ADD COLUMN next_id INTEGER", [])?;
+        }
+        connection.pragma_update(None, "user_version", FORMAT_VERSION)?;
+
+        Ok(SqliteStorage {
+            connection,
+            file_path: String::from(file_path),
+            saved_accounts: HashMap::new(),
+            saved_transactions: HashMap::new(),
+            data_version: None,
+        })
+    }
+
+    fn query_data_version(&self) -> Result<i64, Box<dyn Error>> {
+        Ok(self
+            .connection
+            .pragma_query_value(None, "data_version", |row| row.get(0))?)
+    }
+
+    /// Each account's key, name and next ID, with no next ID for those saved before it was kept
+    fn query_accounts(&self) -> Result<Vec<StoredAccount>, Box<dyn Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT key, name, next_id FROM accounts")?;
+        let accounts = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<Vec<StoredAccount>, _>>()?;
+        Ok(accounts)
+    }
+
+    /// Reads accounts and the transactions picked out by `condition`, which may use `?1` and
+    /// `?2` for `bounds`
+    fn query_ledger_accounts(
+        &self,
+        condition: &str,
+        bounds: &[String],
+    ) -> Result<(HashMap<String, Account>, TransactionRows), Box<dyn Error>> {
+        let mut accounts = HashMap::new();
+        for (key, name, next_id) in self.query_accounts()? {
+            let mut account = Account::build(&name)?;
+            if let Some(next_id) = next_id {
+                account.set_next_id(next_id);
+            }
+            accounts.insert(key, account);
+        }
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {TRANSACTION_COLUMNS} FROM transactions {condition} ORDER BY account, id"
+        ))?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(bounds), |row| {
+                Ok((row.get(0)?, row.get(1)?, transaction_row(row)?))
+            })?
+            .collect::<Result<Vec<(String, u32, TransactionRow)>, _>>()?;
+
+        for (key, id, row) in &rows {
+            let account = match accounts.get_mut(key) {
+                Some(account) => account,
+                None => {
+                    return Err(Box::from(format!(
+                        "Transaction #{id} belongs to account {key}, which is not stored"
+                    )))
+                }
+            };
+            add_row(account, *id, row).map_err(|e| format!("{} #{id}: {e}", account.name()))?;
+        }
+
+        let rows = rows
+            .into_iter()
+            .map(|(key, id, row)| ((key, id), row))
+            .collect();
+        Ok((accounts, rows))
+    }
+
+    fn save_accounts(
+        &self,
+        transaction: &rusqlite::Transaction,
+        ledger: &Ledger,
+    ) -> Result<(), Box<dyn Error>> {
+        for key in self.saved_accounts.keys() {
+            if !ledger.accounts.contains_key(key) {
+                transaction.execute("DELETE FROM transactions WHERE account = ?1", [key])?;
+                transaction.execute("DELETE FROM accounts WHERE key = ?1", [key])?;
+            }
+        }
+        for (key, row) in account_rows(ledger) {
+            if self.saved_accounts.get(&key) != Some(&row) {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO accounts (key, name, next_id) VALUES (?1, ?2, ?3)",
+                    params![key, row.0, row.1],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save_transactions(
+        &self,
+        transaction: &rusqlite::Transaction,
+        rows: &TransactionRows,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut delete =
+            transaction.prepare("DELETE FROM transactions WHERE account = ?1 AND id = ?2")?;
+        for (key, id) in self.saved_transactions.keys() {
+            if !rows.contains_key(&(key.clone(), *id)) {
+                delete.execute(params![key, id])?;
+            }
+        }
+
+        let mut upsert = transaction.prepare(&format!(
+            "INSERT OR REPLACE INTO transactions ({TRANSACTION_COLUMNS}) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+        ))?;
+        for ((key, id), row) in rows {
+            if self.saved_transactions.get(&(key.clone(), *id)) != Some(row) {
+                upsert.execute(params![
+                    key,
+                    id,
+                    row.date,
+                    row.label,
+                    row.amount,
+                    row.category,
+                    row.transfer_account,
+                    row.transfer_id,
+                    row.import_id
+                ])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<Ledger, Box<dyn Error>> {
+        let (accounts, rows) = self.query_ledger_accounts("", &[])?;
+        let mut ledger = Ledger::from(accounts);
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT category, monthly_limit FROM budgets")?;
+        let budgets = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(String, String)>, _>>()?;
+        for (category, limit) in budgets {
+            ledger.budgets.set_limit(&category, limit.parse()?)?;
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT account, label, amount, category, frequency, next_due \
+            FROM recurring ORDER BY position",
+        )?;
+        let recurring = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (account, label, amount, category, frequency, next_due) in recurring {
+            ledger.recurring.push(Recurring::build(
+                &account,
+                &label,
+                amount.parse()?,
+                category,
+                frequency.parse()?,
+                parse_date(&next_due)?,
+            )?);
+        }
+
+        self.saved_accounts = account_rows(&ledger);
+        self.saved_transactions = rows;
+        self.data_version = Some(self.query_data_version()?);
+        Ok(ledger)
+    }
+
+    fn save(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        let rows: TransactionRows = ledger
+            .accounts
+            .iter()
+            .flat_map(|(key, account)| {
+                account.transactions().values().map(|t| {
+                    let row = TransactionRow {
+                        date: t.date().to_string(),
+                        label: String::from(t.label()),
+                        amount: t.amount().to_string(),
+                        category: t.category().map(String::from),
+                        transfer_account: t.transfer().map(|link| String::from(link.account())),
+                        transfer_id: t.transfer().map(TransferLink::id),
+                        import_id: t.import_id().map(String::from),
+                    };
+                    ((key.clone(), t.id()), row)
+                })
+            })
+            .collect();
+
+        let transaction = self.connection.unchecked_transaction()?;
+        self.save_accounts(&transaction, ledger)?;
+        self.save_transactions(&transaction, &rows)?;
+
+        // budgets and recurring transactions are few, so are written whole
+        transaction.execute("DELETE FROM budgets", [])?;
+        for (category, limit) in ledger.budgets.limits() {
+            transaction.execute(
+                "INSERT INTO budgets (category, monthly_limit) VALUES (?1, ?2)",
+                params![category, limit.to_string()],
+            )?;
+        }
+        transaction.execute("DELETE FROM recurring", [])?;
+        for (position, template) in ledger.recurring.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO recurring (position, account, label, amount, category, frequency, next_due) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    position,
+                    template.account(),
+                    template.label(),
+                    template.amount().to_string(),
+                    template.category(),
+                    template.frequency().to_string(),
+                    template.next_due().to_string()
+                ],
+            )?;
+        }
+
+        if let Err(e) = transaction.commit() {
+            return Err(Box::from(format!("Failed saving changes:\n{e}")));
+        }
+        self.saved_accounts = account_rows(ledger);
+        self.saved_transactions = rows;
+        self.data_version = Some(self.query_data_version()?);
+        Ok(())
+    }
+
+    fn changed(&mut self) -> Result<bool, Box<dyn Error>> {
+        match self.data_version {
+            Some(version) => Ok(version != self.query_data_version()?),
+            None => Ok(false),
+        }
+    }
+
+    fn backup(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let backup_path = storage::new_backup_path(&self.file_path);
+        // unlike copying the file, this takes in any changes still in the journal
+        if let Err(e) = self.connection.execute("VACUUM INTO ?1", [&backup_path]) {
+            return Err(Box::from(format!(
+                "Failed backing up {} to {backup_path}: {e}",
+                self.file_path
+            )));
+        }
+        storage::remove_old_backups(&self.file_path)?;
+        Ok(Some(backup_path))
+    }
+
+    fn backups(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        storage::list_backups(&self.file_path)
+    }
+
+    fn restore(&mut self, backup: &str) -> Result<(), Box<dyn Error>> {
+        // opening a database that does not exist would create an empty one
+        if !Path::new(backup).is_file() {
+            return Err(Box::from(format!("Backup {backup} does not exist")));
+        }
+        if let Err(e) = self
+            .connection
+            .restore(DatabaseName::Main, backup, None::<fn(Progress)>)
+        {
+            return Err(Box::from(format!("Failed restoring {backup}: {e}")));
+        }
+        // nothing loaded before now matches what is stored
+        self.saved_accounts.clear();
+        self.saved_transactions.clear();
+        Ok(())
+    }
+
+    fn accounts_between(
+        &mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<String, Account>, Box<dyn Error>> {
+        let (accounts, _) = self.query_ledger_accounts(
+            "WHERE date BETWEEN ?1 AND ?2",
+            &[start.to_string(), end.to_string()],
+        )?;
+        Ok(accounts)
+    }
+}
+
+fn account_rows(ledger: &Ledger) -> AccountRows {
+    ledger
+        .accounts
+        .iter()
+        .map(|(key, account)| {
+            let row = (String::from(account.name()), account.next_id());
+            (key.clone(), row)
+        })
+        .collect()
+}
+
+fn transaction_row(row: &Row) -> rusqlite::Result<TransactionRow> {
+    Ok(TransactionRow {
+        date: row.get(2)?,
+        label: row.get(3)?,
+        amount: row.get(4)?,
+        category: row.get(5)?,
+        transfer_account: row.get(6)?,
+        transfer_id: row.get(7)?,
+        import_id: row.get(8)?,
+    })
+}
+
+fn add_row(account: &mut Account, id: u32, row: &TransactionRow) -> Result<(), Box<dyn Error>> {
+    account.insert_transaction(id, &row.label, row.amount.parse()?, parse_date(&row.date)?)?;
+    account.edit_transaction_category(id, row.category.clone())?;
+    account.set_transaction_import_id(id, row.import_id.clone())?;
+    let transfer = match (&row.transfer_account, row.transfer_id) {
+        (None, None) => None,
+        (Some(other_account), Some(other_id)) => Some(TransferLink::new(other_account, other_id)),
+        _ => return Err(Box::from("Transfer is missing its account or ID")),
+    };
+    account.link_transaction(id, transfer)
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    match date.parse() {
+        Ok(date) => Ok(date),
+        Err(e) => Err(Box::from(format!("Date {date} not valid: {e}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::recurring::{Frequency, Recurring};
+    use crate::sqlite::*;
+    use crate::transfer::transfer;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn in_memory() -> SqliteStorage {
+        SqliteStorage::build(Connection::open_in_memory().unwrap(), ":memory:").unwrap()
+    }
+
+    fn form_ledger() -> Ledger {
+        let mut savings = Account::build("Savings").unwrap();
+        savings
+            .add_transaction("Dinner, drinks", Money::new(-4200, 2), date("2024-05-25"))
+            .unwrap();
+        savings
+            .add_transaction("Pay", Money::new(100000, 2), date("2024-06-01"))
+            .unwrap();
+        savings
+            .edit_transaction_category(1, Some(String::from("Eating Out")))
+            .unwrap();
+        savings
+            .set_transaction_import_id(2, Some(String::from("A1")))
+            .unwrap();
+
+        let mut accounts = HashMap::from([
+            (String::from("savings"), savings),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ]);
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(150, 2),
+            date("2024-05-27"),
+        )
+        .unwrap();
+
+        let mut ledger = Ledger::from(accounts);
+        ledger
+            .budgets
+            .set_limit("Eating Out", Money::new(10000, 2))
+            .unwrap();
+        ledger.recurring.push(
+            Recurring::build(
+                "Savings",
+                "Rent",
+                Money::new(-100000, 2),
+                None,
+                Frequency::Monthly(31),
+                date("2024-05-31"),
+            )
+            .unwrap(),
+        );
+        ledger
+    }
+
+    fn describe(ledger: &Ledger) -> String {
+        let mut lines: Vec<String> = ledger
+            .accounts
+            .values()
+            .map(|account| format!("{account}"))
+            .collect();
+        lines.sort();
+        lines.extend(ledger.recurring.iter().map(|r| format!("{r}")));
+        lines.extend(
+            ledger
+                .budgets
+                .limits()
+                .iter()
+                .map(|(category, limit)| format!("{category} {limit}")),
+        );
+        lines.join("\n")
+    }
+
+    #[test]
+    fn round_trip() {
+        let ledger = form_ledger();
+        let mut storage = in_memory();
+        storage.save(&ledger).unwrap();
+
+        let loaded = storage.load().unwrap();
+        assert_eq!(describe(&loaded), describe(&ledger));
+        let transfer = &loaded.accounts.get("checking").unwrap().transactions()[&1];
+        assert_eq!(transfer.transfer().unwrap().account(), "Savings");
+    }
+
+    #[test]
+    fn incremental_save() {
+        let mut storage = in_memory();
+        storage.save(&form_ledger()).unwrap();
+
+        let mut ledger = storage.load().unwrap();
+        let savings = ledger.accounts.get_mut("savings").unwrap();
+        savings
+            .edit_transaction_label(1, String::from("Lunch"))
+            .unwrap();
+        savings.remove_transaction(2).unwrap();
+        ledger.accounts.remove("checking");
+        ledger
+            .accounts
+            .insert(String::from("cash"), Account::build("Cash").unwrap());
+        storage.save(&ledger).unwrap();
+
+        let loaded = storage.load().unwrap();
+        assert_eq!(describe(&loaded), describe(&ledger));
+        let count: u32 = storage
+            .connection
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        // the removed transaction's ID is not given out again
+        assert_eq!(loaded.accounts["savings"].next_id(), 4);
+    }
+
+    #[test]
+    fn without_next_id() {
+        // as made before the next ID was kept
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE accounts (key TEXT PRIMARY KEY, name TEXT NOT NULL);
+                INSERT INTO accounts (key, name) VALUES ('cash', 'Cash');",
+            )
+            .unwrap();
+
+        let mut storage = SqliteStorage::build(connection, ":memory:").unwrap();
+        let mut ledger = storage.load().unwrap();
+        assert_eq!(ledger.accounts["cash"].next_id(), 1);
+
+        ledger.accounts.get_mut("cash").unwrap().set_next_id(7);
+        storage.save(&ledger).unwrap();
+        assert_eq!(storage.load().unwrap().accounts["cash"].next_id(), 7);
+    }
+
+    #[test]
+    fn accounts_between() {
+        let mut storage = in_memory();
+        storage.save(&form_ledger()).unwrap();
+
+        let accounts = storage
+            .accounts_between(date("2024-05-26"), date("2024-05-31"))
+            .unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        let savings = accounts.get("savings").unwrap();
+        assert_eq!(savings.transactions().len(), 1);
+        assert_eq!(savings.balance(), &Money::new(-150, 2));
+        assert_eq!(
+            accounts.get("checking").unwrap().balance(),
+            &Money::new(150, 2)
+        );
+    }
+
+    #[test]
+    fn file_test() {
+        let file_path = "src/test-files/sqlite-test.db";
+        let _ = std::fs::remove_file(file_path);
+        for backup in storage::list_backups(file_path).unwrap() {
+            std::fs::remove_file(backup).unwrap();
+        }
+
+        let mut storage = SqliteStorage::open(file_path).unwrap();
+        storage.save(&form_ledger()).unwrap();
+        let backup = storage.backup().unwrap().unwrap();
+        assert_eq!(storage.backups().unwrap(), vec![backup.clone()]);
+
+        storage.save(&Ledger::default()).unwrap();
+        storage.restore(&backup).unwrap();
+        assert_eq!(describe(&storage.load().unwrap()), describe(&form_ledger()));
+        assert!(storage.restore("src/test-files/missing.db").is_err());
+
+        assert!(!storage.changed().unwrap());
+        SqliteStorage::open(file_path)
+            .unwrap()
+            .save(&Ledger::default())
+            .unwrap();
+        assert!(storage.changed().unwrap());
+        drop(storage);
+
+        let loaded = SqliteStorage::open(&backup).unwrap().load().unwrap();
+        assert_eq!(describe(&loaded), describe(&form_ledger()));
+        for path in [file_path, &backup] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn newer_version() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", FORMAT_VERSION + 1)
+            .unwrap();
+        let _ = SqliteStorage::build(connection, ":memory:").unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn transaction_without_account() {
+        let mut storage = in_memory();
+        storage
+            .connection
+            .execute(
+                "INSERT INTO transactions (account, id, date, label, amount) \
+                VALUES ('missing', 1, '2024-05-25', 'a', '1.00')",
+                [],
+            )
+            .unwrap();
+        let _ = storage.load().unwrap();
+    }
+}
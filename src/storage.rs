@@ -0,0 +1,549 @@
+use crate::account::Account;
+use crate::encryption::{self, Cipher};
+use crate::file_processing::{self, FileFormat};
+use crate::ledger::Ledger;
+use crate::sqlite::SqliteStorage;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+/// How many backups are kept beside a data file before the oldest are removed
+pub(super) const BACKUPS_KEPT: usize = 5;
+/// How the time a backup was made is written in its name
+const BACKUP_TIME_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.6f";
+
+/// Where a ledger is kept between runs
+pub trait Storage {
+    /// Reads the whole ledger
+    fn load(&mut self) -> Result<Ledger, Box<dyn Error>>;
+
+    /// Writes the ledger back, replacing what was stored
+    fn save(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>>;
+
+    /// Keeps a copy of what is stored, returning where it was put, or `None` if nothing has
+    /// been stored yet. Only the newest `BACKUPS_KEPT` copies are kept.
+    fn backup(&mut self) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Every copy kept by `backup`, oldest first
+    fn backups(&self) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Replaces what is stored with one of the copies listed by `backups`
+    fn restore(&mut self, backup: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Whether what is stored has been changed by something else since it was last loaded
+    /// or saved here
+    fn changed(&mut self) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    /// Brings stored data written by an older version up to date, returning a message
+    /// describing what was done if anything was
+    fn upgrade(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Every account, keyed by lowercase name, holding only its transactions dated between
+    /// `start` and `end` (inclusive). Balances are those of the transactions returned.
+    fn accounts_between(
+        &mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<String, Account>, Box<dyn Error>> {
+        accounts_between(self.load()?, start, end)
+    }
+
+    /// Whether what is stored is encrypted
+    fn encrypted(&self) -> bool {
+        false
+    }
+
+    /// Encrypts what is stored with a new passphrase, in place of any it had before, along
+    /// with any unencrypted backups or copies of it, returning where those are. Backups
+    /// already encrypted keep the passphrase they were made with.
+    fn change_passphrase(&mut self, _passphrase: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        Err(Box::from("Only CSV, JSON and TOML files can be encrypted"))
+    }
+}
+
+/// Opens the storage for a data file in the given format, decrypting and encrypting it
+/// with `cipher` if there is one
+pub(super) fn open(
+    file_path: &str,
+    format: FileFormat,
+    cipher: Option<Cipher>,
+) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+    match (format, cipher) {
+        (FileFormat::Sqlite, None) => Ok(Box::new(SqliteStorage::open(file_path)?)),
+        (FileFormat::Sqlite, Some(_)) => {
+            Err(Box::from("Only CSV, JSON and TOML files can be encrypted"))
+        }
+        (_, cipher) => Ok(Box::new(FileStorage::new(file_path, format, cipher))),
+    }
+}
+
+/// A CSV, JSON or TOML file, read and written whole
+pub struct FileStorage {
+    file_path: String,
+    format: FileFormat,
+    /// Hash of the contents as last loaded or saved
+    stored: Option<u64>,
+    /// Set if the file is encrypted
+    cipher: Option<Cipher>,
+}
+
+impl FileStorage {
+    pub(super) fn new(file_path: &str, format: FileFormat, cipher: Option<Cipher>) -> FileStorage {
+        FileStorage {
+            file_path: String::from(file_path),
+            format,
+            stored: None,
+            cipher,
+        }
+    }
+
+    /// Hash of what is in the file now, treating a missing file as an empty one
+    fn hash_contents(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        fs::read(&self.file_path)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&mut self) -> Result<Ledger, Box<dyn Error>> {
+        let contents = file_processing::get_file_contents(&self.file_path, self.cipher.as_ref())?;
+        let ledger = file_processing::read(self.format, contents)?;
+        self.stored = Some(self.hash_contents());
+        Ok(ledger)
+    }
+
+    fn save(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        file_processing::write_to_file(&self.file_path, self.format, ledger, self.cipher.as_ref())?;
+        self.stored = Some(self.hash_contents());
+        Ok(())
+    }
+
+    fn changed(&mut self) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .stored
+            .is_some_and(|stored| stored != self.hash_contents()))
+    }
+
+    fn accounts_between(
+        &mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<String, Account>, Box<dyn Error>> {
+        // read without counting as a load, so changes made elsewhere are still noticed
+        let contents = file_processing::get_file_contents(&self.file_path, self.cipher.as_ref())?;
+        accounts_between(file_processing::read(self.format, contents)?, start, end)
+    }
+
+    fn backup(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if fs::metadata(&self.file_path).map_or(true, |metadata| metadata.len() == 0) {
+            return Ok(None);
+        }
+        let backup_path = new_backup_path(&self.file_path);
+        if let Err(e) = fs::copy(&self.file_path, &backup_path) {
+            return Err(Box::from(format!(
+                "Failed backing up {} to {backup_path}: {e}",
+                self.file_path
+            )));
+        }
+        remove_old_backups(&self.file_path)?;
+        Ok(Some(backup_path))
+    }
+
+    fn backups(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        list_backups(&self.file_path)
+    }
+
+    fn restore(&mut self, backup: &str) -> Result<(), Box<dyn Error>> {
+        let contents = match fs::read(backup) {
+            Ok(contents) => contents,
+            Err(e) => return Err(Box::from(format!("Could not read {backup}: {e}"))),
+        };
+        // a backup that cannot be read back must not replace a file that can
+        let contents = file_processing::decode(backup, contents, self.cipher.as_ref())?;
+        file_processing::read(self.format, contents.clone())?;
+        // written back under the current passphrase, whichever it was backed up under
+        let contents = file_processing::encode(&contents, self.cipher.as_ref())?;
+        if let Err(e) = file_processing::write_atomically(&self.file_path, contents) {
+            return Err(Box::from(format!(
+                "Failed writing to {}:\n{e}",
+                self.file_path
+            )));
+        }
+        Ok(())
+    }
+
+    fn upgrade(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let contents = file_processing::get_file_contents(&self.file_path, self.cipher.as_ref())?;
+        if !file_processing::needs_migration(self.format, &contents) {
+            return Ok(None);
+        }
+        let ledger = file_processing::read(self.format, contents)?;
+        Ok(Some(file_processing::migrate(
+            &self.file_path,
+            &ledger,
+            self.cipher.as_ref(),
+        )?))
+    }
+
+    fn encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    fn change_passphrase(&mut self, passphrase: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let cipher = Cipher::new(passphrase)?;
+        let changed = self.changed()?;
+        let contents = file_processing::get_file_contents(&self.file_path, self.cipher.as_ref())?;
+        if let Err(e) =
+            file_processing::write_atomically(&self.file_path, cipher.encrypt(&contents)?)
+        {
+            return Err(Box::from(format!(
+                "Failed writing to {}:\n{e}",
+                self.file_path
+            )));
+        }
+        // changes made elsewhere should still be noticed
+        if self.stored.is_some() && !changed {
+            self.stored = Some(self.hash_contents());
+        }
+        let encrypted = encrypt_copies(&self.file_path, &cipher)?;
+        self.cipher = Some(cipher);
+        Ok(encrypted)
+    }
+}
+
+/// Encrypts the backups of the data file, and the copy kept of it when it was converted,
+/// that are not encrypted already, returning where they are
+fn encrypt_copies(file_path: &str, cipher: &Cipher) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut copies = list_backups(file_path)?;
+    copies.push(format!("{file_path}.v1"));
+
+    let mut encrypted = Vec::new();
+    for copy in copies {
+        let contents = match fs::read(&copy) {
+            Ok(contents) if !encryption::is_encrypted(&contents) => contents,
+            _ => continue,
+        };
+        let result = String::from_utf8(contents)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|contents| cipher.encrypt(&contents))
+            .and_then(|contents| Ok(file_processing::write_atomically(&copy, contents)?));
+        if let Err(e) = result {
+            return Err(Box::from(format!("Failed encrypting {copy}: {e}")));
+        }
+        encrypted.push(copy);
+    }
+    Ok(encrypted)
+}
+
+/// Keeps a ledger as CSV in memory, for tests that should not touch the disk
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage {
+    contents: String,
+    /// Backups by name, oldest first
+    backups: Vec<(String, String)>,
+    backups_made: usize,
+}
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn load(&mut self) -> Result<Ledger, Box<dyn Error>> {
+        file_processing::read_from_string(self.contents.clone())
+    }
+
+    fn save(&mut self, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        self.contents = file_processing::write_csv(ledger);
+        Ok(())
+    }
+
+    fn backup(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if self.contents.is_empty() {
+            return Ok(None);
+        }
+        self.backups_made += 1;
+        let name = format!("backup {}", self.backups_made);
+        self.backups.push((name.clone(), self.contents.clone()));
+        if self.backups.len() > BACKUPS_KEPT {
+            self.backups.remove(0);
+        }
+        Ok(Some(name))
+    }
+
+    fn backups(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.backups.iter().map(|(name, _)| name.clone()).collect())
+    }
+
+    fn restore(&mut self, backup: &str) -> Result<(), Box<dyn Error>> {
+        match self.backups.iter().find(|(name, _)| name == backup) {
+            Some((_, contents)) => self.contents = contents.clone(),
+            None => return Err(Box::from(format!("There is no {backup}"))),
+        }
+        Ok(())
+    }
+}
+
+/// Where a backup of the data file made now goes: beside it, named for the time it was made
+pub(super) fn new_backup_path(file_path: &str) -> String {
+    format!(
+        "{file_path}.{}.bak",
+        Local::now().format(BACKUP_TIME_FORMAT)
+    )
+}
+
+/// Backups of the data file beside it, oldest first. Only files named as `new_backup_path`
+/// names them count, so other files that happen to end in ".bak" are left alone.
+pub(super) fn list_backups(file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = Path::new(file_path);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Err(Box::from(format!("{file_path} is not a file"))),
+    };
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.strip_prefix(&prefix).and_then(backup_time).is_some() {
+            backups.push(path.with_file_name(name).to_string_lossy().into_owned());
+        }
+    }
+    // the timestamps sort in the order the backups were made
+    backups.sort();
+    Ok(backups)
+}
+
+/// When a backup was made, read from the end of its name after the data file's, if it is
+/// named as `new_backup_path` names them
+pub(super) fn backup_time(name_end: &str) -> Option<NaiveDateTime> {
+    let time = name_end.strip_suffix(".bak")?;
+    NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT)
+        .ok()
+        .filter(|parsed| parsed.format(BACKUP_TIME_FORMAT).to_string() == time)
+}
+
+/// Removes the oldest backups of the data file until only `BACKUPS_KEPT` are left
+pub(super) fn remove_old_backups(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let backups = list_backups(file_path)?;
+    for backup in &backups[..backups.len().saturating_sub(BACKUPS_KEPT)] {
+        if let Err(e) = fs::remove_file(backup) {
+            return Err(Box::from(format!(
+                "Failed removing old backup {backup}: {e}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn accounts_between(
+    ledger: Ledger,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<HashMap<String, Account>, Box<dyn Error>> {
+    let mut accounts = HashMap::new();
+    for (key, account) in ledger.accounts {
+        accounts.insert(key, copy_between(&account, start, end)?);
+    }
+    Ok(accounts)
+}
+
+/// A copy of an account holding only its transactions dated between `start` and `end`
+fn copy_between(
+    account: &Account,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Account, Box<dyn Error>> {
+    let mut copy = Account::build(account.name())?;
+    for t in account
+        .transactions()
+        .values()
+        .filter(|t| (start..=end).contains(t.date()))
+    {
+        copy.insert_transaction(t.id(), t.label(), *t.amount(), *t.date())?;
+        copy.edit_transaction_category(t.id(), t.category().map(String::from))?;
+        copy.set_transaction_import_id(t.id(), t.import_id().map(String::from))?;
+        copy.link_transaction(t.id(), t.transfer().cloned())?;
+    }
+    copy.set_next_id(account.next_id());
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::encryption::Cipher;
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::storage::*;
+
+    #[test]
+    fn file_storage_test() {
+        let file_path = "src/test-files/storage-test.csv";
+        let mut account = Account::build("Savings").unwrap();
+        for (day, amount) in [
+            ("2024-04-30", 100),
+            ("2024-05-01", 200),
+            ("2024-05-31", 300),
+        ] {
+            account
+                .add_transaction("a", Money::new(amount, 2), day.parse().unwrap())
+                .unwrap();
+        }
+
+        let _ = fs::remove_file(file_path);
+        for backup in list_backups(file_path).unwrap() {
+            fs::remove_file(backup).unwrap();
+        }
+
+        let mut storage = open(file_path, FileFormat::Csv, None).unwrap();
+        storage
+            .save(&Ledger::from(HashMap::from([(
+                String::from("savings"),
+                account,
+            )])))
+            .unwrap();
+        assert!(storage.upgrade().unwrap().is_none());
+        assert_eq!(storage.load().unwrap().accounts.len(), 1);
+
+        let accounts = storage
+            .accounts_between("2024-05-01".parse().unwrap(), "2024-05-31".parse().unwrap())
+            .unwrap();
+        let savings = accounts.get("savings").unwrap();
+        assert_eq!(savings.transactions().len(), 2);
+        assert_eq!(savings.balance(), &Money::new(500, 2));
+
+        let backup = storage.backup().unwrap().unwrap();
+        assert!(backup.starts_with("src/test-files/storage-test.csv."));
+        assert_eq!(storage.backups().unwrap(), vec![backup.clone()]);
+        assert_eq!(
+            fs::read_to_string(&backup).unwrap(),
+            fs::read_to_string(file_path).unwrap()
+        );
+
+        storage.save(&Ledger::default()).unwrap();
+        storage.restore(&backup).unwrap();
+        assert!(storage.changed().unwrap());
+        assert_eq!(storage.load().unwrap().accounts.len(), 1);
+        assert!(!storage.changed().unwrap());
+    }
+
+    #[test]
+    fn old_backups_removed() {
+        let file_path = "src/test-files/backup-test.csv";
+        for backup in list_backups(file_path).unwrap() {
+            fs::remove_file(backup).unwrap();
+        }
+
+        let _ = fs::remove_file(file_path);
+
+        let mut storage = open(file_path, FileFormat::Csv, None).unwrap();
+        assert!(storage.backup().unwrap().is_none());
+
+        let mut made = Vec::new();
+        for name in ["a", "b", "c", "d", "e", "f", "g"] {
+            let account = Account::build(name).unwrap();
+            storage
+                .save(&Ledger::from(HashMap::from([(
+                    String::from(name),
+                    account,
+                )])))
+                .unwrap();
+            made.push(storage.backup().unwrap().unwrap());
+        }
+
+        assert_eq!(
+            storage.backups().unwrap(),
+            made[made.len() - BACKUPS_KEPT..]
+        );
+        // only files named as backups are, so a copy made by hand is never removed
+        for name in ["old", "2024-05-25", "2024-05-25T18-30-00.1.extra"] {
+            let own = format!("{file_path}.{name}.bak");
+            fs::write(&own, "").unwrap();
+            storage.backup().unwrap();
+            assert!(!storage.backups().unwrap().contains(&own));
+            assert!(fs::metadata(&own).is_ok());
+            fs::remove_file(own).unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn restore_unreadable_backup() {
+        let file_path = "src/test-files/restore-test.csv";
+        let mut storage = open(file_path, FileFormat::Csv, None).unwrap();
+        storage.restore("src/test-files/bad-end.csv").unwrap();
+    }
+
+    #[test]
+    fn encrypted_storage() {
+        let file_path = "src/test-files/encrypted-test.json";
+        let _ = fs::remove_file(file_path);
+        let cipher = Cipher::new("correct horse").unwrap();
+        let mut storage = open(file_path, FileFormat::Json, Some(cipher)).unwrap();
+        storage
+            .save(&Ledger::from(HashMap::from([(
+                String::from("savings"),
+                Account::build("Savings").unwrap(),
+            )])))
+            .unwrap();
+
+        let contents = fs::read(file_path).unwrap();
+        assert!(crate::encryption::is_encrypted(&contents));
+        assert!(open(file_path, FileFormat::Json, None)
+            .unwrap()
+            .load()
+            .is_err());
+
+        storage.change_passphrase("battery staple").unwrap();
+        assert!(!storage.changed().unwrap());
+        assert_eq!(storage.load().unwrap().accounts.len(), 1);
+
+        let contents = fs::read(file_path).unwrap();
+        assert!(Cipher::open("correct horse", &contents).is_err());
+        let cipher = Cipher::open("battery staple", &contents).unwrap();
+        let mut reopened = open(file_path, FileFormat::Json, Some(cipher)).unwrap();
+        assert_eq!(reopened.load().unwrap().accounts.len(), 1);
+
+        assert!(open(
+            "src/test-files/encrypted-test.db",
+            FileFormat::Sqlite,
+            Some(Cipher::new("a").unwrap())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn memory_storage_test() {
+        let mut storage = MemoryStorage::default();
+        assert!(storage.load().unwrap().accounts.is_empty());
+
+        let savings = Account::build("Savings").unwrap();
+        storage
+            .save(&Ledger::from(HashMap::from([(
+                String::from("savings"),
+                savings,
+            )])))
+            .unwrap();
+        storage.backup().unwrap();
+        storage.save(&Ledger::default()).unwrap();
+
+        assert!(storage.load().unwrap().accounts.is_empty());
+        assert_eq!(storage.backups().unwrap(), vec!["backup 1"]);
+
+        storage.restore("backup 1").unwrap();
+        assert_eq!(storage.load().unwrap().accounts.len(), 1);
+        assert!(storage.restore("backup 2").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+
+/// Splits a command into words at spaces, except where they are quoted or escaped.
+/// Within `'` everything is taken as typed. Within `"`, and outside quotes, `\` takes the
+/// quote, space or `\` after it as typed, and is kept before anything else, such as in paths.
+/// `""` gives an empty word.
+pub fn tokenize(line: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = Vec::new();
+    // None between words, so that quotes with nothing in them still make a word
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => match chars.next() {
+                Some(escaped) => escape(word.get_or_insert_with(String::new), escaped, true),
+                None => return Err(Box::from("Nothing follows the \\ at the end")),
+            },
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    let quoted = match chars.next() {
+                        Some(quoted) => quoted,
+                        None => return Err(Box::from(format!("Missing the {c} to end a quote"))),
+                    };
+                    match quoted {
+                        end if end == c => break,
+                        // a \ at the very end leaves the quote unfinished
+                        '\\' if c == '"' => {
+                            if let Some(escaped) = chars.next() {
+                                escape(word, escaped, false)
+                            }
+                        }
+                        quoted => word.push(quoted),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Adds a character following `\`, keeping the `\` unless it escapes something special
+fn escape(word: &mut String, escaped: char, outside_quotes: bool) {
+    let special = matches!(escaped, '"' | '\\')
+        || outside_quotes && (escaped == '\'' || escaped.is_whitespace());
+    if !special {
+        word.push('\\');
+    }
+    word.push(escaped);
+}
+
+/// Writes a word so that `tokenize` reads it back as one, quoting it if it has to be
+pub fn quote(word: &str) -> String {
+    if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        return String::from(word);
+    }
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tokenizer::{quote, tokenize};
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line).unwrap()
+    }
+
+    #[test]
+    fn tokenize_test() {
+        assert_eq!(
+            words("at  savings coffee -4.5 "),
+            ["at", "savings", "coffee", "-4.5"]
+        );
+        assert_eq!(
+            words(r#"at "credit card" 'Weekly groceries' -80"#),
+            ["at", "credit card", "Weekly groceries", "-80"]
+        );
+        assert_eq!(
+            words(r#"etl savings 1 "say \"hi\"" Dinner\ out"#),
+            ["etl", "savings", "1", "say \"hi\"", "Dinner out"]
+        );
+        assert_eq!(words(r#"etc savings 1 """#), ["etc", "savings", "1", ""]);
+        assert_eq!(words(r#"a"b c"d 'it''s'"#), ["ab cd", "its"]);
+        // backslashes before anything else are kept, as in paths
+        assert_eq!(
+            words(r#"qe C:\exports\all.qif "C:\new folder\a.qif""#),
+            ["qe", r"C:\exports\all.qif", r"C:\new folder\a.qif"]
+        );
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn tokenize_unterminated_quote() {
+        tokenize(r#"at savings "iced coffee -4.5"#).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn tokenize_trailing_escape() {
+        tokenize(r"at savings coffee\").unwrap();
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        for word in ["Coffee", "Iced Coffee", "say \"hi\"", r"C:\a b", "it's", ""] {
+            assert_eq!(words(&quote(word)), [word]);
+        }
+        assert_eq!(quote("Coffee"), "Coffee");
+        assert_eq!(quote("Iced Coffee"), "\"Iced Coffee\"");
+    }
+}
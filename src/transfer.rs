@@ -0,0 +1,474 @@
+use crate::account::{Account, Transaction, TransferLink};
+use crate::money::Money;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Moves money between two accounts as a pair of linked transactions.
+/// Either both sides are recorded or neither is.
+pub fn transfer(
+    accounts: &mut HashMap<String, Account>,
+    from: &str,
+    to: &str,
+    amount: Money,
+    date: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
+    if from == to {
+        return Err(Box::from("Cannot transfer from an account to itself"));
+    }
+    if amount.is_negative() || amount.is_zero() {
+        return Err(Box::from(format!(
+            "Transfer amount must be positive, not {amount}"
+        )));
+    }
+
+    let from_name = String::from(get_account(accounts, from)?.name());
+    let to_name = String::from(get_account(accounts, to)?.name());
+
+    let from_id = get_account_mut(accounts, from)?.add_transaction(
+        "Transfer",
+        amount.checked_neg()?,
+        date,
+    )?;
+
+    let to_id = match get_account_mut(accounts, to)?.add_transaction("Transfer", amount, date) {
+        Ok(id) => id,
+        Err(e) => {
+            get_account_mut(accounts, from)?.remove_transaction(from_id)?;
+            return Err(e);
+        }
+    };
+
+    get_account_mut(accounts, from)?
+        .link_transaction(from_id, Some(TransferLink::new(&to_name, to_id)))?;
+    get_account_mut(accounts, to)?
+        .link_transaction(to_id, Some(TransferLink::new(&from_name, from_id)))?;
+
+    Ok(())
+}
+
+/// Removes a transaction, along with the other side if it is part of a transfer
+pub fn remove_transaction(
+    accounts: &mut HashMap<String, Account>,
+    account: &str,
+    id: u32,
+) -> Result<(), Box<dyn Error>> {
+    let link = get_link(accounts, account, id)?;
+
+    // both sides are removed from copies first, so that neither is removed if either fails
+    let mut this_account = get_account(accounts, account)?.clone();
+    this_account.remove_transaction(id)?;
+    let other_side = match link {
+        Some((other, other_id)) => {
+            let mut other_account = get_account(accounts, &other)?.clone();
+            other_account.remove_transaction(other_id)?;
+            Some((other, other_account))
+        }
+        None => None,
+    };
+
+    accounts.insert(String::from(account), this_account);
+    if let Some((other, other_account)) = other_side {
+        accounts.insert(other, other_account);
+    }
+    Ok(())
+}
+
+/// Changes a transaction's amount, keeping the other side of a transfer equal and opposite
+pub fn edit_transaction_amount(
+    accounts: &mut HashMap<String, Account>,
+    account: &str,
+    id: u32,
+    new_amount: Money,
+) -> Result<(), Box<dyn Error>> {
+    let link = get_link(accounts, account, id)?;
+
+    if link.is_some() && new_amount.is_zero() {
+        return Err(Box::from("Transfer amount cannot be zero"));
+    }
+
+    let old_amount = *get_account(accounts, account)?
+        .transactions()
+        .get(&id)
+        .unwrap()
+        .amount();
+
+    get_account_mut(accounts, account)?.edit_transaction_amount(id, new_amount)?;
+
+    if let Some((other, other_id)) = link {
+        let result = new_amount.checked_neg().and_then(|other_amount| {
+            get_account_mut(accounts, &other)?.edit_transaction_amount(other_id, other_amount)
+        });
+        if let Err(e) = result {
+            get_account_mut(accounts, account)?.edit_transaction_amount(id, old_amount)?;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Changes a transaction's date, moving the other side of a transfer with it
+pub fn edit_transaction_date(
+    accounts: &mut HashMap<String, Account>,
+    account: &str,
+    id: u32,
+    new_date: NaiveDate,
+) -> Result<(), Box<dyn Error>> {
+    let link = get_link(accounts, account, id)?;
+
+    get_account_mut(accounts, account)?.edit_transaction_date(id, new_date)?;
+
+    if let Some((other, other_id)) = link {
+        get_account_mut(accounts, &other)?.edit_transaction_date(other_id, new_date)?;
+    }
+    Ok(())
+}
+
+/// Changes a transaction's label, giving the other side of a transfer the same one
+pub fn edit_transaction_label(
+    accounts: &mut HashMap<String, Account>,
+    account: &str,
+    id: u32,
+    new_label: String,
+) -> Result<(), Box<dyn Error>> {
+    let link = get_link(accounts, account, id)?;
+
+    get_account_mut(accounts, account)?.edit_transaction_label(id, new_label.clone())?;
+
+    if let Some((other, other_id)) = link {
+        get_account_mut(accounts, &other)?.edit_transaction_label(other_id, new_label)?;
+    }
+    Ok(())
+}
+
+/// Turns the far side of every transfer made with an account into an ordinary
+/// transaction, so the account can be removed without leaving broken links
+pub fn unlink_account(
+    accounts: &mut HashMap<String, Account>,
+    account: &str,
+) -> Result<(), Box<dyn Error>> {
+    let links: Vec<(String, u32)> = get_account(accounts, account)?
+        .transactions()
+        .values()
+        .filter_map(|t| t.transfer())
+        .map(|link| (link.account().to_lowercase(), link.id()))
+        .collect();
+
+    for (other, other_id) in links {
+        if let Some(other_account) = accounts.get_mut(&other) {
+            if other_account.transactions().contains_key(&other_id) {
+                other_account.link_transaction(other_id, None)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the other side of a transaction if it is a transfer whose counterpart still exists
+/// and is linked back to it
+fn get_link(
+    accounts: &HashMap<String, Account>,
+    account: &str,
+    id: u32,
+) -> Result<Option<(String, u32)>, Box<dyn Error>> {
+    let transaction = match get_account(accounts, account)?.transactions().get(&id) {
+        Some(t) => t,
+        None => {
+            return Err(Box::from(format!(
+                "Transaction #{} is not present in {}",
+                id, account
+            )))
+        }
+    };
+
+    Ok(transaction.transfer().and_then(|link| {
+        let other = link.account().to_lowercase();
+        let linked_back = |t: &Transaction| {
+            t.transfer()
+                .is_some_and(|back| back.account().to_lowercase() == account && back.id() == id)
+        };
+        match accounts.get(&other)?.transactions().get(&link.id()) {
+            Some(t) if linked_back(t) => Some((other, link.id())),
+            _ => None,
+        }
+    }))
+}
+
+fn get_account<'a>(
+    accounts: &'a HashMap<String, Account>,
+    account: &str,
+) -> Result<&'a Account, Box<dyn Error>> {
+    match accounts.get(account) {
+        Some(a) => Ok(a),
+        None => Err(Box::from(format!("Account name {account} invalid"))),
+    }
+}
+
+fn get_account_mut<'a>(
+    accounts: &'a mut HashMap<String, Account>,
+    account: &str,
+) -> Result<&'a mut Account, Box<dyn Error>> {
+    match accounts.get_mut(account) {
+        Some(a) => Ok(a),
+        None => Err(Box::from(format!("Account name {account} invalid"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::Account;
+    use crate::money::Money;
+    use crate::transfer::*;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    fn form_accounts() -> HashMap<String, Account> {
+        HashMap::from([
+            (String::from("savings"), Account::build("Savings").unwrap()),
+            (
+                String::from("checking"),
+                Account::build("Checking").unwrap(),
+            ),
+        ])
+    }
+
+    fn date() -> NaiveDate {
+        "2024-05-25".parse().unwrap()
+    }
+
+    #[test]
+    fn transfer_test() {
+        let mut accounts = form_accounts();
+
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(5000, 2),
+            date(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            &format!("{}", accounts.get("savings").unwrap()),
+            "Name: Savings | Balance: $-50.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Transfer | Amount: $-50.00 | Transfer to Checking #1"
+        );
+        assert_eq!(
+            &format!("{}", accounts.get("checking").unwrap()),
+            "Name: Checking | Balance: $50.00\n\
+            Transactions:\n\
+            ID: 1 | Date: 25 May 2024 | Label: Transfer | Amount: $50.00 | Transfer from Savings #1"
+        );
+    }
+
+    #[test]
+    fn transfer_invalid() {
+        let mut accounts = form_accounts();
+
+        assert!(transfer(
+            &mut accounts,
+            "savings",
+            "savings",
+            Money::new(1, 2),
+            date()
+        )
+        .is_err());
+        assert!(transfer(
+            &mut accounts,
+            "savings",
+            "expenses",
+            Money::new(1, 2),
+            date()
+        )
+        .is_err());
+        assert!(transfer(&mut accounts, "savings", "checking", Money::zero(), date()).is_err());
+        assert!(transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(-1, 2),
+            date()
+        )
+        .is_err());
+
+        assert!(accounts.get("savings").unwrap().transactions().is_empty());
+        assert!(accounts.get("checking").unwrap().transactions().is_empty());
+    }
+
+    #[test]
+    fn transfer_rolls_back() {
+        let mut accounts = form_accounts();
+        accounts
+            .get_mut("checking")
+            .unwrap()
+            .add_transaction("Big", Money::new(i64::MAX, 2), date())
+            .unwrap();
+
+        assert!(transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(1, 2),
+            date()
+        )
+        .is_err());
+
+        assert!(accounts.get("savings").unwrap().transactions().is_empty());
+        assert_eq!(accounts.get("savings").unwrap().balance(), &Money::zero());
+        assert_eq!(accounts.get("checking").unwrap().transactions().len(), 1);
+    }
+
+    #[test]
+    fn remove_either_side() {
+        let mut accounts = form_accounts();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(100, 2),
+            date(),
+        )
+        .unwrap();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(100, 2),
+            date(),
+        )
+        .unwrap();
+
+        remove_transaction(&mut accounts, "savings", 1).unwrap();
+        remove_transaction(&mut accounts, "checking", 2).unwrap();
+
+        for account in accounts.values() {
+            assert!(account.transactions().is_empty());
+            assert_eq!(account.balance(), &Money::zero());
+        }
+    }
+
+    #[test]
+    fn edit_both_sides() {
+        let mut accounts = form_accounts();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(100, 2),
+            date(),
+        )
+        .unwrap();
+
+        edit_transaction_amount(&mut accounts, "checking", 1, Money::new(250, 2)).unwrap();
+        let new_date: NaiveDate = "2024-05-26".parse().unwrap();
+        edit_transaction_date(&mut accounts, "savings", 1, new_date).unwrap();
+
+        let savings = accounts
+            .get("savings")
+            .unwrap()
+            .transactions()
+            .get(&1)
+            .unwrap();
+        let checking = accounts
+            .get("checking")
+            .unwrap()
+            .transactions()
+            .get(&1)
+            .unwrap();
+        assert_eq!(savings.amount(), &Money::new(-250, 2));
+        assert_eq!(checking.amount(), &Money::new(250, 2));
+        assert_eq!(savings.date(), &new_date);
+        assert_eq!(checking.date(), &new_date);
+
+        edit_transaction_label(&mut accounts, "checking", 1, String::from("Rent Share")).unwrap();
+        assert!(["savings", "checking"]
+            .iter()
+            .all(|key| accounts[*key].transactions()[&1].label() == "Rent Share"));
+        assert!(edit_transaction_label(&mut accounts, "savings", 1, String::new()).is_err());
+        assert_eq!(
+            accounts["checking"].transactions()[&1].label(),
+            "Rent Share"
+        );
+    }
+
+    #[test]
+    fn unlink_removed_account() {
+        let mut accounts = form_accounts();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(100, 2),
+            date(),
+        )
+        .unwrap();
+
+        unlink_account(&mut accounts, "savings").unwrap();
+        accounts.remove("savings");
+
+        let checking = accounts.get("checking").unwrap();
+        assert!(checking
+            .transactions()
+            .get(&1)
+            .unwrap()
+            .transfer()
+            .is_none());
+        assert_eq!(checking.balance(), &Money::new(100, 2));
+    }
+
+    #[test]
+    fn remove_rolls_back() {
+        let mut accounts = form_accounts();
+        let checking = accounts.get_mut("checking").unwrap();
+        checking
+            .add_transaction("Debt", Money::new(i64::MIN, 2), date())
+            .unwrap();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(1, 2),
+            date(),
+        )
+        .unwrap();
+        accounts
+            .get_mut("checking")
+            .unwrap()
+            .add_transaction("Fee", Money::new(-1, 2), date())
+            .unwrap();
+
+        assert!(remove_transaction(&mut accounts, "savings", 1).is_err());
+
+        assert_eq!(accounts.get("savings").unwrap().transactions().len(), 1);
+        assert_eq!(
+            accounts.get("savings").unwrap().balance(),
+            &Money::new(-1, 2)
+        );
+        assert_eq!(accounts.get("checking").unwrap().transactions().len(), 3);
+    }
+
+    #[test]
+    fn remove_keeps_unlinked_partner() {
+        let mut accounts = form_accounts();
+        transfer(
+            &mut accounts,
+            "savings",
+            "checking",
+            Money::new(100, 2),
+            date(),
+        )
+        .unwrap();
+        accounts
+            .get_mut("checking")
+            .unwrap()
+            .link_transaction(1, None)
+            .unwrap();
+
+        remove_transaction(&mut accounts, "savings", 1).unwrap();
+
+        assert!(accounts.get("savings").unwrap().transactions().is_empty());
+        assert_eq!(accounts.get("checking").unwrap().transactions().len(), 1);
+    }
+}
@@ -0,0 +1,993 @@
+use crate::account::{Account, Transaction};
+use crate::history::{self, History};
+use crate::input_processing::LabelCase;
+use crate::ledger::Ledger;
+use crate::money::Money;
+use crate::{commands, perform, tokenizer, transfer, Session};
+use chrono::{Local, NaiveDate};
+use convert_case::{Case, Casing};
+use itertools::Itertools;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{
+    Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+};
+use ratatui::{DefaultTerminal, Frame};
+use std::error::Error;
+
+/// How many rows Page Up and Page Down move through the transactions
+const PAGE: usize = 10;
+
+const BROWSE_KEYS: &str = "←/→ account  ↑/↓ transaction  a add  e edit  d delete  t transfer  \
+    n new account  D remove account  u undo  r redo  s save  : command  q save and quit  Q quit";
+
+/// What the keyboard is being used for
+enum Mode {
+    Browse,
+    /// Typing a menu command after `:`
+    Command(String),
+    Form(Form),
+    /// Waiting for `y` before removing something
+    Confirm(Removal),
+    /// Showing what a command had to say over the transactions, scrolled down this many lines
+    Output {
+        text: String,
+        scroll: u16,
+    },
+}
+
+enum Removal {
+    Transaction(u32),
+    Account,
+}
+
+enum FormKind {
+    AddAccount,
+    AddTransaction,
+    EditTransaction(u32),
+    Transfer,
+}
+
+/// Fields to fill in, each a name and what has been typed
+struct Form {
+    kind: FormKind,
+    fields: Vec<(&'static str, String)>,
+    focus: usize,
+}
+
+impl Form {
+    fn new(kind: FormKind, fields: Vec<(&'static str, String)>) -> Form {
+        Form {
+            kind,
+            fields,
+            focus: 0,
+        }
+    }
+
+    fn value(&self, index: usize) -> &str {
+        self.fields[index].1.trim()
+    }
+
+    fn title(&self) -> &str {
+        match self.kind {
+            FormKind::AddAccount => " New account ",
+            FormKind::AddTransaction => " Add transaction ",
+            FormKind::EditTransaction(_) => " Edit transaction ",
+            FormKind::Transfer => " Transfer ",
+        }
+    }
+}
+
+/// What to do once a key has been handled
+enum Action {
+    Continue,
+    Quit,
+    /// Leave the screen to run a command that asks for input itself, such as `pw`
+    Suspend(String),
+}
+
+struct App {
+    mode: Mode,
+    /// The outcome of the last thing done, shown along the bottom
+    status: String,
+    /// Position of the selected account, in name order
+    account: usize,
+    transactions: TableState,
+    history: History,
+    unsaved: bool,
+}
+
+/// Shows the ledger full screen until quit, with `status` to begin with
+pub(super) fn run(
+    session: &mut Session,
+    ledger: &mut Ledger,
+    status: String,
+    unsaved: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(status, unsaved);
+    app.select_last(ledger);
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal, session, ledger);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(status: String, unsaved: bool) -> App {
+        App {
+            mode: Mode::Browse,
+            status,
+            account: 0,
+            transactions: TableState::default(),
+            history: History::default(),
+            unsaved,
+        }
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        session: &mut Session,
+        ledger: &mut Ledger,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame, ledger))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            match self.handle_key(key, session, ledger) {
+                Action::Continue => {}
+                Action::Quit => return Ok(()),
+                Action::Suspend(entered) => {
+                    ratatui::restore();
+                    let result = self.perform(&entered, session, ledger);
+                    *terminal = ratatui::init();
+                    self.show(result);
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, session: &mut Session, ledger: &mut Ledger) -> Action {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => return self.browse(key, session, ledger),
+            Mode::Command(mut entered) => match key.code {
+                KeyCode::Enter => return self.command(entered.trim(), session, ledger),
+                KeyCode::Esc => {}
+                KeyCode::Backspace => {
+                    entered.pop();
+                    self.mode = Mode::Command(entered);
+                }
+                KeyCode::Char(c) => {
+                    entered.push(c);
+                    self.mode = Mode::Command(entered);
+                }
+                _ => self.mode = Mode::Command(entered),
+            },
+            Mode::Form(mut form) => match key.code {
+                KeyCode::Enter => match self.submit(&form, session, ledger) {
+                    Ok(message) => self.status = message,
+                    Err(e) => {
+                        self.status = format!("Error: {e}");
+                        self.mode = Mode::Form(form);
+                    }
+                },
+                KeyCode::Esc => self.status = String::new(),
+                code => {
+                    match code {
+                        KeyCode::Tab | KeyCode::Down => {
+                            form.focus = (form.focus + 1) % form.fields.len()
+                        }
+                        KeyCode::BackTab | KeyCode::Up => {
+                            form.focus = (form.focus + form.fields.len() - 1) % form.fields.len()
+                        }
+                        KeyCode::Backspace => {
+                            form.fields[form.focus].1.pop();
+                        }
+                        KeyCode::Char(c) => form.fields[form.focus].1.push(c),
+                        _ => {}
+                    }
+                    self.mode = Mode::Form(form);
+                }
+            },
+            Mode::Confirm(removal) => {
+                self.status = match key.code {
+                    KeyCode::Char('y') => match self.remove(removal, session, ledger) {
+                        Ok(message) => message,
+                        Err(e) => format!("Error: {e}"),
+                    },
+                    _ => String::new(),
+                }
+            }
+            Mode::Output { text, scroll } => {
+                let scroll = match key.code {
+                    KeyCode::Up => scroll.saturating_sub(1),
+                    KeyCode::Down => scroll.saturating_add(1),
+                    KeyCode::PageUp => scroll.saturating_sub(PAGE as u16),
+                    KeyCode::PageDown => scroll.saturating_add(PAGE as u16),
+                    _ => return Action::Continue,
+                };
+                self.mode = Mode::Output { text, scroll };
+            }
+        }
+        Action::Continue
+    }
+
+    fn browse(&mut self, key: KeyEvent, session: &mut Session, ledger: &mut Ledger) -> Action {
+        let today = NaiveDate::from(Local::now().naive_local()).to_string();
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                match self.unsaved {
+                    true => self.status = String::from(
+                        "There are unsaved changes: q saves them and quits, Q quits without saving",
+                    ),
+                    false => return Action::Quit,
+                }
+            }
+            KeyCode::Char('q') => return self.command("q", session, ledger),
+            KeyCode::Char('Q') => return Action::Quit,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(ledger, -1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(ledger, 1),
+            KeyCode::PageUp => self.move_selection(ledger, -(PAGE as isize)),
+            KeyCode::PageDown => self.move_selection(ledger, PAGE as isize),
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::BackTab => self.move_account(ledger, -1),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => self.move_account(ledger, 1),
+            KeyCode::Char('n') => {
+                self.mode = Mode::Form(Form::new(
+                    FormKind::AddAccount,
+                    vec![("Name", String::new())],
+                ))
+            }
+            _ if accounts(ledger).is_empty() => {
+                self.status = String::from("Add an account first by pressing n")
+            }
+            KeyCode::Char('a') => {
+                self.mode = Mode::Form(Form::new(
+                    FormKind::AddTransaction,
+                    vec![
+                        ("Label", String::new()),
+                        ("Amount", String::new()),
+                        ("Date", today),
+                        ("Category", String::new()),
+                    ],
+                ))
+            }
+            KeyCode::Char('t') => {
+                self.mode = Mode::Form(Form::new(
+                    FormKind::Transfer,
+                    vec![
+                        ("To account", String::new()),
+                        ("Amount", String::new()),
+                        ("Date", today),
+                    ],
+                ))
+            }
+            KeyCode::Char('D') => {
+                self.status = format!(
+                    "Remove account {}? y to confirm, any other key to cancel",
+                    accounts(ledger)[self.account].name()
+                );
+                self.mode = Mode::Confirm(Removal::Account);
+            }
+            KeyCode::Char('e') | KeyCode::Char('d') => match self.selected(ledger) {
+                Some(transaction) if key.code == KeyCode::Char('e') => {
+                    self.mode = Mode::Form(Form::new(
+                        FormKind::EditTransaction(transaction.id()),
+                        vec![
+                            ("Label", String::from(transaction.label())),
+                            ("Amount", transaction.amount().to_string()),
+                            ("Date", transaction.date().to_string()),
+                            (
+                                "Category",
+                                String::from(transaction.category().unwrap_or("")),
+                            ),
+                        ],
+                    ))
+                }
+                Some(transaction) => {
+                    self.status = format!(
+                        "Remove {} (#{})? y to confirm, any other key to cancel",
+                        transaction.label(),
+                        transaction.id()
+                    );
+                    self.mode = Mode::Confirm(Removal::Transaction(transaction.id()));
+                }
+                None => self.status = String::from("There is no transaction selected"),
+            },
+            KeyCode::Char('u') => return self.command("undo", session, ledger),
+            KeyCode::Char('r') => return self.command("redo", session, ledger),
+            KeyCode::Char('s') => return self.command("s", session, ledger),
+            KeyCode::Char(':') => self.mode = Mode::Command(String::new()),
+            KeyCode::Esc => self.status = String::new(),
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Carries out a command typed after `:` as the menu would
+    fn command(&mut self, entered: &str, session: &mut Session, ledger: &mut Ledger) -> Action {
+        let word = match tokenizer::tokenize(entered) {
+            Ok(words) => words.into_iter().next().unwrap_or_default(),
+            Err(e) => {
+                self.status = format!("Error: {e}");
+                return Action::Continue;
+            }
+        };
+        match word.as_str() {
+            "" => {}
+            "q" if session.check_writable().is_err() => return Action::Quit,
+            "q" => match session.save(ledger) {
+                Ok(()) => return Action::Quit,
+                Err(e) => self.status = format!("Error: {e}"),
+            },
+            "q!" => return Action::Quit,
+            _ => match commands::find(&word).map(|command| command.code) {
+                Some("pw") => return Action::Suspend(String::from(entered)),
+                Some(_) => {
+                    let result = self.perform(entered, session, ledger);
+                    self.show(result);
+                }
+                None => self.status = String::from("Please enter a valid input"),
+            },
+        }
+        Action::Continue
+    }
+
+    fn perform(
+        &mut self,
+        entered: &str,
+        session: &mut Session,
+        ledger: &mut Ledger,
+    ) -> Result<String, Box<dyn Error>> {
+        let result = perform(
+            entered,
+            session,
+            ledger,
+            &mut self.history,
+            &mut self.unsaved,
+        );
+        self.clamp_selection(ledger);
+        result
+    }
+
+    /// Puts a command's outcome in the status line, or over the transactions if it is long
+    fn show(&mut self, result: Result<String, Box<dyn Error>>) {
+        match result {
+            Ok(message) if message.contains('\n') => {
+                self.status = String::new();
+                self.mode = Mode::Output {
+                    text: message,
+                    scroll: 0,
+                };
+            }
+            Ok(message) => self.status = message,
+            Err(e) => self.status = format!("Error: {e}"),
+        }
+    }
+
+    /// Makes the changes a form describes, keeping the ledger as it was if any fail
+    fn submit(
+        &mut self,
+        form: &Form,
+        session: &mut Session,
+        ledger: &mut Ledger,
+    ) -> Result<String, Box<dyn Error>> {
+        let before = ledger.clone();
+        let result = match form.kind {
+            FormKind::AddAccount => self.add_account(form, ledger),
+            FormKind::AddTransaction => self.add_transaction(form, session.label_case, ledger),
+            FormKind::EditTransaction(id) => {
+                return self.edit_transaction(form, id, session, ledger);
+            }
+            FormKind::Transfer => self.add_transfer(form, ledger),
+        };
+        match result {
+            Ok((entered, message)) => {
+                self.record(&entered, &before, session, ledger)?;
+                Ok(message)
+            }
+            Err(e) => {
+                *ledger = before;
+                Err(e)
+            }
+        }
+    }
+
+    fn remove(
+        &mut self,
+        removal: Removal,
+        session: &mut Session,
+        ledger: &mut Ledger,
+    ) -> Result<String, Box<dyn Error>> {
+        let before = ledger.clone();
+        let key = self.account_key(ledger);
+        let (entered, message) = match removal {
+            Removal::Transaction(id) => {
+                transfer::remove_transaction(&mut ledger.accounts, &key, id)?;
+                (
+                    command_text(&["rt", &key, &id.to_string()]),
+                    format!("Removed transaction #{id}"),
+                )
+            }
+            Removal::Account => {
+                let name = String::from(ledger.accounts[&key].name());
+                transfer::unlink_account(&mut ledger.accounts, &key)?;
+                ledger.accounts.remove(&key);
+                (
+                    command_text(&["ra", &name]),
+                    format!("Removed account {name}"),
+                )
+            }
+        };
+        self.record(&entered, &before, session, ledger)?;
+        self.clamp_selection(ledger);
+        Ok(message)
+    }
+
+    /// Notes what a form changed in the journal and the history, as `perform` does for commands
+    fn record(
+        &mut self,
+        entered: &str,
+        before: &Ledger,
+        session: &mut Session,
+        ledger: &Ledger,
+    ) -> Result<(), Box<dyn Error>> {
+        let changes = history::changes_between(before, ledger);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.unsaved = true;
+        session.record(entered, &changes, ledger)?;
+        self.history.record(entered, changes);
+        Ok(())
+    }
+
+    fn add_account(
+        &mut self,
+        form: &Form,
+        ledger: &mut Ledger,
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let name = form.value(0).to_case(Case::Title);
+        if ledger.accounts.contains_key(&name.to_lowercase()) {
+            return Err(Box::from(format!("Account {name} already exists.")));
+        }
+        ledger
+            .accounts
+            .insert(name.to_lowercase(), Account::build(&name)?);
+
+        self.account = accounts(ledger)
+            .iter()
+            .position(|account| account.name() == name)
+            .unwrap_or_default();
+        self.select_last(ledger);
+        Ok((
+            command_text(&["aa", &name]),
+            format!("Added account {name}"),
+        ))
+    }
+
+    fn add_transaction(
+        &mut self,
+        form: &Form,
+        case: LabelCase,
+        ledger: &mut Ledger,
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let label = case.apply(form.value(0));
+        let amount = parse_amount(form.value(1))?;
+        let date = parse_date(form.value(2))?;
+        let category = parse_category(form.value(3));
+
+        let key = self.account_key(ledger);
+        let account = get_account_mut(ledger, &key)?;
+        let id = account.add_transaction(&label, amount, date)?;
+        account.edit_transaction_category(id, category.clone())?;
+
+        let (amount, date) = (amount.to_string(), date.to_string());
+        let mut words = vec!["atd", account.name(), &label, &amount, &date];
+        words.extend(category.as_deref());
+        let entered = command_text(&words);
+        self.select(ledger, id);
+        Ok((entered, format!("Added {label} as #{id}")))
+    }
+
+    /// Changes whichever of the transaction's details were changed in the form, noting each
+    /// as the edit command that makes it. Nothing is changed if any of them fail.
+    fn edit_transaction(
+        &mut self,
+        form: &Form,
+        id: u32,
+        session: &mut Session,
+        ledger: &mut Ledger,
+    ) -> Result<String, Box<dyn Error>> {
+        let before = ledger.clone();
+        let steps = match Self::edit_steps(form, id, &self.account_key(ledger), session, ledger) {
+            Ok(steps) => steps,
+            Err(e) => {
+                *ledger = before;
+                return Err(e);
+            }
+        };
+
+        let mut previous = before;
+        for (entered, after) in steps {
+            self.record(&entered, &previous, session, &after)?;
+            previous = after;
+        }
+        self.select(ledger, id);
+        Ok(format!("Changed #{id}"))
+    }
+
+    /// Makes each change to the transaction in turn, giving the command for it and the
+    /// ledger as it was after it
+    fn edit_steps(
+        form: &Form,
+        id: u32,
+        key: &str,
+        session: &Session,
+        ledger: &mut Ledger,
+    ) -> Result<Vec<(String, Ledger)>, Box<dyn Error>> {
+        let label = session.label_case.apply(form.value(0));
+        let amount = parse_amount(form.value(1))?;
+        let date = parse_date(form.value(2))?;
+        let category = parse_category(form.value(3));
+
+        let transaction = match get_account_mut(ledger, key)?.transactions().get(&id) {
+            Some(transaction) => transaction.clone(),
+            None => return Err(Box::from(format!("Transaction #{id} no longer exists"))),
+        };
+        let id_text = id.to_string();
+        let mut steps = Vec::new();
+
+        if label != transaction.label() {
+            transfer::edit_transaction_label(&mut ledger.accounts, key, id, label.clone())?;
+            steps.push((
+                command_text(&["etl", key, &id_text, &label]),
+                ledger.clone(),
+            ));
+        }
+        if amount != *transaction.amount() {
+            transfer::edit_transaction_amount(&mut ledger.accounts, key, id, amount)?;
+            let amount = amount.to_string();
+            steps.push((
+                command_text(&["eta", key, &id_text, &amount]),
+                ledger.clone(),
+            ));
+        }
+        if date != *transaction.date() {
+            transfer::edit_transaction_date(&mut ledger.accounts, key, id, date)?;
+            let date = date.to_string();
+            steps.push((command_text(&["etd", key, &id_text, &date]), ledger.clone()));
+        }
+        if category.as_deref() != transaction.category() {
+            get_account_mut(ledger, key)?.edit_transaction_category(id, category.clone())?;
+            let category = category.unwrap_or_default();
+            steps.push((
+                command_text(&["etc", key, &id_text, &category]),
+                ledger.clone(),
+            ));
+        }
+        Ok(steps)
+    }
+
+    fn add_transfer(
+        &mut self,
+        form: &Form,
+        ledger: &mut Ledger,
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let to = form.value(0).to_lowercase();
+        let amount = parse_amount(form.value(1))?;
+        let date = parse_date(form.value(2))?;
+
+        let key = self.account_key(ledger);
+        if !ledger.accounts.contains_key(&to) {
+            return Err(Box::from(format!(
+                "Account name {} not present.",
+                form.value(0)
+            )));
+        }
+        transfer::transfer(&mut ledger.accounts, &key, &to, amount, date)?;
+
+        let id = ledger.accounts[&key].next_id() - 1;
+        self.select(ledger, id);
+        Ok((
+            command_text(&["tr", &key, &to, &amount.to_string(), &date.to_string()]),
+            format!("Transferred {amount} to {}", ledger.accounts[&to].name()),
+        ))
+    }
+
+    /// The lowercase name the selected account is kept under
+    fn account_key(&self, ledger: &Ledger) -> String {
+        accounts(ledger)
+            .get(self.account)
+            .map(|account| account.name().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    fn selected<'a>(&self, ledger: &'a Ledger) -> Option<&'a Transaction> {
+        let account = *accounts(ledger).get(self.account)?;
+        transactions(account)
+            .get(self.transactions.selected()?)
+            .copied()
+    }
+
+    fn select(&mut self, ledger: &Ledger, id: u32) {
+        if let Some(account) = accounts(ledger).get(self.account) {
+            let row = transactions(account).iter().position(|t| t.id() == id);
+            self.transactions.select(row);
+        }
+    }
+
+    /// Selects the most recent transaction of the selected account
+    fn select_last(&mut self, ledger: &Ledger) {
+        let count = accounts(ledger)
+            .get(self.account)
+            .map_or(0, |account| account.transactions().len());
+        self.transactions.select(count.checked_sub(1));
+    }
+
+    fn move_account(&mut self, ledger: &Ledger, by: isize) {
+        let count = accounts(ledger).len();
+        if count > 0 {
+            self.account = (self.account as isize + by).rem_euclid(count as isize) as usize;
+            self.select_last(ledger);
+        }
+    }
+
+    fn move_selection(&mut self, ledger: &Ledger, by: isize) {
+        let count = accounts(ledger)
+            .get(self.account)
+            .map_or(0, |account| account.transactions().len());
+        if count > 0 {
+            let row = self.transactions.selected().unwrap_or_default() as isize + by;
+            self.transactions
+                .select(Some(row.clamp(0, count as isize - 1) as usize));
+        }
+    }
+
+    /// Keeps the selection on an account and transaction that exist after accounts or
+    /// transactions have been removed, e.g. by undoing
+    fn clamp_selection(&mut self, ledger: &Ledger) {
+        let count = accounts(ledger).len();
+        if self.account >= count {
+            self.account = count.saturating_sub(1);
+            self.select_last(ledger);
+        } else {
+            self.move_selection(ledger, 0);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, ledger: &Ledger) {
+        let [main, status, keys] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [side, table] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main);
+
+        self.draw_side(frame, side, ledger);
+        self.draw_transactions(frame, table, ledger);
+
+        let status_line = match &self.mode {
+            Mode::Command(entered) => format!(":{entered}"),
+            _ => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+        let hint = match self.mode {
+            Mode::Browse => BROWSE_KEYS,
+            Mode::Command(_) => "Enter to run the command, Esc to cancel",
+            Mode::Form(_) => "Tab/↑/↓ field  Enter save  Esc cancel",
+            Mode::Confirm(_) => "y to confirm, any other key to cancel",
+            Mode::Output { .. } => "↑/↓ scroll  any other key to close",
+        };
+        frame.render_widget(
+            Paragraph::new(hint).style(Style::default().add_modifier(Modifier::DIM)),
+            keys,
+        );
+
+        match &self.mode {
+            Mode::Form(form) => draw_form(frame, form),
+            Mode::Output { text, scroll } => {
+                let area = centered(frame.area(), 90, text.lines().count() as u16 + 2);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(text.as_str())
+                        .scroll((*scroll, 0))
+                        .block(Block::default().borders(Borders::ALL)),
+                    area,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Accounts with their balances, then budgets and recurring transactions if there are any
+    fn draw_side(&self, frame: &mut Frame, area: Rect, ledger: &Ledger) {
+        let today = NaiveDate::from(Local::now().naive_local());
+        let budgets = crate::budget_lines(&ledger.budgets, &ledger.accounts, today);
+        let recurring: Vec<String> = ledger
+            .recurring
+            .iter()
+            .enumerate()
+            .map(|(number, template)| format!("{}. {template}", number + 1))
+            .collect();
+
+        let height = |lines: &[String]| match lines.len() {
+            0 => 0,
+            n => n as u16 + 2,
+        };
+        let [accounts_area, budgets_area, recurring_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Max(height(&budgets)),
+            Constraint::Max(height(&recurring)),
+        ])
+        .areas(area);
+
+        let items: Vec<ListItem> = accounts(ledger)
+            .iter()
+            .map(|account| ListItem::new(format!("{}  ${}", account.name(), account.balance())))
+            .collect();
+        let mut state = ListState::default().with_selected(Some(self.account));
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(" Accounts "))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            accounts_area,
+            &mut state,
+        );
+
+        let month = format!(" Budgets for {} ", today.format("%B %Y"));
+        for (lines, title, area) in [
+            (budgets, month.as_str(), budgets_area),
+            (recurring, " Recurring ", recurring_area),
+        ] {
+            if !lines.is_empty() {
+                frame.render_widget(
+                    Paragraph::new(lines.join("\n"))
+                        .block(Block::default().borders(Borders::ALL).title(title)),
+                    area,
+                );
+            }
+        }
+    }
+
+    fn draw_transactions(&mut self, frame: &mut Frame, area: Rect, ledger: &Ledger) {
+        let account = match accounts(ledger).get(self.account) {
+            Some(account) => *account,
+            None => {
+                frame.render_widget(
+                    Paragraph::new("No accounts yet. Press n to add one.")
+                        .block(Block::default().borders(Borders::ALL)),
+                    area,
+                );
+                return;
+            }
+        };
+
+        let rows = transactions(account).into_iter().map(|t| {
+            Row::new(vec![
+                Cell::from(t.date().format("%d %b %Y").to_string()),
+                Cell::from(Line::from(format!("#{}", t.id())).alignment(Alignment::Right)),
+                Cell::from(t.label()),
+                Cell::from(Line::from(format!("${}", t.amount())).alignment(Alignment::Right)),
+                Cell::from(t.category().unwrap_or_default()),
+                Cell::from(
+                    t.transfer()
+                        .map(|link| format!("{} #{}", link.account(), link.id()))
+                        .unwrap_or_default(),
+                ),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(11),
+                Constraint::Length(6),
+                Constraint::Min(12),
+                Constraint::Length(12),
+                Constraint::Length(16),
+                Constraint::Length(16),
+            ],
+        )
+        .header(
+            Row::new(["Date", "ID", "Label", "Amount", "Category", "Transfer"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " {} | Balance: ${} ",
+            account.name(),
+            account.balance()
+        )))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.transactions);
+    }
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let lines: Vec<Line> = form
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, (name, value))| match index == form.focus {
+            true => Line::from(format!("> {name:<11}{value}_"))
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+            false => Line::from(format!("  {name:<11}{value}")),
+        })
+        .collect();
+
+    let area = centered(frame.area(), 60, lines.len() as u16 + 2);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(form.title())),
+        area,
+    );
+}
+
+/// An area `percent` of the width across and `height` rows high in the middle of `area`
+fn centered(area: Rect, percent: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(percent)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    area
+}
+
+/// Accounts in name order, as listed
+fn accounts(ledger: &Ledger) -> Vec<&Account> {
+    ledger
+        .accounts
+        .values()
+        .sorted_by(|a, b| Ord::cmp(a.name(), b.name()))
+        .collect()
+}
+
+/// An account's transactions oldest first, as listed
+fn transactions(account: &Account) -> Vec<&Transaction> {
+    account
+        .transactions()
+        .values()
+        .sorted_by_key(|t| (*t.date(), t.id()))
+        .collect()
+}
+
+/// Writes out what a form did as the command it is like, to be shown in the history and journal
+fn command_text(words: &[&str]) -> String {
+    words.iter().map(|word| tokenizer::quote(word)).join(" ")
+}
+
+fn get_account_mut<'a>(
+    ledger: &'a mut Ledger,
+    key: &str,
+) -> Result<&'a mut Account, Box<dyn Error>> {
+    match ledger.accounts.get_mut(key) {
+        Some(account) => Ok(account),
+        None => Err(Box::from(format!("Account name {key} invalid"))),
+    }
+}
+
+fn parse_amount(amount: &str) -> Result<Money, Box<dyn Error>> {
+    match amount.parse() {
+        Ok(amount) => Ok(amount),
+        Err(e) => Err(Box::from(format!("Amount entered invalid: {e}"))),
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    match date.parse() {
+        Ok(date) => Ok(date),
+        Err(e) => Err(Box::from(format!("Date entered invalid: {e}"))),
+    }
+}
+
+fn parse_category(category: &str) -> Option<String> {
+    match category {
+        "" => None,
+        category => Some(category.to_case(Case::Title)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ledger::Ledger;
+    use crate::money::Money;
+    use crate::tui::{Action, App, Mode};
+    use crate::{parse_args, Session};
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::Terminal;
+    use std::fs;
+
+    fn press(app: &mut App, session: &mut Session, ledger: &mut Ledger, keys: &[KeyCode]) {
+        for key in keys {
+            let action = app.handle_key(KeyEvent::new(*key, KeyModifiers::NONE), session, ledger);
+            assert!(matches!(action, Action::Continue));
+        }
+    }
+
+    fn typed(text: &str) -> Vec<KeyCode> {
+        text.chars().map(KeyCode::Char).collect()
+    }
+
+    fn screen(app: &mut App, ledger: &Ledger) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame, ledger)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn tui_forms() {
+        let file_path = "src/test-files/tui-test.csv";
+        let _ = fs::remove_file(file_path);
+        let config = parse_args(&[String::from("budget"), String::from(file_path)]).unwrap();
+        let (mut session, _) = Session::open(&config).unwrap();
+        let mut ledger = session.load().unwrap();
+        let mut app = App::new(String::new(), false);
+        let (session, ledger) = (&mut session, &mut ledger);
+
+        press(&mut app, session, ledger, &typed("nsavings"));
+        press(&mut app, session, ledger, &[KeyCode::Enter]);
+        assert_eq!(app.status, "Added account Savings");
+
+        press(&mut app, session, ledger, &typed("aiced coffee"));
+        press(&mut app, session, ledger, &[KeyCode::Tab]);
+        press(&mut app, session, ledger, &typed("-4.5"));
+        press(&mut app, session, ledger, &[KeyCode::Enter]);
+        let savings = &ledger.accounts["savings"];
+        assert_eq!(savings.transactions()[&1].label(), "Iced Coffee");
+        assert_eq!(savings.balance(), &Money::new(-450, 2));
+
+        // a form that cannot be saved stays open with the error shown
+        press(&mut app, session, ledger, &typed("abad"));
+        press(&mut app, session, ledger, &[KeyCode::Tab]);
+        press(&mut app, session, ledger, &typed("lots"));
+        press(&mut app, session, ledger, &[KeyCode::Enter]);
+        assert!(matches!(app.mode, Mode::Form(_)));
+        assert!(app.status.starts_with("Error: Amount entered invalid"));
+        press(&mut app, session, ledger, &[KeyCode::Esc]);
+        assert_eq!(ledger.accounts["savings"].transactions().len(), 1);
+
+        press(&mut app, session, ledger, &typed("e"));
+        press(&mut app, session, ledger, &[KeyCode::Tab]);
+        press(&mut app, session, ledger, &[KeyCode::Backspace; 5]);
+        press(&mut app, session, ledger, &typed("-5"));
+        press(&mut app, session, ledger, &[KeyCode::Enter]);
+        assert_eq!(ledger.accounts["savings"].balance(), &Money::new(-500, 2));
+        assert_eq!(app.history.done().first(), Some(&"eta savings 1 -5.00"));
+
+        let shown = screen(&mut app, ledger);
+        assert!(shown.contains("Savings  $-5.00"));
+        assert!(shown.contains("Iced Coffee"));
+
+        press(&mut app, session, ledger, &typed("dy"));
+        assert!(ledger.accounts["savings"].transactions().is_empty());
+        press(&mut app, session, ledger, &typed("u"));
+        assert_eq!(app.status, "Undid: rt savings 1");
+        assert_eq!(ledger.accounts["savings"].transactions().len(), 1);
+
+        press(&mut app, session, ledger, &typed(":balance"));
+        press(&mut app, session, ledger, &[KeyCode::Enter]);
+        assert_eq!(app.status, "Savings\t-5.00");
+
+        let quit = app.handle_key(
+            KeyEvent::new(KeyCode::Char('Q'), KeyModifiers::NONE),
+            session,
+            ledger,
+        );
+        assert!(matches!(quit, Action::Quit));
+    }
+}